@@ -0,0 +1,243 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{duration_millis, elapsed_since, millis_duration, now, Clock, Expiry, Instant, Policy};
+
+/// A value that fits in 64 bits, as cached by [`AtomicBucket`].
+pub trait AtomicValue: Copy {
+    fn into_bits(self) -> u64;
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! atomic_value {
+    ($($ty:ty),*) => {$(
+        impl AtomicValue for $ty {
+            fn into_bits(self) -> u64 {
+                self as u64
+            }
+
+            fn from_bits(bits: u64) -> Self {
+                bits as $ty
+            }
+        }
+    )*};
+}
+
+atomic_value!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl AtomicValue for bool {
+    fn into_bits(self) -> u64 {
+        u64::from(self)
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        bits != 0
+    }
+}
+
+/// A bucket for a small [`Copy`] value, read without taking a lock.
+///
+/// The value, `hit_count` and the evaporation clock are kept in atomics, so
+/// a call served from the cache is a handful of atomic operations. Only a
+/// refresh takes the internal lock: of several callers finding the value
+/// expired, one runs its task while the others wait for it and are served
+/// its value.
+///
+/// The API is narrower than [`Bucket`](crate::Bucket)'s: no observers,
+/// error handling or async tasks. Calls racing with a refresh may be
+/// judged against the old `hit_count` or age, and so be served the new
+/// value one pour early or late.
+///
+/// # Example
+/// ```
+/// use uchimizu::{AtomicBucket, Policy};
+///
+/// let quota = AtomicBucket::new(Policy::expire_within_counts(2));
+/// assert_eq!(quota.get(), None);
+/// assert_eq!(quota.call(|| 100u64), 100);
+/// assert_eq!(quota.call(|| 200), 100);
+/// assert_eq!(quota.call(|| 300), 100);
+/// assert_eq!(quota.get(), None);
+/// assert_eq!(quota.call(|| 400), 400);
+/// ```
+#[derive(Debug)]
+pub struct AtomicBucket<T, P = Policy> {
+    policy: P,
+    value: AtomicU64,
+    /// The generation, bumped by each refresh, shifted left by one; the low
+    /// bit is set while a value is cached.
+    state: AtomicU64,
+    hit_count: AtomicU64,
+    /// When the evaporation clock started, in milliseconds after `epoch`.
+    initiate_millis: AtomicU64,
+    epoch: Instant,
+    clock: Option<Arc<dyn Clock>>,
+    refresh: Mutex<()>,
+    _value: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, P> AtomicBucket<T, P>
+where
+    T: AtomicValue,
+    P: Expiry,
+{
+    pub fn new(policy: P) -> Self {
+        AtomicBucket {
+            policy,
+            value: AtomicU64::new(0),
+            state: AtomicU64::new(0),
+            hit_count: AtomicU64::new(0),
+            initiate_millis: AtomicU64::new(0),
+            epoch: now(),
+            clock: None,
+            refresh: Mutex::new(()),
+            _value: std::marker::PhantomData,
+        }
+    }
+
+    /// Reads time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.epoch = clock.now();
+        self.initiate_millis = AtomicU64::new(0);
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub fn hit_count(&self) -> u64 {
+        self.hit_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the cached value only if the policy is still remaining.
+    ///
+    /// Like [`Bucket::fresh`](crate::Bucket::fresh), this does not count as
+    /// a pour.
+    pub fn get(&self) -> Option<T> {
+        let state = self.state.load(Ordering::Acquire);
+        if state & 1 == 0 || !self.is_remaining(self.hit_count()) {
+            return None;
+        }
+        Some(self.load())
+    }
+
+    /// Returns the cached value regardless of the policy.
+    pub fn cached(&self) -> Option<T> {
+        let state = self.state.load(Ordering::Acquire);
+        (state & 1 == 1).then(|| self.load())
+    }
+
+    /// Serves the cached value while the policy is remaining, and otherwise
+    /// runs the task and caches its output.
+    ///
+    /// A panicking task leaves the bucket as it was and the panic is
+    /// propagated to the caller.
+    pub fn call<F>(&self, task: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let state = self.state.load(Ordering::Acquire);
+        if state & 1 == 1 {
+            let hit_count = self.hit_count.fetch_add(1, Ordering::Relaxed);
+            if self.is_remaining(hit_count) {
+                return self.load();
+            }
+        }
+        let _guard = self.refresh.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = self.state.load(Ordering::Acquire);
+        if current != state && current & 1 == 1 {
+            // Another caller refreshed the bucket while this one waited.
+            return self.load();
+        }
+        let state = current;
+        let entry = task();
+        self.value.store(entry.into_bits(), Ordering::Relaxed);
+        self.hit_count.store(0, Ordering::Relaxed);
+        self.initiate_millis
+            .store(self.now_millis(), Ordering::Relaxed);
+        self.state
+            .store((state | 1).wrapping_add(2), Ordering::Release);
+        entry
+    }
+
+    /// Drops the cached value, so that the next call runs its task.
+    pub fn refresh(&self) {
+        let _guard = self.refresh.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = self.state.load(Ordering::Acquire);
+        self.hit_count.store(0, Ordering::Relaxed);
+        self.initiate_millis
+            .store(self.now_millis(), Ordering::Relaxed);
+        self.state
+            .store((state & !1).wrapping_add(2), Ordering::Release);
+    }
+
+    fn load(&self) -> T {
+        T::from_bits(self.value.load(Ordering::Relaxed))
+    }
+
+    fn is_remaining(&self, hit_count: u64) -> bool {
+        let initiate = self.initiate_millis.load(Ordering::Relaxed);
+        let age = self.now_millis().saturating_sub(initiate);
+        self.policy.is_remaining(hit_count, millis_duration(age))
+    }
+
+    fn now_millis(&self) -> u64 {
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => now(),
+        };
+        duration_millis(elapsed_since(self.epoch, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Barrier;
+
+    #[test]
+    fn expires_by_time() {
+        let clock = crate::ManualClock::new();
+        let b = AtomicBucket::new(Policy::expire_within_secs(1)).with_clock(clock.clone());
+        assert_eq!(b.call(|| 1u32), 1);
+        clock.advance(millis_duration(999));
+        assert_eq!(b.get(), Some(1));
+        assert_eq!(b.call(|| 2), 1);
+        clock.advance(millis_duration(1));
+        assert_eq!(b.get(), None);
+        assert_eq!(b.cached(), Some(1));
+        assert_eq!(b.call(|| 3), 3);
+        b.refresh();
+        assert_eq!(b.cached(), None);
+        assert_eq!(b.call(|| 4), 4);
+    }
+
+    #[test]
+    fn one_refresh_wins_per_expiry() {
+        let b = Arc::new(AtomicBucket::new(Policy::bottom_less()));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(8));
+        let threads = (0..8)
+            .map(|i| {
+                let (b, runs, barrier) = (b.clone(), runs.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    b.call(|| {
+                        runs.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        i
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        let values = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(values.iter().all(|&v| v == values[0]));
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Datelike, NaiveTime, TimeDelta, Timelike, Utc};
+
+/// A calendar boundary in UTC, see [`Bucket::expire_at_boundary`](crate::Bucket::expire_at_boundary).
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, Copy, PartialEq, Eq))]
+#[cfg_attr(
+    feature = "serde",
+    derive(
+        Debug,
+        Clone,
+        Copy,
+        PartialEq,
+        Eq,
+        serde::Serialize,
+        serde::Deserialize
+    )
+)]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Boundary {
+    /// The start of every hour.
+    Hour,
+    /// Midnight.
+    Day,
+    /// Midnight between Sunday and Monday.
+    Week,
+}
+
+impl Boundary {
+    /// Returns the first boundary strictly after `at`.
+    pub(crate) fn next_after(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = at.date_naive().and_time(NaiveTime::MIN).and_utc();
+        match self {
+            Boundary::Hour => midnight + TimeDelta::hours(i64::from(at.hour()) + 1),
+            Boundary::Day => midnight + TimeDelta::days(1),
+            Boundary::Week => {
+                let monday =
+                    midnight - TimeDelta::days(i64::from(at.weekday().num_days_from_monday()));
+                monday + TimeDelta::weeks(1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn next_boundaries() {
+        // A Wednesday.
+        let at = Utc.with_ymd_and_hms(2024, 5, 15, 13, 30, 0).unwrap();
+        assert_eq!(
+            Boundary::Hour.next_after(at),
+            Utc.with_ymd_and_hms(2024, 5, 15, 14, 0, 0).unwrap()
+        );
+        assert_eq!(
+            Boundary::Day.next_after(at),
+            Utc.with_ymd_and_hms(2024, 5, 16, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            Boundary::Week.next_after(at),
+            Utc.with_ymd_and_hms(2024, 5, 20, 0, 0, 0).unwrap()
+        );
+        let midnight = Utc.with_ymd_and_hms(2024, 5, 20, 0, 0, 0).unwrap();
+        assert_eq!(
+            Boundary::Week.next_after(midnight),
+            Utc.with_ymd_and_hms(2024, 5, 27, 0, 0, 0).unwrap()
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{duration_millis, duration_millis_ceil, elapsed_since, Duration, Instant};
+
+/// Configures the circuit breaker of
+/// [`Bucket::with_breaker`](crate::Bucket::with_breaker).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerConfig {
+    /// How many consecutive task failures open the breaker. Zero is treated
+    /// as one.
+    pub failures: u32,
+    /// How long the breaker stays open before a call may probe the task.
+    pub cooldown: Duration,
+}
+
+impl BreakerConfig {
+    pub fn new(failures: u32, cooldown: Duration) -> BreakerConfig {
+        BreakerConfig { failures, cooldown }
+    }
+}
+
+/// The state of a circuit breaker, as reported by
+/// [`Bucket::breaker_state`](crate::Bucket::breaker_state).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Tasks run as usual.
+    Closed,
+    /// Too many consecutive failures: tasks are not run until the cooldown
+    /// is over.
+    Open,
+    /// The cooldown is over, and the next call runs the task as a probe.
+    /// A success closes the breaker, a failure opens it again.
+    HalfOpen,
+}
+
+/// Counts consecutive failures of a bucket's task.
+///
+/// Only the configuration is persisted: a deserialized bucket starts with
+/// its breaker closed.
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, serde::Serialize, serde::Deserialize)
+)]
+pub(crate) struct Breaker {
+    failures: u32,
+    cooldown_millis: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    consecutive: u32,
+    /// When the breaker last opened; it stays so until a success.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    opened: Option<Instant>,
+}
+
+/// Only the configuration is compared, not the failures counted so far.
+impl PartialEq for Breaker {
+    fn eq(&self, other: &Self) -> bool {
+        self.failures == other.failures && self.cooldown_millis == other.cooldown_millis
+    }
+}
+
+impl Eq for Breaker {}
+
+impl Breaker {
+    /// # Panics
+    /// Panics if the cooldown is negative or too large to be represented.
+    pub(crate) fn new(config: BreakerConfig) -> Breaker {
+        Breaker {
+            failures: config.failures.max(1),
+            cooldown_millis: duration_millis_ceil(config.cooldown).expect("duration out of range"),
+            consecutive: 0,
+            opened: None,
+        }
+    }
+
+    pub(crate) fn state(&self, now: Instant) -> BreakerState {
+        match self.opened {
+            None => BreakerState::Closed,
+            Some(opened) if duration_millis(elapsed_since(opened, now)) < self.cooldown_millis => {
+                BreakerState::Open
+            }
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    pub(crate) fn record_failure(&mut self, now: Instant) {
+        self.consecutive = self.consecutive.saturating_add(1);
+        if self.consecutive >= self.failures {
+            self.opened = Some(now);
+        }
+    }
+
+    pub(crate) fn record_success(&mut self) {
+        self.consecutive = 0;
+        self.opened = None;
+    }
+}
@@ -0,0 +1,89 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "std")]
+use crate::Duration;
+use crate::Instant;
+
+/// A source of the current time for a [`Bucket`](crate::Bucket).
+///
+/// Without the `std` feature there is no system clock, so every bucket is
+/// made with one, see [`Bucket::new_with_clock`](crate::Bucket::new_with_clock).
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The clock buckets use unless told otherwise.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        crate::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same time, so a clone kept outside the bucket can be
+/// used to advance it.
+///
+/// # Example
+/// ```
+/// use uchimizu::{ManualClock, Policy};
+///
+/// let clock = ManualClock::new();
+/// let mut b = Policy::expire_within_secs(3600).into_bucket_with_clock(clock.clone());
+/// assert_eq!(b.call_sync(|| 1), 1);
+/// assert_eq!(b.call_sync(|| 2), 1);
+/// # #[cfg(not(feature = "chrono-clock"))]
+/// clock.advance(std::time::Duration::from_secs(3600));
+/// # #[cfg(feature = "chrono-clock")]
+/// # clock.advance(chrono::TimeDelta::seconds(3600));
+/// assert_eq!(b.call_sync(|| 3), 3);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+#[cfg(feature = "std")]
+impl ManualClock {
+    /// Creates a clock stopped at the current system time.
+    pub fn new() -> ManualClock {
+        ManualClock::starting_at(crate::now())
+    }
+
+    pub fn starting_at(now: Instant) -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn advance(&self, d: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += d;
+    }
+
+    pub fn set(&self, now: Instant) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,272 @@
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use dashmap::DashMap;
+
+use crate::{Bucket, Expiry, Policy, SharedBucket};
+
+/// A keyed cache that can be called through `&self` from many tasks.
+///
+/// Each key has its own [`SharedBucket`], so calls for the same key are
+/// coalesced while refreshes of different keys run in parallel. The map
+/// itself is sharded, and a shard is only locked while a key's bucket is
+/// looked up or created.
+///
+/// The number of loads running at once across all keys can be bounded with
+/// [`ConcurrentBucketMap::with_max_concurrent_loads`].
+///
+/// # Example
+/// ```
+/// use uchimizu::{ConcurrentBucketMap, Policy};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let m = ConcurrentBucketMap::new(Policy::expire_within_counts(10));
+/// let load = |key: &u32| {
+///     let value = key * 10;
+///     async move { value }
+/// };
+/// assert_eq!(m.call(1, load).await, 10);
+/// assert_eq!(m.call(2, load).await, 20);
+/// assert_eq!(m.len(), 2);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentBucketMap<K, T, P = Policy>
+where
+    K: Eq + Hash,
+{
+    policy: P,
+    buckets: DashMap<K, SharedBucket<T, P>>,
+    loads: Limit,
+}
+
+impl<K, T, P> ConcurrentBucketMap<K, T, P>
+where
+    K: Eq + Hash,
+    P: Expiry + Clone,
+{
+    pub fn new(policy: P) -> Self {
+        ConcurrentBucketMap {
+            policy,
+            buckets: DashMap::new(),
+            loads: Limit::new(usize::MAX),
+        }
+    }
+
+    /// Runs at most `n` loads at once, across all keys.
+    ///
+    /// A miss beyond the limit waits for another load to finish before its
+    /// task runs, while hits are served as usual. Calls for the same key
+    /// are still coalesced, so they take a single slot. Zero is treated as
+    /// one.
+    pub fn with_max_concurrent_loads(mut self, n: usize) -> Self {
+        self.loads = Limit::new(n.max(1));
+        self
+    }
+
+    /// Returns the number of loads running right now, not counting those
+    /// waiting for the limit.
+    pub fn in_flight_loads(&self) -> usize {
+        self.loads.state().in_flight
+    }
+
+    /// Returns the number of keys that have a bucket.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// See [`SharedBucket::invalidate`].
+    pub fn invalidate(&self, key: &K) {
+        if let Some(bucket) = self.buckets.get(key) {
+            bucket.invalidate();
+        }
+    }
+
+    /// Drops every bucket. Calls in progress complete on their own bucket.
+    pub fn clear(&self) {
+        self.buckets.clear();
+    }
+
+    /// See [`BucketMap::call`](crate::BucketMap::call).
+    pub async fn call<F, Fut>(&self, key: K, task: F) -> T
+    where
+        K: Clone,
+        T: Clone,
+        F: FnOnce(&K) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let bucket = self
+            .buckets
+            .entry(key.clone())
+            .or_insert_with(|| Bucket::new(self.policy.clone()).into_shared())
+            .clone();
+        let loads = &self.loads;
+        bucket
+            .call(|| async {
+                let _permit = loads.acquire().await;
+                task(&key).await
+            })
+            .await
+    }
+}
+
+/// Counts the loads in flight, making new ones wait while `max` are.
+///
+/// Every waiter is woken when a load finishes, so a waiter that is dropped
+/// cannot leave a free slot unclaimed.
+#[derive(Debug)]
+struct Limit {
+    max: usize,
+    state: Mutex<LimitState>,
+}
+
+#[derive(Debug, Default)]
+struct LimitState {
+    in_flight: usize,
+    wakers: Vec<Waker>,
+}
+
+impl Limit {
+    fn new(max: usize) -> Limit {
+        Limit {
+            max,
+            state: Mutex::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, LimitState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn acquire(&self) -> Acquire<'_> {
+        Acquire { limit: self }
+    }
+}
+
+struct Acquire<'a> {
+    limit: &'a Limit,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Permit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit<'a>> {
+        let mut state = self.limit.state();
+        if state.in_flight < self.limit.max {
+            state.in_flight += 1;
+            return Poll::Ready(Permit { limit: self.limit });
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// A slot taken from a [`Limit`], released when dropped.
+struct Permit<'a> {
+    limit: &'a Limit,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limit.state();
+        state.in_flight -= 1;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn many_callers_across_many_keys() {
+        const KEYS: usize = 20;
+        let m = Arc::new(ConcurrentBucketMap::new(Policy::expire_within_counts(1000)));
+        let runs: Arc<Vec<AtomicUsize>> =
+            Arc::new((0..KEYS).map(|_| AtomicUsize::new(0)).collect());
+        let mut handles = Vec::new();
+        for i in 0..400 {
+            let m = m.clone();
+            let runs = runs.clone();
+            handles.push(tokio::spawn(async move {
+                let key = i % KEYS;
+                let load = |key: &usize| {
+                    runs[*key].fetch_add(1, Ordering::SeqCst);
+                    let value = *key;
+                    async move {
+                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                        value
+                    }
+                };
+                assert_eq!(m.call(key, load).await, key);
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(m.len(), KEYS);
+        for runs in runs.iter() {
+            assert_eq!(runs.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_beyond_the_limit_wait() {
+        use tokio::sync::Barrier;
+
+        let m = Arc::new(
+            ConcurrentBucketMap::new(Policy::expire_within_counts(1000))
+                .with_max_concurrent_loads(2),
+        );
+        assert_eq!(m.call(100, |_| async { 100 }).await, 100);
+
+        let arrive = Arc::new(Barrier::new(3));
+        let release = Arc::new(Barrier::new(3));
+        let started = Arc::new(AtomicUsize::new(0));
+        let mut handles = Vec::new();
+        for key in 0..4 {
+            let m = m.clone();
+            let (arrive, release, started) = (arrive.clone(), release.clone(), started.clone());
+            handles.push(tokio::spawn(async move {
+                let load = |key: &usize| {
+                    let key = *key;
+                    async move {
+                        started.fetch_add(1, Ordering::SeqCst);
+                        arrive.wait().await;
+                        release.wait().await;
+                        key
+                    }
+                };
+                assert_eq!(m.call(key, load).await, key);
+            }));
+        }
+        for round in 1..=2 {
+            arrive.wait().await;
+            assert_eq!(started.load(Ordering::SeqCst), 2 * round);
+            assert_eq!(m.in_flight_loads(), 2);
+            // Hits do not wait for the limit.
+            let hit = m.call(100, |_| async { 0 });
+            let hit = tokio::time::timeout(std::time::Duration::from_secs(1), hit).await;
+            assert_eq!(hit, Ok(100));
+            release.wait().await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(m.in_flight_loads(), 0);
+    }
+}
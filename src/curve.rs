@@ -0,0 +1,162 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+/// How the evaporated amount grows with time.
+#[derive(Clone, Default)]
+pub(crate) enum Curve {
+    /// `evaporation_cost` per second, pro rata.
+    #[default]
+    Linear,
+    /// Like `Linear`, but time gives the amount back to the pours instead
+    /// of taking more away.
+    Refill,
+    /// The evaporated amount after the given milliseconds.
+    Custom(Arc<dyn Fn(u64) -> u64 + Send + Sync>),
+}
+
+impl Curve {
+    pub(crate) fn evaporated(&self, evaporation_cost: u64, duration_millis: u128) -> u64 {
+        match self {
+            Curve::Linear | Curve::Refill => {
+                (u128::from(evaporation_cost).saturating_mul(duration_millis) / 1000)
+                    .try_into()
+                    .unwrap_or(u64::MAX)
+            }
+            Curve::Custom(f) => f(duration_millis.try_into().unwrap_or(u64::MAX)),
+        }
+    }
+
+    /// Returns the amount consumed by `pour_amount` and the time elapsed.
+    pub(crate) fn consumed(
+        &self,
+        pour_amount: u64,
+        evaporation_cost: u64,
+        duration_millis: u128,
+    ) -> u64 {
+        let evaporated = self.evaporated(evaporation_cost, duration_millis);
+        match self {
+            Curve::Refill => pour_amount.saturating_sub(evaporated),
+            Curve::Linear | Curve::Custom(_) => pour_amount.saturating_add(evaporated),
+        }
+    }
+
+    /// Returns the first millisecond at which at least `amount` has
+    /// evaporated, or `None` if that never happens.
+    pub(crate) fn reaches_at_millis(&self, evaporation_cost: u64, amount: u64) -> Option<u64> {
+        match self {
+            Curve::Refill => None,
+            Curve::Linear => {
+                if evaporation_cost == 0 {
+                    return None;
+                }
+                let millis = (u128::from(amount) * 1000).div_ceil(u128::from(evaporation_cost));
+                Some(millis.try_into().unwrap_or(u64::MAX))
+            }
+            Curve::Custom(f) => {
+                if f(u64::MAX) < amount {
+                    return None;
+                }
+                let (mut lo, mut hi) = (0, u64::MAX);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if f(mid) >= amount {
+                        hi = mid;
+                    } else {
+                        lo = mid + 1;
+                    }
+                }
+                Some(lo)
+            }
+        }
+    }
+
+    /// Multiplies the evaporated amount of a custom curve, saturating.
+    pub(crate) fn scaled(&self, factor: u64) -> Curve {
+        match self {
+            Curve::Linear => Curve::Linear,
+            Curve::Refill => Curve::Refill,
+            Curve::Custom(f) => {
+                let f = f.clone();
+                Curve::Custom(Arc::new(move |millis| f(millis).saturating_mul(factor)))
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn is_linear(&self) -> bool {
+        matches!(self, Curve::Linear)
+    }
+}
+
+/// Custom curves compare equal only to themselves and their clones.
+impl PartialEq for Curve {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Curve::Linear, Curve::Linear) | (Curve::Refill, Curve::Refill) => true,
+            (Curve::Custom(a), Curve::Custom(b)) => Arc::ptr_eq(a, b),
+            (_, _) => false,
+        }
+    }
+}
+
+impl Eq for Curve {}
+
+impl fmt::Debug for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Curve::Linear => write!(f, "Linear"),
+            Curve::Refill => write!(f, "Refill"),
+            Curve::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// A custom curve is code, so a policy using one cannot be serialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Curve {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Curve::Linear => s.serialize_str("linear"),
+            Curve::Refill => s.serialize_str("refill"),
+            Curve::Custom(_) => Err(serde::ser::Error::custom(
+                "custom evaporation curves cannot be serialized",
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Curve {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(d)?;
+        match name.as_str() {
+            "linear" => Ok(Curve::Linear),
+            "refill" => Ok(Curve::Refill),
+            other => Err(serde::de::Error::unknown_variant(
+                other,
+                &["linear", "refill"],
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_curve_reaches_amount() {
+        let curve = Curve::Custom(Arc::new(|millis| millis / 10));
+        assert_eq!(curve.reaches_at_millis(0, 0), Some(0));
+        assert_eq!(curve.reaches_at_millis(0, 5), Some(50));
+        assert_eq!(curve.reaches_at_millis(0, u64::MAX), None);
+        assert_eq!(curve.scaled(2).reaches_at_millis(0, 5), Some(30));
+    }
+
+    #[test]
+    fn linear_curve_reaches_amount() {
+        assert_eq!(Curve::Linear.reaches_at_millis(1000, 5), Some(5));
+        assert_eq!(Curve::Linear.reaches_at_millis(3, 1), Some(334));
+        assert_eq!(Curve::Linear.reaches_at_millis(0, 1), None);
+    }
+}
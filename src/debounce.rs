@@ -0,0 +1,104 @@
+use core::future::Future;
+
+use crate::{Bucket, Clock, Expiry, Policy, Task};
+
+/// Runs side effects at most as often as a policy allows.
+///
+/// This is a [`Bucket`] with nothing to cache: a run starts the quiet
+/// period and counts as its first pour, and every skipped call pours as
+/// well.
+///
+/// # Example
+/// ```
+/// use uchimizu::{Debouncer, Policy};
+///
+/// # #[cfg(feature = "std")]
+/// # {
+/// let mut flush = Debouncer::new(Policy::expire_within_counts(3));
+/// let runs: Vec<bool> = (0..5).map(|_| flush.should_run()).collect();
+/// assert_eq!(runs, [true, false, false, true, false]);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Debouncer<P = Policy> {
+    bucket: Bucket<(), P>,
+}
+
+impl<P> Debouncer<P>
+where
+    P: Expiry,
+{
+    #[cfg(feature = "std")]
+    pub fn new(expiry: P) -> Self {
+        Debouncer {
+            bucket: Bucket::new(expiry).count_refresh_as_pour(true),
+        }
+    }
+
+    /// Reads time from `clock`, which is required without the `std`
+    /// feature, see [`Bucket::new_with_clock`].
+    pub fn new_with_clock(expiry: P, clock: impl Clock + 'static) -> Self {
+        Debouncer {
+            bucket: Bucket::new_with_clock(expiry, clock).count_refresh_as_pour(true),
+        }
+    }
+
+    /// See [`Bucket::with_clock`].
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        Debouncer {
+            bucket: self.bucket.with_clock(clock),
+        }
+    }
+
+    /// Returns whether the quiet period is over, starting a new one if so.
+    pub fn should_run(&mut self) -> bool {
+        let mut due = false;
+        self.bucket.call_sync(|| due = true);
+        due
+    }
+
+    /// Runs `task` if the quiet period is over, and returns whether it ran.
+    ///
+    /// If `task` is cancelled, the quiet period does not start.
+    pub async fn run_if_due<F, Fut>(&mut self, task: F) -> bool
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = ()>,
+    {
+        let mut ran = false;
+        self.bucket
+            .call(|| async {
+                task.call().await;
+                ran = true;
+            })
+            .await;
+        ran
+    }
+
+    /// Ends the quiet period, so the next call runs.
+    pub fn reset(&mut self) {
+        self.bucket.refresh();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{millis_duration, ManualClock};
+
+    #[tokio::test]
+    async fn runs_once_per_interval() {
+        let clock = ManualClock::new();
+        let mut d = Debouncer::new(Policy::expire_within_secs(10)).with_clock(clock.clone());
+        let runs = core::cell::Cell::new(0);
+        for _ in 0..3 {
+            d.run_if_due(|| async { runs.set(runs.get() + 1) }).await;
+        }
+        assert_eq!(runs.get(), 1);
+        clock.advance(millis_duration(10_000));
+        assert!(d.run_if_due(|| async {}).await);
+        assert!(!d.should_run());
+        d.reset();
+        assert!(d.should_run());
+    }
+}
@@ -0,0 +1,250 @@
+use alloc::string::String;
+use core::fmt;
+
+/// Error returned by [`Bucket::call_try`](crate::Bucket::call_try).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The task ran and failed.
+    Task(E),
+    /// A previous attempt failed and the bucket's error policy has not
+    /// expired yet, so the task was not run.
+    NegativeCached,
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Task(e) => write!(f, "task failed: {}", e),
+            Error::NegativeCached => write!(f, "previous attempt failed recently"),
+        }
+    }
+}
+
+impl<E> core::error::Error for Error<E>
+where
+    E: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Task(e) => Some(e),
+            Error::NegativeCached => None,
+        }
+    }
+}
+
+/// Error of [`Bucket::call_with_fallback`](crate::Bucket::call_with_fallback)
+/// when both tasks failed.
+///
+/// It reads as the primary error, with the secondary one as its
+/// [`source`](core::error::Error::source).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackError<E, S> {
+    pub primary: E,
+    pub secondary: S,
+}
+
+impl<E, S> fmt::Display for FallbackError<E, S>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.primary)
+    }
+}
+
+impl<E, S> core::error::Error for FallbackError<E, S>
+where
+    E: fmt::Debug + fmt::Display,
+    S: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.secondary)
+    }
+}
+
+/// Error returned by the validating constructors of [`Policy`](crate::Policy).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// Every parameter is zero, so the policy means nothing.
+    AllZero,
+    /// `initial_amount` is zero, so the policy is always exhausted and the
+    /// costs have no effect.
+    ZeroInitialAmount,
+    /// The duration is negative or too large to be represented.
+    DurationOutOfRange,
+    /// A parameter does not fit in a `u64`.
+    Overflow,
+    /// A fractional parameter is NaN or negative.
+    InvalidFraction,
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::AllZero => write!(f, "all policy parameters are zero"),
+            PolicyError::ZeroInitialAmount => write!(f, "initial amount is zero"),
+            PolicyError::DurationOutOfRange => write!(f, "duration is out of range"),
+            PolicyError::Overflow => write!(f, "policy parameter overflowed"),
+            PolicyError::InvalidFraction => write!(f, "policy parameter is NaN or negative"),
+        }
+    }
+}
+
+impl core::error::Error for PolicyError {}
+
+/// Error returned when [`Policy`](crate::Policy) fails to parse from its
+/// notation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePolicyError {
+    pub(crate) token: String,
+    pub(crate) expected: &'static str,
+}
+
+impl ParsePolicyError {
+    /// The token that could not be parsed, empty if the input ended early.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl fmt::Display for ParsePolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.token.as_str() {
+            "" => write!(f, "unexpected end of policy")?,
+            token => write!(f, "unexpected `{}` in policy", token)?,
+        }
+        if self.expected.chars().all(crate::notation::is_punct) {
+            write!(f, ", expected `{}`", self.expected)
+        } else {
+            write!(f, ", expected {}", self.expected)
+        }
+    }
+}
+
+impl core::error::Error for ParsePolicyError {}
+
+/// Error returned by [`Bucket::call_with_timeout`](crate::Bucket::call_with_timeout)
+/// when the task timed out and nothing was cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutError;
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task timed out")
+    }
+}
+
+impl core::error::Error for TimeoutError {}
+
+/// Error returned by the methods of
+/// [`SharedBucket`](crate::SharedBucket) that spawn background work when
+/// no [`Spawner`](crate::Spawner) is set and no runtime feature provides
+/// one.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoSpawner;
+
+#[cfg(feature = "std")]
+impl fmt::Display for NoSpawner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no spawner configured")
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for NoSpawner {}
+
+/// Error returned by [`Bucket::try_cached`](crate::Bucket::try_cached) when
+/// there is no fresh value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheMiss {
+    /// Nothing has been cached yet, or the value was invalidated.
+    Empty,
+    /// A value is cached but the policy is exhausted.
+    Expired {
+        /// The time elapsed since the last refresh.
+        age: crate::Duration,
+    },
+}
+
+impl fmt::Display for CacheMiss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMiss::Empty => write!(f, "nothing is cached"),
+            CacheMiss::Expired { age } => write!(
+                f,
+                "cached value expired, refreshed {} ms ago",
+                crate::duration_millis(*age)
+            ),
+        }
+    }
+}
+
+impl core::error::Error for CacheMiss {}
+
+/// Error returned by [`Bucket::try_acquire`](crate::Bucket::try_acquire)
+/// once the budget is drained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exhausted {
+    /// How long until the policy has refilled enough for another pour, or
+    /// `None` if only a refresh can restore it.
+    pub retry_after: Option<crate::Duration>,
+}
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.retry_after {
+            Some(d) => write!(
+                f,
+                "budget exhausted, retry after {} ms",
+                crate::duration_millis_ceil(d).unwrap_or(0)
+            ),
+            None => write!(f, "budget exhausted"),
+        }
+    }
+}
+
+impl core::error::Error for Exhausted {}
+
+/// Error returned by a [`Store`](crate::Store).
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum StoreError {
+    /// Reading or writing the underlying storage failed.
+    Io(std::io::Error),
+    /// The stored bucket could not be encoded or decoded.
+    Format(Box<dyn core::error::Error + Send + Sync>),
+    /// The storage backend reported an error.
+    Backend(Box<dyn core::error::Error + Send + Sync>),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "store i/o failed: {}", e),
+            StoreError::Format(e) => write!(f, "stored bucket is malformed: {}", e),
+            StoreError::Backend(e) => write!(f, "store backend failed: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            StoreError::Io(e) => Some(e),
+            StoreError::Format(e) | StoreError::Backend(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::{Arc, OnceLock};
+
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::{BucketObserver, Duration};
+
+/// How many events a slow subscriber can fall behind before the oldest are
+/// dropped.
+const CAPACITY: usize = 256;
+
+/// What a bucket did, as reported by [`Bucket::events`](crate::Bucket::events).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEvent {
+    /// A cached value was served.
+    Hit,
+    /// A call could not be served from the cache.
+    Miss,
+    /// The task started running.
+    RefreshStarted,
+    /// The task finished and its value was stored, `elapsed` after it
+    /// started.
+    RefreshSucceeded { elapsed: Duration },
+    /// The task failed.
+    RefreshFailed,
+    /// The cached value was dropped by `invalidate` or `refresh`, or
+    /// taken by `take`.
+    Invalidated,
+}
+
+/// The sending side of the event stream, created by the first subscriber
+/// and shared by every handle to the bucket.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Events {
+    tx: Arc<OnceLock<broadcast::Sender<CacheEvent>>>,
+}
+
+impl Events {
+    pub(crate) fn subscribe(&self) -> impl Stream<Item = CacheEvent> + Send + Unpin + 'static {
+        let rx = self
+            .tx
+            .get_or_init(|| broadcast::Sender::new(CAPACITY))
+            .subscribe();
+        BroadcastStream::new(rx).filter_map(Result::ok)
+    }
+
+    pub(crate) fn sender(&self) -> Option<Sender<'_>> {
+        self.tx.get().map(Sender)
+    }
+}
+
+/// Publishes the observer hooks as events. Sending never blocks: with no
+/// subscriber the event is dropped, and a full channel drops its oldest.
+#[derive(Debug)]
+pub(crate) struct Sender<'a>(&'a broadcast::Sender<CacheEvent>);
+
+impl Sender<'_> {
+    fn send(&self, event: CacheEvent) {
+        let _ = self.0.send(event);
+    }
+}
+
+impl BucketObserver for Sender<'_> {
+    fn on_hit(&self) {
+        self.send(CacheEvent::Hit);
+    }
+
+    fn on_miss(&self) {
+        self.send(CacheEvent::Miss);
+        self.send(CacheEvent::RefreshStarted);
+    }
+
+    fn on_refresh_complete(&self, elapsed: Duration) {
+        self.send(CacheEvent::RefreshSucceeded { elapsed });
+    }
+
+    fn on_refresh_failed(&self) {
+        self.send(CacheEvent::RefreshFailed);
+    }
+
+    fn on_invalidate(&self) {
+        self.send(CacheEvent::Invalidated);
+    }
+}
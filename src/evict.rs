@@ -0,0 +1,106 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+/// A callback that receives the values a bucket drops.
+#[cfg(feature = "std")]
+pub(crate) struct Evict<T> {
+    f: Arc<Mutex<dyn FnMut(T) + Send>>,
+}
+
+/// Without `std` there is no lock to share a callback behind, so a bucket
+/// never has one.
+#[cfg(not(feature = "std"))]
+#[derive(Clone)]
+pub(crate) struct Evict<T> {
+    never: core::convert::Infallible,
+    _value: core::marker::PhantomData<fn(T)>,
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> Evict<T> {
+    pub(crate) fn call(&self, _value: T) {
+        match self.never {}
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Evict<T> {
+    pub(crate) fn new(f: impl FnMut(T) + Send + 'static) -> Evict<T> {
+        Evict {
+            f: Arc::new(Mutex::new(f)),
+        }
+    }
+
+    pub(crate) fn call(&self, value: T) {
+        (self.f.lock().unwrap())(value);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Clone for Evict<T> {
+    fn clone(&self) -> Self {
+        Evict { f: self.f.clone() }
+    }
+}
+
+impl<T> fmt::Debug for Evict<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Evict(..)")
+    }
+}
+
+#[cfg(feature = "std")]
+type Bind<K, T> = dyn Fn(&K) -> Evict<T> + Send + Sync;
+
+/// A callback shared by the buckets of a keyed map, which also receives the
+/// key of the dropped value.
+#[cfg(feature = "std")]
+pub(crate) struct KeyedEvict<K, T> {
+    f: Arc<Mutex<dyn FnMut(K, T) + Send>>,
+    bind: Arc<Bind<K, T>>,
+}
+
+#[cfg(feature = "std")]
+impl<K, T> KeyedEvict<K, T> {
+    pub(crate) fn new(f: impl FnMut(K, T) + Send + 'static) -> KeyedEvict<K, T>
+    where
+        K: Clone + Send + 'static,
+        T: 'static,
+    {
+        let f: Arc<Mutex<dyn FnMut(K, T) + Send>> = Arc::new(Mutex::new(f));
+        let shared = f.clone();
+        let bind = Arc::new(move |key: &K| {
+            let f = shared.clone();
+            let key = key.clone();
+            Evict::new(move |value| (f.lock().unwrap())(key.clone(), value))
+        });
+        KeyedEvict { f, bind }
+    }
+
+    /// Returns the callback for the bucket of `key`.
+    pub(crate) fn bind(&self, key: &K) -> Evict<T> {
+        (self.bind)(key)
+    }
+
+    pub(crate) fn call(&self, key: K, value: T) {
+        (self.f.lock().unwrap())(key, value);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, T> Clone for KeyedEvict<K, T> {
+    fn clone(&self) -> Self {
+        KeyedEvict {
+            f: self.f.clone(),
+            bind: self.bind.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, T> fmt::Debug for KeyedEvict<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyedEvict(..)")
+    }
+}
@@ -0,0 +1,239 @@
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use http::header::CACHE_CONTROL;
+use http::{HeaderMap, Response, Uri};
+
+use crate::{BucketMap, Error, Policy};
+
+/// Caches the responses of idempotent GET requests by URL.
+///
+/// `fetch` is any async function from a [`Uri`] to a [`Response`], so any
+/// HTTP client can be adapted to it. How long a response is kept is read
+/// from its `Cache-Control` header: `max-age` sets the lifetime in seconds,
+/// `no-store` and `no-cache` keep it from being served again, and without
+/// either the fallback policy applies. Failed fetches are not cached.
+///
+/// # Example
+/// ```
+/// use http::{Response, Uri};
+/// use uchimizu::{HttpCache, Policy};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let fetch = |uri: Uri| async move {
+///     Response::builder()
+///         .header("cache-control", "public, max-age=60")
+///         .body(format!("body of {}", uri))
+///         .map_err(|e| e.to_string())
+/// };
+/// let cache = HttpCache::new(fetch, Policy::expire_within_secs(5));
+/// let uri: Uri = "https://example.com/a".parse().unwrap();
+/// let res = cache.get(uri.clone()).await.unwrap();
+/// assert_eq!(res.body(), "body of https://example.com/a");
+/// assert_eq!(cache.policy_of(&uri), Some(Policy::expire_within_secs(60)));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HttpCache<F, B> {
+    fetch: F,
+    fallback: Policy,
+    map: Mutex<BucketMap<Uri, Response<B>>>,
+}
+
+impl<F, B> HttpCache<F, B> {
+    /// Caches the responses of `fetch`, for as long as `fallback` allows if
+    /// they do not say.
+    pub fn new(fetch: F, fallback: Policy) -> Self {
+        HttpCache {
+            fetch,
+            map: Mutex::new(BucketMap::new(fallback.clone())),
+            fallback,
+        }
+    }
+
+    /// See [`BucketMap::with_capacity`].
+    pub fn with_capacity(mut self, max_entries: usize) -> Self {
+        let map = self
+            .map
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.map = Mutex::new(map.with_capacity(max_entries));
+        self
+    }
+
+    /// Returns the policy the response for `uri` is cached with, if there
+    /// is one.
+    pub fn policy_of(&self, uri: &Uri) -> Option<Policy> {
+        let map = self.lock();
+        let bucket = map.bucket(uri)?;
+        bucket.cached()?;
+        Some(bucket.policy.clone())
+    }
+
+    /// See [`BucketMap::invalidate`].
+    pub fn invalidate(&self, uri: &Uri) {
+        self.lock().invalidate(uri);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BucketMap<Uri, Response<B>>> {
+        self.map.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Serves the cached response for `uri` while its policy is remaining,
+    /// and otherwise fetches it.
+    ///
+    /// The cache is not locked during the fetch, so concurrent misses for
+    /// the same URL each fetch it.
+    pub async fn get<Fut, E>(&self, uri: Uri) -> Result<Response<B>, Error<E>>
+    where
+        F: Fn(Uri) -> Fut,
+        Fut: Future<Output = Result<Response<B>, E>>,
+        B: Clone,
+    {
+        let exhausted = {
+            let mut map = self.lock();
+            let bucket = map.touch(&uri);
+            if let Some(entry) = bucket.hit() {
+                bucket.observe(|o| o.on_hit());
+                return Ok(entry);
+            }
+            let exhausted = bucket.miss();
+            bucket.observe(|o| o.on_miss());
+            exhausted
+        };
+        let result = (self.fetch)(uri.clone()).await;
+        let mut map = self.lock();
+        let bucket = map.touch(&uri);
+        let result = match result {
+            Ok(res) => {
+                let policy = policy_of(res.headers()).unwrap_or_else(|| self.fallback.clone());
+                let res = bucket.complete(res, exhausted);
+                bucket.set_policy(policy);
+                Ok(res)
+            }
+            Err(e) => bucket.recover_observed(e),
+        };
+        map.reweigh(&uri);
+        result
+    }
+}
+
+/// Reads the policy a response asks for from its `Cache-Control` headers.
+fn policy_of(headers: &HeaderMap) -> Option<Policy> {
+    let mut max_age = None;
+    let directives = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for directive in directives {
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+        if name.eq_ignore_ascii_case("no-store") || name.eq_ignore_ascii_case("no-cache") {
+            return Some(Policy::pierced());
+        }
+        if name.eq_ignore_ascii_case("max-age") {
+            max_age = value.and_then(|v| v.parse().ok()).or(max_age);
+        }
+    }
+    max_age.map(Policy::expire_within_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn headers(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(CACHE_CONTROL, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn reads_cache_control() {
+        let policy = |values: &[&str]| policy_of(&headers(values));
+        assert_eq!(
+            policy(&["public, max-age=300"]),
+            Some(Policy::expire_within_secs(300))
+        );
+        assert_eq!(
+            policy(&["Max-Age=\"10\""]),
+            Some(Policy::expire_within_secs(10))
+        );
+        assert_eq!(
+            policy(&["private", "max-age=5"]),
+            Some(Policy::expire_within_secs(5))
+        );
+        assert_eq!(policy(&["max-age=60, no-store"]), Some(Policy::pierced()));
+        assert_eq!(policy(&["no-cache"]), Some(Policy::pierced()));
+        assert_eq!(policy(&["max-age=soon"]), None);
+        assert_eq!(policy(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn caches_per_url_for_max_age() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let fetch = {
+            let fetches = fetches.clone();
+            move |uri: Uri| {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let cache_control = match uri.path() {
+                        "/fresh" => "max-age=60",
+                        "/uncached" => "no-store",
+                        _ => "public",
+                    };
+                    Response::builder()
+                        .header(CACHE_CONTROL, cache_control)
+                        .body(uri.path().to_string())
+                        .map_err(|e| e.to_string())
+                }
+            }
+        };
+        let cache = HttpCache::new(fetch, Policy::expire_within_counts(1));
+        let get = |path: &str| {
+            let uri = format!("http://example.com{}", path).parse().unwrap();
+            cache.get(uri)
+        };
+        for _ in 0..3 {
+            assert_eq!(get("/fresh").await.unwrap().body(), "/fresh");
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        get("/uncached").await.unwrap();
+        get("/uncached").await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+        // Falls back to one hit per fetch.
+        get("/other").await.unwrap();
+        get("/other").await.unwrap();
+        get("/other").await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_failures() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let fetch = {
+            let fetches = fetches.clone();
+            move |_: Uri| {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                async { Err::<Response<()>, _>("down") }
+            }
+        };
+        let cache = HttpCache::new(fetch, Policy::expire_within_secs(60));
+        let uri: Uri = "http://example.com/".parse().unwrap();
+        assert_eq!(
+            cache.get(uri.clone()).await.unwrap_err(),
+            Error::Task("down")
+        );
+        assert!(cache.get(uri.clone()).await.is_err());
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.policy_of(&uri), None);
+    }
+}
@@ -0,0 +1,56 @@
+use core::time::Duration;
+
+/// A point in time, measured from an epoch chosen by the [`Clock`](crate::Clock) that
+/// reports it, such as boot.
+///
+/// This stands in for `std::time::Instant` without the `std` feature,
+/// where there is no system clock and every bucket is made with a
+/// [`Clock`](crate::Clock) that reports it.
+///
+/// It is also used in the browser with the `wasm` feature, where
+/// `std::time::Instant::now` panics. There the epoch is the Unix epoch and
+/// the time is read from `Date.now()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+
+impl Instant {
+    pub const EPOCH: Instant = Instant(Duration::ZERO);
+
+    /// Returns the time since the epoch.
+    pub fn since_epoch(self) -> Duration {
+        self.0
+    }
+
+    pub fn checked_add(self, d: Duration) -> Option<Instant> {
+        self.0.checked_add(d).map(Instant)
+    }
+
+    pub fn checked_sub(self, d: Duration) -> Option<Instant> {
+        self.0.checked_sub(d).map(Instant)
+    }
+
+    /// Returns the time from `earlier` to `self`, or zero if `earlier` is
+    /// later.
+    pub fn saturating_duration_since(self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+impl core::ops::Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, d: Duration) -> Instant {
+        Instant(self.0 + d)
+    }
+}
+
+impl core::ops::AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, d: Duration) {
+        self.0 += d;
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn date_now() -> Instant {
+    Instant(Duration::from_micros((js_sys::Date::now() * 1000.0) as u64))
+}
@@ -0,0 +1,141 @@
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+
+/// Per-bucket random stretch of the evaporation clock.
+///
+/// The factor is drawn once per refresh cycle, so consecutive checks within
+/// a cycle agree. The random state is never persisted: every deserialized
+/// copy of a bucket draws its own factors.
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, serde::Serialize, serde::Deserialize)
+)]
+pub(crate) struct Jitter {
+    fraction: f32,
+    #[cfg_attr(feature = "serde", serde(skip, default = "random_seed"))]
+    state: u64,
+}
+
+/// Only the configuration is compared, not the random state.
+impl PartialEq for Jitter {
+    fn eq(&self, other: &Self) -> bool {
+        self.fraction == other.fraction
+    }
+}
+
+impl Eq for Jitter {}
+
+#[cfg(feature = "std")]
+fn random_seed() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Without `std` there is no entropy to draw on, so buckets take distinct
+/// but predictable seeds in the order they are made.
+#[cfg(not(feature = "std"))]
+fn random_seed() -> u64 {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed) as u64;
+    n.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+impl Jitter {
+    pub(crate) fn new(fraction: f32) -> Jitter {
+        Jitter::with_seed(fraction, random_seed())
+    }
+
+    fn with_seed(fraction: f32, seed: u64) -> Jitter {
+        Jitter {
+            fraction,
+            state: seed,
+        }
+    }
+
+    /// Draws the factor for the next refresh cycle.
+    pub(crate) fn reroll(&mut self) {
+        // splitmix64
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    }
+
+    /// Returns the factor of the current cycle, within `1 ± fraction`.
+    fn factor(&self) -> f64 {
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        let unit = (z >> 11) as f64 / (1u64 << 53) as f64;
+        let factor = 1.0 + f64::from(self.fraction) * (2.0 * unit - 1.0);
+        factor.max(f64::MIN_POSITIVE)
+    }
+
+    /// Converts real elapsed milliseconds into the milliseconds the policy
+    /// sees. A factor above one makes the entry live longer.
+    pub(crate) fn shrink(&self, millis: u64) -> u64 {
+        (millis as f64 / self.factor()) as u64
+    }
+
+    /// The smallest real elapsed time that [`Jitter::shrink`] maps to at
+    /// least `millis`.
+    pub(crate) fn stretch(&self, millis: u64) -> u64 {
+        // `f64::ceil` needs `std`.
+        let exact = millis as f64 * self.factor();
+        let mut real = exact as u64;
+        if (real as f64) < exact {
+            real = real.saturating_add(1);
+        }
+        while real < u64::MAX && self.shrink(real) < millis {
+            real += 1;
+        }
+        while real > 0 && self.shrink(real - 1) >= millis {
+            real -= 1;
+        }
+        real
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factor_is_within_fraction_and_stable() {
+        let mut j = Jitter::with_seed(0.2, 42);
+        for _ in 0..1000 {
+            let f = j.factor();
+            assert!((0.8..=1.2).contains(&f));
+            assert_eq!(j.factor(), f);
+            j.reroll();
+        }
+    }
+
+    #[test]
+    fn reroll_changes_factor() {
+        let mut j = Jitter::with_seed(0.5, 7);
+        let before = j.factor();
+        j.reroll();
+        assert_ne!(j.factor(), before);
+    }
+
+    #[test]
+    fn zero_fraction_is_identity() {
+        let j = Jitter::with_seed(0.0, 1);
+        assert_eq!(j.shrink(12_345), 12_345);
+        assert_eq!(j.stretch(12_345), 12_345);
+    }
+
+    #[test]
+    fn stretch_inverts_shrink() {
+        let mut j = Jitter::with_seed(0.3, 3);
+        for millis in [1, 999, 10_000, 3_600_000] {
+            let real = j.stretch(millis);
+            assert!(j.shrink(real) >= millis);
+            assert!(j.shrink(real - 1) < millis);
+            j.reroll();
+        }
+    }
+}
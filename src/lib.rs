@@ -1,27 +1,304 @@
-use std::future::Future;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[cfg(not(feature = "serde"))]
-type Instant = std::time::Instant;
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+
+#[cfg(feature = "std")]
+mod atomic;
+#[cfg(feature = "chrono-clock")]
+mod boundary;
+mod breaker;
+mod clock;
+#[cfg(feature = "concurrent")]
+mod concurrent;
+mod curve;
+mod debounce;
+mod error;
+#[cfg(feature = "tokio")]
+mod events;
+mod evict;
+#[cfg(feature = "http")]
+mod http_cache;
+#[cfg(any(
+    not(feature = "std"),
+    all(
+        feature = "wasm",
+        target_arch = "wasm32",
+        not(feature = "chrono-clock")
+    )
+))]
+mod instant;
+mod jitter;
+#[cfg(feature = "std")]
+mod loading;
+#[cfg(feature = "std")]
+mod map;
+#[cfg(feature = "std")]
+mod mapped;
+mod notation;
+mod observer;
+#[cfg(feature = "redis")]
+mod redis_store;
+#[cfg(feature = "std")]
+mod refresher;
+mod retry;
+#[cfg(all(feature = "serde", not(feature = "chrono-clock")))]
+mod serde_std;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "sled")]
+mod sled_store;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod spawn;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "tokio")]
+mod subscribe;
+#[cfg(feature = "std")]
+mod tiered;
+#[cfg(all(feature = "serde", feature = "chrono-clock"))]
+pub mod timestamp;
+#[cfg(feature = "tower")]
+mod tower;
+#[cfg(all(
+    feature = "chrono-clock",
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+mod wall;
+
+#[cfg(feature = "std")]
+pub use atomic::{AtomicBucket, AtomicValue};
+#[cfg(feature = "chrono-clock")]
+pub use boundary::Boundary;
+pub use breaker::{BreakerConfig, BreakerState};
+pub use clock::Clock;
+#[cfg(feature = "std")]
+pub use clock::{ManualClock, SystemClock};
+#[cfg(feature = "concurrent")]
+pub use concurrent::ConcurrentBucketMap;
+pub use debounce::Debouncer;
+pub use error::{
+    CacheMiss, Error, Exhausted, FallbackError, ParsePolicyError, PolicyError, TimeoutError,
+};
+#[cfg(feature = "std")]
+pub use error::{NoSpawner, StoreError};
+#[cfg(feature = "tokio")]
+pub use events::CacheEvent;
+#[cfg(feature = "http")]
+pub use http_cache::HttpCache;
+#[cfg(feature = "std")]
+pub use loading::{LoadingBucket, SharedLoadingBucket};
+#[cfg(feature = "std")]
+pub use map::{Batch, BucketMap, EntryInfo};
+#[cfg(feature = "std")]
+pub use mapped::MappedBucket;
+pub use observer::BucketObserver;
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+#[cfg(feature = "std")]
+pub use refresher::{BlockingRefresherHandle, RefreshSchedule, RefresherHandle};
+pub use retry::RetryPolicy;
+#[cfg(feature = "std")]
+pub use shared::SharedBucket;
+#[cfg(feature = "sled")]
+pub use sled_store::SledStore;
+#[cfg(feature = "async-std")]
+pub use spawn::AsyncStdSpawner;
+#[cfg(feature = "smol")]
+pub use spawn::SmolSpawner;
+#[cfg(feature = "tokio")]
+pub use spawn::TokioSpawner;
+#[cfg(feature = "std")]
+pub use spawn::{BoxFuture, Spawner};
 #[cfg(feature = "serde")]
-type Instant = chrono::DateTime<chrono::Utc>;
+pub use store::FileStore;
+#[cfg(feature = "std")]
+pub use store::{PersistentBucket, Store};
+#[cfg(feature = "std")]
+pub use tiered::TieredBucket;
+#[cfg(feature = "tower")]
+pub use tower::{Cache, CacheLayer};
+/// # Example
+/// ```
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// static LOADS: AtomicU32 = AtomicU32::new(0);
+///
+/// #[uchimizu::memoize(policy = "secs:300")]
+/// async fn load_config(env: &str) -> String {
+///     LOADS.fetch_add(1, Ordering::SeqCst);
+///     format!("config for {}", env)
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// assert_eq!(load_config("prod").await, "config for prod");
+/// assert_eq!(load_config("prod").await, "config for prod");
+/// assert_eq!(load_config("dev").await, "config for dev");
+/// assert_eq!(LOADS.load(Ordering::SeqCst), 2);
+///
+/// // Without parameters, every call shares one bucket.
+/// #[uchimizu::memoize(policy = "counts:1")]
+/// async fn next_id() -> u32 {
+///     LOADS.fetch_add(1, Ordering::SeqCst)
+/// }
+/// assert_eq!(next_id().await, 2);
+/// assert_eq!(next_id().await, 2);
+/// assert_eq!(next_id().await, 3);
+/// # }
+/// ```
+///
+/// Parameters that cannot be kept as a key are rejected at compile time:
+/// ```compile_fail
+/// #[uchimizu::memoize(policy = "secs:300")]
+/// async fn append(log: &mut Vec<String>) {}
+/// ```
+#[cfg(feature = "macros")]
+pub use uchimizu_macros::memoize;
 
-#[cfg(not(feature = "serde"))]
-type Duration = std::time::Duration;
+use breaker::Breaker;
+use curve::Curve;
+use evict::Evict;
+use jitter::Jitter;
+use observer::Observers;
 #[cfg(feature = "serde")]
-type Duration = chrono::TimeDelta;
+use snapshot::Version;
+#[cfg(feature = "tokio")]
+use subscribe::Publisher;
 
+/// The point-in-time type used by buckets; wall-clock time with
+/// `chrono-clock`.
+///
+/// With `serde`, buckets serialize it as the time elapsed so far and
+/// restore it relative to when they are deserialized.
+#[cfg(all(
+    feature = "std",
+    not(feature = "chrono-clock"),
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+pub type Instant = std::time::Instant;
+/// The point-in-time type used by buckets; wall-clock time with
+/// `chrono-clock`.
+#[cfg(feature = "chrono-clock")]
+pub type Instant = chrono::DateTime<chrono::Utc>;
+#[cfg(any(
+    not(feature = "std"),
+    all(
+        feature = "wasm",
+        target_arch = "wasm32",
+        not(feature = "chrono-clock")
+    )
+))]
+pub use instant::Instant;
+
+/// The duration type matching [`Instant`].
+#[cfg(not(feature = "chrono-clock"))]
+pub type Duration = core::time::Duration;
+/// The duration type matching [`Instant`].
+#[cfg(feature = "chrono-clock")]
+pub type Duration = chrono::TimeDelta;
+
+#[cfg(feature = "std")]
 fn now() -> Instant {
-    #[cfg(not(feature = "serde"))]
+    #[cfg(all(
+        feature = "wasm",
+        target_arch = "wasm32",
+        not(feature = "chrono-clock")
+    ))]
+    return instant::date_now();
+    #[cfg(all(
+        feature = "std",
+        not(feature = "chrono-clock"),
+        not(feature = "tokio"),
+        not(all(feature = "wasm", target_arch = "wasm32"))
+    ))]
     return std::time::Instant::now();
-    #[cfg(feature = "serde")]
+    #[cfg(all(
+        not(feature = "chrono-clock"),
+        feature = "tokio",
+        not(all(feature = "wasm", target_arch = "wasm32"))
+    ))]
+    return tokio::time::Instant::now().into_std();
+    #[cfg(all(
+        feature = "chrono-clock",
+        not(all(feature = "wasm", target_arch = "wasm32"))
+    ))]
+    return wall::now();
+    #[cfg(all(feature = "chrono-clock", feature = "wasm", target_arch = "wasm32"))]
     return chrono::Utc::now();
 }
 
-fn duration_secs(d: Duration) -> u32 {
-    #[cfg(not(feature = "serde"))]
-    return d.as_secs().try_into().unwrap();
-    #[cfg(feature = "serde")]
-    return d.num_seconds().try_into().unwrap();
+fn duration_millis(d: Duration) -> u64 {
+    #[cfg(not(feature = "chrono-clock"))]
+    return d.as_millis().try_into().unwrap_or(u64::MAX);
+    #[cfg(feature = "chrono-clock")]
+    return d.num_milliseconds().try_into().unwrap_or(0);
+}
+
+fn millis_duration(millis: u64) -> Duration {
+    #[cfg(not(feature = "chrono-clock"))]
+    return core::time::Duration::from_millis(millis);
+    #[cfg(feature = "chrono-clock")]
+    return chrono::TimeDelta::try_milliseconds(millis.try_into().unwrap_or(i64::MAX))
+        .unwrap_or(chrono::TimeDelta::MAX);
+}
+
+/// Converts to milliseconds, rounding up. Returns `None` if negative or too
+/// large.
+fn duration_millis_ceil(d: Duration) -> Option<u64> {
+    #[cfg(not(feature = "chrono-clock"))]
+    return d.as_nanos().div_ceil(1_000_000).try_into().ok();
+    #[cfg(feature = "chrono-clock")]
+    return {
+        let millis = d.num_milliseconds();
+        let rest = d - chrono::TimeDelta::milliseconds(millis);
+        let millis = if rest > chrono::TimeDelta::zero() {
+            millis.checked_add(1)?
+        } else {
+            millis
+        };
+        millis.try_into().ok()
+    };
+}
+
+fn saturating_sub(a: Duration, b: Duration) -> Duration {
+    #[cfg(not(feature = "chrono-clock"))]
+    return a.saturating_sub(b);
+    #[cfg(feature = "chrono-clock")]
+    return (a - b).max(chrono::TimeDelta::zero());
+}
+
+/// Moves `at` back by `d`, stopping at the earliest representable instant.
+/// A negative `d` leaves it unchanged.
+fn saturating_instant_sub(at: Instant, d: Duration) -> Instant {
+    #[cfg(not(feature = "chrono-clock"))]
+    return at.checked_sub(d).unwrap_or_else(|| {
+        let mut at = at;
+        let mut step = d;
+        while !step.is_zero() {
+            match at.checked_sub(step) {
+                Some(earlier) => at = earlier,
+                None => step /= 2,
+            }
+        }
+        at
+    });
+    #[cfg(feature = "chrono-clock")]
+    return at
+        .checked_sub_signed(d.max(chrono::TimeDelta::zero()))
+        .unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+}
+
+fn elapsed_since(earlier: Instant, now: Instant) -> Duration {
+    #[cfg(not(feature = "chrono-clock"))]
+    return now.saturating_duration_since(earlier);
+    #[cfg(feature = "chrono-clock")]
+    return (now - earlier).max(chrono::TimeDelta::zero());
 }
 
 #[cfg_attr(not(feature = "serde"), derive(Debug, Clone, PartialEq, Eq))]
@@ -29,70 +306,362 @@ fn duration_secs(d: Duration) -> u32 {
     feature = "serde",
     derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)
 )]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Policy {
-    initial_amount: u32,
-    pour_cost: u32,
-    evaporation_cost: u32,
+    rule: Rule,
+}
+
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, PartialEq, Eq))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)
+)]
+#[cfg_attr(feature = "serde", serde(untagged))]
+enum Rule {
+    Linear(Linear),
+    AnyOf { any_of: Vec<Policy> },
+    AllOf { all_of: Vec<Policy> },
+}
+
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, PartialEq, Eq))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)
+)]
+struct Linear {
+    initial_amount: u64,
+    pour_cost: u64,
+    evaporation_cost: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Curve::is_linear")
+    )]
+    curve: Curve,
+    /// The consumption at which the value turns stale, see
+    /// [`Policy::with_soft_threshold`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    soft_threshold: Option<u64>,
+}
+
+/// Where [`Policy::with_soft_threshold`] draws the line, converted from a
+/// float for a fraction of the budget or from a `u64` for an amount.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoftThreshold {
+    /// A fraction of `initial_amount`, clamped to `0.0..=1.0`.
+    Fraction(f64),
+    /// An amount of consumption.
+    Amount(u64),
+}
+
+impl From<f64> for SoftThreshold {
+    fn from(fraction: f64) -> Self {
+        SoftThreshold::Fraction(fraction)
+    }
+}
+
+impl From<f32> for SoftThreshold {
+    fn from(fraction: f32) -> Self {
+        SoftThreshold::Fraction(fraction.into())
+    }
+}
+
+impl From<u64> for SoftThreshold {
+    fn from(amount: u64) -> Self {
+        SoftThreshold::Amount(amount)
+    }
+}
+
+/// How a cached value stands against the soft and hard thresholds, as
+/// reported by [`Bucket::freshness`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// The value is within both thresholds.
+    Fresh,
+    /// The soft threshold has been reached: the value is still served, but
+    /// should be refreshed.
+    Stale,
+    /// The policy is exhausted, or nothing is cached.
+    Expired,
+}
+
+/// Where the value returned by [`Bucket::call_with_fallback`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The cached value was served.
+    Hit,
+    /// The primary task produced the value.
+    Primary,
+    /// The primary task failed and the secondary one produced the value.
+    Secondary,
+    /// No task produced a value, and the expired cached value was served
+    /// instead, within the grace period of [`Bucket::with_grace`] or while
+    /// the breaker of [`Bucket::with_breaker`] is open.
+    Stale,
+}
+
+impl Linear {
+    fn remaining_after(&self, hit_count: u64, duration_millis: u128) -> u64 {
+        self.initial_amount
+            .saturating_sub(self.consumed_after(hit_count, duration_millis))
+    }
+
+    fn exhausted_at_millis(&self, hit_count: u64, duration_millis: u128) -> Option<u64> {
+        if self.remaining_after(hit_count, duration_millis) == 0 {
+            return Some(0);
+        }
+        let left = self
+            .initial_amount
+            .saturating_sub(self.pour_cost.saturating_mul(hit_count));
+        self.curve.reaches_at_millis(self.evaporation_cost, left)
+    }
+
+    fn replenished_at_millis(&self, hit_count: u64, duration_millis: u128) -> Option<u64> {
+        if self.remaining_after(hit_count, duration_millis) > 0 {
+            return Some(0);
+        }
+        if !matches!(self.curve, Curve::Refill)
+            || self.initial_amount == 0
+            || self.evaporation_cost == 0
+        {
+            return None;
+        }
+        let pour_amount = self.pour_cost.saturating_mul(hit_count);
+        let needed = u128::from(pour_amount - self.initial_amount) + 1;
+        let millis = (needed * 1000).div_ceil(u128::from(self.evaporation_cost));
+        Some(millis.try_into().unwrap_or(u64::MAX))
+    }
+
+    /// Whether refill has put back everything poured.
+    fn is_refilled(&self, hit_count: u64, duration_millis: u128) -> bool {
+        matches!(self.curve, Curve::Refill) && self.consumed_after(hit_count, duration_millis) == 0
+    }
+
+    fn consumed_after(&self, hit_count: u64, duration_millis: u128) -> u64 {
+        let pour_amount = self.pour_cost.saturating_mul(hit_count);
+        self.curve
+            .consumed(pour_amount, self.evaporation_cost, duration_millis)
+    }
+
+    /// Whether less than the soft threshold has been consumed; always true
+    /// without one.
+    fn is_soft_remaining(&self, hit_count: u64, duration_millis: u128) -> bool {
+        self.soft_threshold.is_none_or(|threshold| {
+            self.consumed_after(hit_count, duration_millis) < threshold.min(self.initial_amount)
+        })
+    }
+}
+
+/// Rounds `value` to a whole number of [`Policy::FRACTION_SCALE`] units.
+fn fixed_point(value: f64) -> Result<u64, PolicyError> {
+    if value.is_nan() || value < 0.0 {
+        return Err(PolicyError::InvalidFraction);
+    }
+    let scaled = value * Policy::FRACTION_SCALE as f64 + 0.5;
+    // `u64::MAX as f64` rounds up to 2^64, which no longer fits.
+    if scaled >= u64::MAX as f64 {
+        return Err(PolicyError::Overflow);
+    }
+    Ok(scaled as u64)
 }
 
 impl Policy {
+    /// Creates a policy without validating it.
+    ///
+    /// Neither this nor the presets check their parameters; use
+    /// [`Policy::try_new`] for values coming from configuration. See
+    /// [`Policy::new_u64`] for amounts beyond `u32`.
     pub fn new(initial_amount: u32, pour_cost: u32, evaporation_cost: u32) -> Policy {
+        Policy::new_u64(
+            u64::from(initial_amount),
+            u64::from(pour_cost),
+            u64::from(evaporation_cost),
+        )
+    }
+
+    /// Like [`Policy::new`], with amounts that do not fit in a `u32`, such
+    /// as a budget in bytes.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let five_gb = 5 * 1024 * 1024 * 1024;
+    /// let p = Policy::new_u64(five_gb, 1, 0);
+    /// assert!(p.is_remaining_millis(five_gb - 1, 0));
+    /// assert!(!p.is_remaining_millis(five_gb, 0));
+    /// ```
+    pub fn new_u64(initial_amount: u64, pour_cost: u64, evaporation_cost: u64) -> Policy {
         Policy {
-            initial_amount,
-            pour_cost,
-            evaporation_cost,
+            rule: Rule::Linear(Linear {
+                initial_amount,
+                pour_cost,
+                evaporation_cost,
+                curve: Curve::Linear,
+                soft_threshold: None,
+            }),
         }
     }
 
+    /// A token bucket: each pour takes `pour_cost` out of `capacity`, and
+    /// time puts `refill_per_sec` back, pro rata.
+    ///
+    /// The amount is computed from the pours and time since the last
+    /// refresh. A bucket restarts that accounting whenever time has put
+    /// back everything poured, so refill earned while idle is capped at
+    /// `capacity`, see [`Expiry::is_refilled`]. The policy never expires by
+    /// time; [`Policy::initial_amount`] and [`Policy::evaporation_cost`]
+    /// return `capacity` and `refill_per_sec`.
+    ///
     /// # Example
     /// ```
     /// use uchimizu::Policy;
     ///
-    /// let p = Policy::bottom_less();
-    /// assert!(p.is_remaining(0, 0));
-    /// assert!(p.is_remaining(0, 1));
-    /// assert!(p.is_remaining(1, 1));
+    /// let p = Policy::replenishing(3, 1, 1);
+    /// assert!(p.is_remaining_millis(2, 0));
+    /// assert!(!p.is_remaining_millis(3, 0));
+    /// assert!(p.is_remaining_millis(3, 1_000));
+    /// assert_eq!(p.remaining_after(3, 2), 2);
     /// ```
-    pub fn bottom_less() -> Policy {
+    pub fn replenishing(capacity: u32, pour_cost: u32, refill_per_sec: u32) -> Policy {
+        Policy::replenishing_u64(
+            u64::from(capacity),
+            u64::from(pour_cost),
+            u64::from(refill_per_sec),
+        )
+    }
+
+    /// Like [`Policy::replenishing`], with amounts that do not fit in a
+    /// `u32`.
+    pub fn replenishing_u64(capacity: u64, pour_cost: u64, refill_per_sec: u64) -> Policy {
         Policy {
-            initial_amount: 1,
-            pour_cost: 0,
-            evaporation_cost: 0,
+            rule: Rule::Linear(Linear {
+                initial_amount: capacity,
+                pour_cost,
+                evaporation_cost: refill_per_sec,
+                curve: Curve::Refill,
+                soft_threshold: None,
+            }),
+        }
+    }
+
+    /// Creates a policy, rejecting degenerate parameters.
+    ///
+    /// A zero `initial_amount` is rejected because the policy would always
+    /// be exhausted; use [`Policy::pierced`] if that is intended.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Policy, PolicyError};
+    ///
+    /// assert_eq!(Policy::try_new(100, 1, 0), Ok(Policy::new(100, 1, 0)));
+    /// assert_eq!(Policy::try_new(0, 0, 0), Err(PolicyError::AllZero));
+    /// assert_eq!(Policy::try_new(0, 1, 1), Err(PolicyError::ZeroInitialAmount));
+    /// ```
+    pub fn try_new(
+        initial_amount: u64,
+        pour_cost: u64,
+        evaporation_cost: u64,
+    ) -> Result<Policy, PolicyError> {
+        match (initial_amount, pour_cost, evaporation_cost) {
+            (0, 0, 0) => Err(PolicyError::AllZero),
+            (0, _, _) => Err(PolicyError::ZeroInitialAmount),
+            (_, _, _) => Ok(Policy::new_u64(initial_amount, pour_cost, evaporation_cost)),
         }
     }
 
+    /// How many units of budget a whole unit passed to
+    /// [`Policy::new_fractional`] is worth.
+    pub const FRACTION_SCALE: u64 = 1_000_000_000;
+
+    /// Creates a policy from fractional parameters, such as a pour that
+    /// costs a quarter of the budget or an evaporation of one budget per
+    /// ten minutes.
+    ///
+    /// The parameters are rounded to billionths and kept as integers, so
+    /// the policy behaves exactly like [`Policy::new`] with every parameter
+    /// multiplied by [`Policy::FRACTION_SCALE`], which is also what the
+    /// accessors and [`Bucket::remaining`] report. Pours therefore add up
+    /// without float drift, and the policy is remaining while strictly
+    /// less than `initial` has been consumed. The parameters are validated
+    /// like [`Policy::try_new`], and NaN or negative ones are rejected.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Policy, PolicyError};
+    ///
+    /// let p = Policy::new_fractional(1.0, 0.1, 0.0).unwrap();
+    /// assert!(p.is_remaining_millis(9, 0));
+    /// // Ten pours of 0.1 consume exactly 1.0.
+    /// assert!(!p.is_remaining_millis(10, 0));
+    ///
+    /// let p = Policy::new_fractional(1.0, 0.0, 1.0 / 600.0).unwrap();
+    /// assert!(p.is_remaining_millis(0, 599_000));
+    /// assert!(!p.is_remaining_millis(0, 600_000));
+    ///
+    /// assert_eq!(Policy::new_fractional(f64::NAN, 1.0, 0.0), Err(PolicyError::InvalidFraction));
+    /// assert_eq!(Policy::new_fractional(1e12, 1.0, 0.0), Err(PolicyError::Overflow));
+    /// ```
+    pub fn new_fractional(
+        initial: f64,
+        pour: f64,
+        evap_per_sec: f64,
+    ) -> Result<Policy, PolicyError> {
+        Policy::try_new(
+            fixed_point(initial)?,
+            fixed_point(pour)?,
+            fixed_point(evap_per_sec)?,
+        )
+    }
+
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::bottom_less();
+    /// assert!(p.is_remaining_millis(0, 0));
+    /// assert!(p.is_remaining_millis(0, 1_000));
+    /// assert!(p.is_remaining_millis(1, 1_000));
+    /// ```
+    pub fn bottom_less() -> Policy {
+        Policy::new_u64(1, 0, 0)
+    }
+
     /// # Example
     /// ```
     /// use uchimizu::Policy;
     ///
     /// let p = Policy::pierced();
-    /// assert!(!p.is_remaining(0, 0));
-    /// assert!(!p.is_remaining(0, 1));
-    /// assert!(!p.is_remaining(1, 1));
+    /// assert!(!p.is_remaining_millis(0, 0));
+    /// assert!(!p.is_remaining_millis(0, 1_000));
+    /// assert!(!p.is_remaining_millis(1, 1_000));
     /// ```
     pub fn pierced() -> Policy {
-        Policy {
-            initial_amount: 0,
-            pour_cost: 1,
-            evaporation_cost: 1,
-        }
+        Policy::new_u64(0, 1, 1)
     }
 
+    /// A bucket with this policy serves its cached value `count` times
+    /// after each refresh.
+    ///
     /// # Example
     /// ```
     /// use uchimizu::Policy;
     ///
     /// let p = Policy::expire_within_counts(5);
-    /// assert!(p.is_remaining(4, 1000));
-    /// assert!(!p.is_remaining(5, 1000));
-    /// assert!(!p.is_remaining(6, 1000));
+    /// assert!(p.is_remaining_millis(4, 1_000_000));
+    /// assert!(!p.is_remaining_millis(5, 1_000_000));
+    /// assert!(!p.is_remaining_millis(6, 1_000_000));
     /// ```
     pub fn expire_within_counts(count: u32) -> Policy {
-        Policy {
-            initial_amount: count,
-            pour_cost: 1,
-            evaporation_cost: 0,
-        }
+        Policy::expire_within_counts_u64(u64::from(count))
+    }
+
+    /// Like [`Policy::expire_within_counts`], with a count that does not
+    /// fit in a `u32`.
+    pub fn expire_within_counts_u64(count: u64) -> Policy {
+        Policy::new_u64(count, 1, 0)
     }
 
     /// # Example
@@ -100,146 +669,4428 @@ impl Policy {
     /// use uchimizu::Policy;
     ///
     /// let p = Policy::expire_within_secs(5);
-    /// assert!(p.is_remaining(1000, 4));
-    /// assert!(!p.is_remaining(1000, 5));
-    /// assert!(!p.is_remaining(1000, 6));
+    /// assert!(p.is_remaining_millis(1000, 4_000));
+    /// assert!(!p.is_remaining_millis(1000, 5_000));
+    /// assert!(!p.is_remaining_millis(1000, 6_000));
     /// ```
     pub fn expire_within_secs(secs: u32) -> Policy {
-        Policy {
-            initial_amount: secs,
-            pour_cost: 0,
-            evaporation_cost: 1,
-        }
+        Policy::expire_within_secs_u64(u64::from(secs))
     }
 
-    pub fn is_remaining(&self, hit_count: u32, duration_secs: u32) -> bool {
-        let pour_amount = self.pour_cost * hit_count;
-        let evaporation_amount = self.evaporation_cost * duration_secs;
-        pour_amount + evaporation_amount < self.initial_amount
+    /// Like [`Policy::expire_within_secs`], with seconds that do not fit in
+    /// a `u32`.
+    pub fn expire_within_secs_u64(secs: u64) -> Policy {
+        Policy::expire_within_millis(secs.saturating_mul(1000))
     }
 
-    pub fn into_bucket<T>(self) -> Bucket<T> {
-        Bucket {
-            cache: None,
-            policy: self,
-            hit_count: 0,
-            initiate: now(),
-        }
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::expire_within_millis(250);
+    /// assert!(p.is_remaining_millis(1000, 249));
+    /// assert!(!p.is_remaining_millis(1000, 250));
+    /// assert!(!p.is_remaining_millis(1000, 251));
+    /// ```
+    pub fn expire_within_millis(millis: u64) -> Policy {
+        Policy::new_u64(millis, 0, 1000)
     }
-}
 
-#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, PartialEq, Eq))]
-#[cfg_attr(
-    feature = "serde",
-    derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)
-)]
-pub struct Bucket<T> {
-    cache: Option<T>,
-    policy: Policy,
-    hit_count: u32,
-    initiate: Instant,
-}
+    /// Expires after `count` pours or `secs` seconds, whichever comes
+    /// first; the same as [`Policy::any_of`] the two presets.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::expire_within_counts_or_secs(3, 60);
+    /// // The pours run out first.
+    /// assert!(p.is_remaining_millis(2, 10_000));
+    /// assert!(!p.is_remaining_millis(3, 10_000));
+    /// // The time runs out first.
+    /// assert!(p.is_remaining_millis(1, 59_000));
+    /// assert!(!p.is_remaining_millis(1, 60_000));
+    /// ```
+    pub fn expire_within_counts_or_secs(count: u32, secs: u32) -> Policy {
+        Policy::expire_within_counts_or_secs_u64(u64::from(count), u64::from(secs))
+    }
 
-pub trait Task<T> {
-    fn call(&self) -> T;
-}
+    /// Like [`Policy::expire_within_counts_or_secs`], with a count or
+    /// seconds that do not fit in a `u32`.
+    pub fn expire_within_counts_or_secs_u64(count: u64, secs: u64) -> Policy {
+        Policy::any_of(alloc::vec![
+            Policy::expire_within_counts_u64(count),
+            Policy::expire_within_secs_u64(secs),
+        ])
+    }
 
-impl<F, T> Task<T> for F
-where
-    F: Fn() -> T,
-{
-    fn call(&self) -> T {
-        (self)()
+    /// Expires after `d`, rounded up to the next millisecond.
+    ///
+    /// # Panics
+    /// Panics if `d` is negative or too large; see
+    /// [`Policy::try_expire_within`].
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// let p = Policy::expire_within(std::time::Duration::from_micros(1500));
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # let p = Policy::expire_within(chrono::TimeDelta::microseconds(1500));
+    /// assert_eq!(p, Policy::expire_within_millis(2));
+    /// ```
+    pub fn expire_within(d: impl Into<Duration>) -> Policy {
+        Policy::try_expire_within(d).expect("duration out of range")
     }
-}
 
-impl<T> AsRef<Policy> for Bucket<T> {
-    fn as_ref(&self) -> &Policy {
-        &self.policy
+    /// Like [`Policy::expire_within`], but rejects durations that cannot be
+    /// represented instead of panicking.
+    pub fn try_expire_within(d: impl Into<Duration>) -> Result<Policy, PolicyError> {
+        duration_millis_ceil(d.into())
+            .map(Policy::expire_within_millis)
+            .ok_or(PolicyError::DurationOutOfRange)
     }
-}
 
-impl<T> Bucket<T>
-where
-    T: Clone,
-{
-    pub async fn call<F, Fut>(&mut self, task: F) -> T
-    where
-        F: Task<Fut>,
-        Fut: Future<Output = T>,
-    {
-        let entry = match (
-            self.policy
-                .is_remaining(self.hit_count, duration_secs(now() - self.initiate)),
-            self.cache.clone(),
-        ) {
-            (true, Some(c)) => c,
-            (_, _) => {
-                self.refresh();
-                let entry = task.call().await;
-                self.cache = Some(entry.clone());
-                entry
-            }
-        };
-        self.hit_count += 1;
-        entry
+    /// Returns whether the policy is still remaining after `hit_count`
+    /// pours and `elapsed` time, as a bucket judges its value.
+    ///
+    /// `elapsed` is truncated to whole milliseconds, as a bucket does, and
+    /// a negative duration counts as zero.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// # let millis = |m: f64| std::time::Duration::from_secs_f64(m / 1000.0);
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # let millis = |m: f64| chrono::TimeDelta::microseconds((m * 1000.0) as i64);
+    /// let p = Policy::expire_within_millis(1500);
+    /// assert!(p.is_remaining_at(0, millis(1499.9)));
+    /// assert!(!p.is_remaining_at(0, millis(1500.0)));
+    /// assert!(Policy::expire_within_millis(1).is_remaining_at(0, millis(0.5)));
+    /// ```
+    ///
+    /// Once the consumed amount reaches `initial_amount` the policy stays
+    /// exhausted; the arithmetic saturates instead of wrapping around:
+    ///
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::new(100, 10, 10);
+    /// assert!(!p.is_remaining_millis(u64::MAX, 0));
+    /// assert!(!p.is_remaining_millis(0, u64::MAX));
+    /// assert!(!p.is_remaining_millis(u64::MAX, u64::MAX));
+    /// ```
+    pub fn is_remaining_at(&self, hit_count: u64, elapsed: Duration) -> bool {
+        self.is_remaining_millis(hit_count, duration_millis(elapsed))
     }
 
-    pub fn refresh(&mut self) {
-        self.hit_count = 0;
-        self.cache = None;
-        self.initiate = now();
+    /// Like [`Policy::is_remaining_at`], with the elapsed time in whole
+    /// seconds.
+    #[deprecated(
+        since = "0.0.2",
+        note = "the unit is easy to get wrong, use `Policy::is_remaining_at` instead"
+    )]
+    pub fn is_remaining(&self, hit_count: u64, duration_secs: u64) -> bool {
+        self.is_remaining_exact(hit_count, u128::from(duration_secs) * 1000)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Like [`Policy::is_remaining_at`], with the elapsed time in
+    /// milliseconds.
+    ///
+    /// `evaporation_cost` is still charged per second, pro rata.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::expire_within_secs(1);
+    /// assert!(p.is_remaining_millis(0, 999));
+    /// assert!(!p.is_remaining_millis(0, 1000));
+    /// ```
+    pub fn is_remaining_millis(&self, hit_count: u64, duration_millis: u64) -> bool {
+        self.is_remaining_exact(hit_count, u128::from(duration_millis))
+    }
 
-    async fn wait_50_millis() {
-        tokio::time::sleep(std::time::Duration::from_millis(50)).await
+    fn is_remaining_exact(&self, hit_count: u64, duration_millis: u128) -> bool {
+        match &self.rule {
+            Rule::Linear(l) => l.remaining_after(hit_count, duration_millis) > 0,
+            Rule::AnyOf { any_of } => any_of
+                .iter()
+                .all(|p| p.is_remaining_exact(hit_count, duration_millis)),
+            Rule::AllOf { all_of } => all_of
+                .iter()
+                .any(|p| p.is_remaining_exact(hit_count, duration_millis)),
+        }
     }
 
-    #[tokio::test]
-    async fn it_works() {
-        let mut b = Policy::new(100, 10, 50).into_bucket();
-        b.call(wait_50_millis).await;
+    /// Expires as soon as any of `policies` is expired.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::any_of(vec![
+    ///     Policy::expire_within_counts(100),
+    ///     Policy::expire_within_secs(60),
+    /// ]);
+    /// assert!(p.is_remaining_millis(99, 59_000));
+    /// assert!(!p.is_remaining_millis(100, 0));
+    /// assert!(!p.is_remaining_millis(0, 60_000));
+    /// ```
+    pub fn any_of(policies: Vec<Policy>) -> Policy {
+        Policy {
+            rule: Rule::AnyOf { any_of: policies },
+        }
     }
 
-    #[tokio::test]
-    async fn bottom_less_works() {
-        let mut b = Policy::bottom_less().into_bucket();
-        b.call(wait_50_millis).await;
+    /// Expires only once all of `policies` are expired.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::all_of(vec![
+    ///     Policy::expire_within_counts(100),
+    ///     Policy::expire_within_secs(60),
+    /// ]);
+    /// assert!(p.is_remaining_millis(100, 0));
+    /// assert!(p.is_remaining_millis(0, 60_000));
+    /// assert!(!p.is_remaining_millis(100, 60_000));
+    /// ```
+    pub fn all_of(policies: Vec<Policy>) -> Policy {
+        Policy {
+            rule: Rule::AllOf { all_of: policies },
+        }
     }
 
-    #[tokio::test]
-    async fn pierced_works() {
-        let mut b = Policy::pierced().into_bucket();
-        b.call(wait_50_millis).await;
+    /// Returns how much of `initial_amount` is left, saturating at zero.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::new(100, 10, 1);
+    /// assert_eq!(p.remaining_after(0, 0), 100);
+    /// assert_eq!(p.remaining_after(5, 20), 30);
+    /// assert_eq!(p.remaining_after(u64::MAX, 0), 0);
+    /// ```
+    pub fn remaining_after(&self, hit_count: u64, duration_secs: u64) -> u64 {
+        self.remaining_after_exact(hit_count, u128::from(duration_secs) * 1000)
     }
 
-    #[tokio::test]
-    async fn expire_within_secs_works() {
-        let mut b = Policy::expire_within_secs(1).into_bucket();
-        b.call(wait_50_millis).await;
+    /// Like [`Policy::remaining_after`], with the elapsed time in
+    /// milliseconds.
+    ///
+    /// For [`Policy::any_of`] this is the least remaining amount of the
+    /// children, for [`Policy::all_of`] the greatest.
+    pub fn remaining_after_millis(&self, hit_count: u64, duration_millis: u64) -> u64 {
+        self.remaining_after_exact(hit_count, u128::from(duration_millis))
     }
 
-    #[tokio::test]
-    async fn expire_within_counts_works() {
-        let mut b = Policy::expire_within_counts(1).into_bucket();
-        b.call(wait_50_millis).await;
+    fn remaining_after_exact(&self, hit_count: u64, duration_millis: u128) -> u64 {
+        match &self.rule {
+            Rule::Linear(l) => l.remaining_after(hit_count, duration_millis),
+            Rule::AnyOf { any_of } => any_of
+                .iter()
+                .map(|p| p.remaining_after_exact(hit_count, duration_millis))
+                .min()
+                .unwrap_or(u64::MAX),
+            Rule::AllOf { all_of } => all_of
+                .iter()
+                .map(|p| p.remaining_after_exact(hit_count, duration_millis))
+                .max()
+                .unwrap_or(0),
+        }
     }
 
-    #[tokio::test]
+    /// Returns the milliseconds after which the policy is exhausted by
+    /// evaporation, or `None` if it never evaporates.
+    fn exhausted_at_millis(&self, hit_count: u64, duration_millis: u128) -> Option<u64> {
+        match &self.rule {
+            Rule::Linear(l) => l.exhausted_at_millis(hit_count, duration_millis),
+            Rule::AnyOf { any_of } => any_of
+                .iter()
+                .filter_map(|p| p.exhausted_at_millis(hit_count, duration_millis))
+                .min(),
+            Rule::AllOf { all_of } => all_of
+                .iter()
+                .map(|p| p.exhausted_at_millis(hit_count, duration_millis))
+                .try_fold(0, |acc, at| Some(acc.max(at?))),
+        }
+    }
+
+    /// Returns the milliseconds after which the policy is remaining again
+    /// by refill, or `None` if time never replenishes it.
+    fn replenished_at_millis(&self, hit_count: u64, duration_millis: u128) -> Option<u64> {
+        match &self.rule {
+            Rule::Linear(l) => l.replenished_at_millis(hit_count, duration_millis),
+            Rule::AnyOf { any_of } => any_of
+                .iter()
+                .map(|p| p.replenished_at_millis(hit_count, duration_millis))
+                .try_fold(0, |acc, at| Some(acc.max(at?))),
+            Rule::AllOf { all_of } => all_of
+                .iter()
+                .filter_map(|p| p.replenished_at_millis(hit_count, duration_millis))
+                .min(),
+        }
+    }
+
+    /// For composite policies this is the consumption of the child that is
+    /// closest to (for [`Policy::any_of`]) or furthest from (for
+    /// [`Policy::all_of`]) being exhausted.
+    fn consumed_after(&self, hit_count: u64, duration_millis: u128) -> u64 {
+        match &self.rule {
+            Rule::Linear(l) => l.consumed_after(hit_count, duration_millis),
+            Rule::AnyOf { any_of } => any_of
+                .iter()
+                .map(|p| p.consumed_after(hit_count, duration_millis))
+                .max()
+                .unwrap_or(0),
+            Rule::AllOf { all_of } => all_of
+                .iter()
+                .map(|p| p.consumed_after(hit_count, duration_millis))
+                .min()
+                .unwrap_or(u64::MAX),
+        }
+    }
+
+    /// A composite policy is refilled once all of its children are, so
+    /// that restarting the accounting takes nothing from any of them.
+    fn is_refilled_exact(&self, hit_count: u64, duration_millis: u128) -> bool {
+        match &self.rule {
+            Rule::Linear(l) => l.is_refilled(hit_count, duration_millis),
+            Rule::AnyOf { any_of: policies } | Rule::AllOf { all_of: policies } => policies
+                .iter()
+                .all(|p| p.is_refilled_exact(hit_count, duration_millis)),
+        }
+    }
+
+    /// Like `is_remaining`, against the soft thresholds. A child without
+    /// one is never stale.
+    fn is_soft_remaining_exact(&self, hit_count: u64, duration_millis: u128) -> bool {
+        match &self.rule {
+            Rule::Linear(l) => l.is_soft_remaining(hit_count, duration_millis),
+            Rule::AnyOf { any_of } => any_of
+                .iter()
+                .all(|p| p.is_soft_remaining_exact(hit_count, duration_millis)),
+            Rule::AllOf { all_of } => all_of
+                .iter()
+                .any(|p| p.is_soft_remaining_exact(hit_count, duration_millis)),
+        }
+    }
+
+    /// Returns a policy that is exhausted once `ratio` of each budget has
+    /// been consumed.
+    fn portion(&self, ratio: f32) -> Policy {
+        self.map_linear(&|l| Linear {
+            initial_amount: (l.initial_amount as f64 * f64::from(ratio)) as u64,
+            ..l.clone()
+        })
+    }
+
+    /// Returns the parameters of a policy made with [`Policy::new`] or a
+    /// preset, or `None` for composite policies.
+    fn linear(&self) -> Option<&Linear> {
+        match &self.rule {
+            Rule::Linear(l) => Some(l),
+            Rule::AnyOf { .. } | Rule::AllOf { .. } => None,
+        }
+    }
+
+    /// `None` for [`Policy::any_of`] and [`Policy::all_of`].
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::expire_within_secs(60);
+    /// assert_eq!(p.initial_amount(), Some(60_000));
+    /// assert_eq!(p.pour_cost(), Some(0));
+    /// assert_eq!(p.evaporation_cost(), Some(1000));
+    /// ```
+    pub fn initial_amount(&self) -> Option<u64> {
+        self.linear().map(|l| l.initial_amount)
+    }
+
+    /// `None` for [`Policy::any_of`] and [`Policy::all_of`].
+    pub fn pour_cost(&self) -> Option<u64> {
+        self.linear().map(|l| l.pour_cost)
+    }
+
+    /// `None` for [`Policy::any_of`] and [`Policy::all_of`].
+    pub fn evaporation_cost(&self) -> Option<u64> {
+        self.linear().map(|l| l.evaporation_cost)
+    }
+
+    /// Returns a copy with `initial_amount` replaced.
+    ///
+    /// For composite policies every child is changed.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// let p = Policy::expire_within_counts(5).with_initial_amount(10);
+    /// assert_eq!(p, Policy::expire_within_counts(10));
+    /// ```
+    pub fn with_initial_amount(&self, initial_amount: u64) -> Policy {
+        self.map_linear(&|l| Linear {
+            initial_amount,
+            ..l.clone()
+        })
+    }
+
+    /// Returns a copy with `pour_cost` replaced.
+    ///
+    /// For composite policies every child is changed.
+    pub fn with_pour_cost(&self, pour_cost: u64) -> Policy {
+        self.map_linear(&|l| Linear {
+            pour_cost,
+            ..l.clone()
+        })
+    }
+
+    /// Returns a copy with `evaporation_cost` replaced.
+    ///
+    /// For composite policies every child is changed.
+    pub fn with_evaporation_cost(&self, evaporation_cost: u64) -> Policy {
+        self.map_linear(&|l| Linear {
+            evaporation_cost,
+            ..l.clone()
+        })
+    }
+
+    /// Returns a copy that evaporates `f(elapsed_millis)` instead of
+    /// `evaporation_cost` per second.
+    ///
+    /// `f` must not decrease as time goes on, and may be called with any
+    /// elapsed time up to `u64::MAX`, so it should saturate rather than
+    /// overflow. `evaporation_cost` is ignored
+    /// while a custom curve is set. For composite policies every child is
+    /// changed.
+    ///
+    /// A policy with a custom curve compares equal only to copies of
+    /// itself, and cannot be serialized.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// // Loses freshness slowly at first and quickly towards 100 seconds.
+    /// let p = Policy::new(10_000, 0, 0).with_evaporation_fn(|millis| (millis / 1000).saturating_pow(2));
+    /// assert!(p.is_remaining_millis(0, 50_000));
+    /// assert!(p.is_remaining_millis(0, 99_000));
+    /// assert!(!p.is_remaining_millis(0, 100_000));
+    /// ```
+    pub fn with_evaporation_fn(&self, f: impl Fn(u64) -> u64 + Send + Sync + 'static) -> Policy {
+        let curve = Curve::Custom(Arc::new(f));
+        self.map_linear(&|l| Linear {
+            curve: curve.clone(),
+            ..l.clone()
+        })
+    }
+
+    /// Returns a copy that turns stale once `threshold` has been consumed,
+    /// before it expires.
+    ///
+    /// The threshold is a fraction of `initial_amount` when given as a
+    /// float, or an amount of consumption when given as a `u64`; either way
+    /// it is capped at `initial_amount`. Reaching the threshold exactly
+    /// counts as stale, just as consuming exactly `initial_amount` counts
+    /// as expired.
+    ///
+    /// A stale value is still served by [`Bucket::call`], but
+    /// [`Bucket::freshness`] reports it as [`Freshness::Stale`] and
+    /// [`SharedBucket::call_swr`] starts a background refresh, as with
+    /// [`Bucket::refresh_ahead`], which takes precedence if both are set.
+    /// For composite policies every child is changed; a composite is stale
+    /// when the children that decide its expiry are.
+    ///
+    /// # Panics
+    /// Panics if the fraction is NaN.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Freshness, Policy};
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(4)
+    ///     .with_soft_threshold(0.5)
+    ///     .into_bucket();
+    /// assert_eq!(b.freshness(), Freshness::Expired);
+    /// b.call_sync(|| 1);
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.freshness(), Freshness::Fresh);
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.freshness(), Freshness::Stale);
+    /// assert_eq!(b.call_sync(|| 2), 1);
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.freshness(), Freshness::Expired);
+    ///
+    /// let p = Policy::expire_within_secs(60).with_soft_threshold(45_000);
+    /// assert_eq!(p, Policy::expire_within_secs(60).with_soft_threshold(0.75));
+    /// # }
+    /// ```
+    pub fn with_soft_threshold(&self, threshold: impl Into<SoftThreshold>) -> Policy {
+        let threshold = threshold.into();
+        if let SoftThreshold::Fraction(fraction) = threshold {
+            assert!(!fraction.is_nan(), "soft threshold is NaN");
+        }
+        self.map_linear(&|l| Linear {
+            soft_threshold: Some(match threshold {
+                SoftThreshold::Fraction(fraction) => {
+                    (l.initial_amount as f64 * fraction.clamp(0.0, 1.0)) as u64
+                }
+                SoftThreshold::Amount(amount) => amount.min(l.initial_amount),
+            }),
+            ..l.clone()
+        })
+    }
+
+    /// Multiplies all three parameters by `factor`.
+    ///
+    /// The scaled policy expires after the same pours and time, but each
+    /// unit of budget is finer, so costs can then be tuned with more
+    /// precision. For composite policies every child is scaled.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Policy, PolicyError};
+    ///
+    /// let p = Policy::new(5, 1, 0).scaled(10).unwrap();
+    /// assert_eq!(p, Policy::new(50, 10, 0));
+    /// // Now a pour can cost half as much as before.
+    /// let p = p.with_pour_cost(5);
+    /// assert!(p.is_remaining_millis(9, 0));
+    /// assert!(!p.is_remaining_millis(10, 0));
+    ///
+    /// assert_eq!(Policy::new_u64(u64::MAX, 1, 1).scaled(2), Err(PolicyError::Overflow));
+    /// ```
+    pub fn scaled(&self, factor: u64) -> Result<Policy, PolicyError> {
+        self.try_map_linear(&|l| {
+            Some(Linear {
+                initial_amount: l.initial_amount.checked_mul(factor)?,
+                pour_cost: l.pour_cost.checked_mul(factor)?,
+                evaporation_cost: l.evaporation_cost.checked_mul(factor)?,
+                curve: l.curve.scaled(factor),
+                soft_threshold: match l.soft_threshold {
+                    Some(threshold) => Some(threshold.checked_mul(factor)?),
+                    None => None,
+                },
+            })
+        })
+        .ok_or(PolicyError::Overflow)
+    }
+
+    fn map_linear(&self, f: &impl Fn(&Linear) -> Linear) -> Policy {
+        self.try_map_linear(&|l| Some(f(l))).unwrap()
+    }
+
+    fn try_map_linear(&self, f: &impl Fn(&Linear) -> Option<Linear>) -> Option<Policy> {
+        let rule = match &self.rule {
+            Rule::Linear(l) => Rule::Linear(f(l)?),
+            Rule::AnyOf { any_of } => Rule::AnyOf {
+                any_of: any_of
+                    .iter()
+                    .map(|p| p.try_map_linear(f))
+                    .collect::<Option<_>>()?,
+            },
+            Rule::AllOf { all_of } => Rule::AllOf {
+                all_of: all_of
+                    .iter()
+                    .map(|p| p.try_map_linear(f))
+                    .collect::<Option<_>>()?,
+            },
+        };
+        Some(Policy { rule })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_bucket<T>(self) -> Bucket<T> {
+        Bucket::new(self)
+    }
+
+    /// A bucket that starts out holding `value`, as if a task had just
+    /// refreshed it, see [`Bucket::replace`].
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// struct LoginResponse {
+    ///     token: String,
+    /// }
+    ///
+    /// let login = LoginResponse {
+    ///     token: "token-1".to_string(),
+    /// };
+    /// // The token came with the login, so there is no need to fetch it.
+    /// let mut tokens = Policy::expire_within_secs(3600).into_bucket_with(login.token);
+    /// let token = tokens
+    ///     .call(|| async { unreachable!("the token is already cached") })
+    ///     .await;
+    /// assert_eq!(token, "token-1");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn into_bucket_with<T>(self, value: T) -> Bucket<T> {
+        let mut bucket = Bucket::new(self);
+        bucket.put(value);
+        bucket
+    }
+
+    /// A bucket that starts out holding the value of `task`, so that the
+    /// first call is already a hit.
+    ///
+    /// To prime a [`LoadingBucket`] with its own loader, use
+    /// [`LoadingBucket::primed`].
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut config = Policy::expire_within_secs(60)
+    ///     .into_primed_bucket(|| async { "loaded at startup" })
+    ///     .await;
+    /// assert_eq!(config.call(|| async { "loaded later" }).await, "loaded at startup");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub async fn into_primed_bucket<T, F, Fut>(self, task: F) -> Bucket<T>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        self.into_bucket_with(task.call().await)
+    }
+
+    /// Like [`Policy::into_primed_bucket`], for tasks that can fail, so
+    /// that startup can give up when priming does.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let r = Policy::expire_within_secs(60)
+    ///     .try_into_primed_bucket(|| async { Err::<u32, _>("unavailable") })
+    ///     .await;
+    /// assert_eq!(r.unwrap_err(), "unavailable");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub async fn try_into_primed_bucket<T, F, Fut, E>(self, task: F) -> Result<Bucket<T>, E>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        Ok(self.into_bucket_with(task.call().await?))
+    }
+
+    /// A bucket that shares its value, see [`Bucket::call_arc`].
+    #[cfg(feature = "std")]
+    pub fn into_arc_bucket<T>(self) -> Bucket<Arc<T>> {
+        Bucket::new(self)
+    }
+
+    /// See [`Bucket::new_with_clock`].
+    pub fn into_bucket_with_clock<T>(self, clock: impl Clock + 'static) -> Bucket<T> {
+        Bucket::new_with_clock(self, clock)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_shared_bucket<T>(self) -> SharedBucket<T> {
+        Bucket::new(self).into_shared()
+    }
+
+    /// A bucket that owns its task, see [`LoadingBucket`].
+    #[cfg(feature = "std")]
+    pub fn into_loading_bucket<T, F, Fut>(self, load: F) -> LoadingBucket<T>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        Bucket::new(self).with_loader(load)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_bucket_map<K, T>(self) -> BucketMap<K, T>
+    where
+        K: Eq + std::hash::Hash,
+    {
+        BucketMap::new(self)
+    }
+}
+
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, serde::Serialize, serde::Deserialize)
+)]
+pub struct Bucket<T, P = Policy> {
+    #[cfg(feature = "serde")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    version: Version,
+    cache: Option<T>,
+    policy: P,
+    hit_count: u64,
+    #[cfg_attr(
+        all(feature = "serde", not(feature = "chrono-clock")),
+        serde(with = "crate::serde_std::instant")
+    )]
+    #[cfg_attr(feature = "serde-ts-millis", serde(with = "crate::timestamp::millis"))]
+    initiate: Instant,
+    error_policy: Option<Policy>,
+    failure: Option<Failure>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    refresh_ahead: Option<Policy>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    jitter: Option<Jitter>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    grace_millis: Option<u64>,
+    /// Milliseconds after `initiate` at which the value expires regardless
+    /// of the policy, see [`Bucket::call_with_deadline`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    deadline_millis: Option<u64>,
+    /// Milliseconds after `initiate` at which the pour accounting last
+    /// restarted without a refresh, see [`Bucket::rebase`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    rebased_millis: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    count_refresh: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    breaker: Option<Breaker>,
+    #[cfg(feature = "chrono-clock")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    boundary: Option<Boundary>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    stats: BucketStats,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    clock: Option<Arc<dyn Clock>>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "Option::default"))]
+    evict: Option<Evict<T>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observers: Observers,
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(feature = "serde", serde(skip, default = "Publisher::default"))]
+    publisher: Publisher<T>,
+}
+
+/// Statistics are not part of the cache state, so they are ignored.
+impl<T, P> PartialEq for Bucket<T, P>
+where
+    T: PartialEq,
+    P: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        #[cfg(feature = "chrono-clock")]
+        if self.boundary != other.boundary {
+            return false;
+        }
+        self.cache == other.cache
+            && self.policy == other.policy
+            && self.hit_count == other.hit_count
+            && self.initiate == other.initiate
+            && self.error_policy == other.error_policy
+            && self.failure == other.failure
+            && self.refresh_ahead == other.refresh_ahead
+            && self.jitter == other.jitter
+            && self.grace_millis == other.grace_millis
+            && self.deadline_millis == other.deadline_millis
+            && self.rebased_millis == other.rebased_millis
+            && self.count_refresh == other.count_refresh
+            && self.breaker == other.breaker
+    }
+}
+
+impl<T, P> Eq for Bucket<T, P>
+where
+    T: Eq,
+    P: Eq,
+{
+}
+
+/// Counters describing how a [`Bucket`] has been used.
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, Default, PartialEq, Eq))]
+#[cfg_attr(
+    feature = "serde",
+    derive(
+        Debug,
+        Clone,
+        Default,
+        PartialEq,
+        Eq,
+        serde::Serialize,
+        serde::Deserialize
+    )
+)]
+pub struct BucketStats {
+    /// Calls served from the cache.
+    pub hits: u64,
+    /// Calls that ran the task.
+    pub misses: u64,
+    /// Task results stored in the cache.
+    pub refreshes: u64,
+    /// Task runs that returned an error.
+    pub failures: u64,
+}
+
+/// The pour accounting of a [`Bucket`] without its value, see
+/// [`Bucket::state`].
+///
+/// With `serde`, it can be serialized whatever the type of the value.
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, PartialEq, Eq))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)
+)]
+pub struct BucketState<P = Policy> {
+    pub policy: P,
+    pub hit_count: u64,
+    /// When the evaporation clock last restarted.
+    #[cfg_attr(
+        all(feature = "serde", not(feature = "chrono-clock")),
+        serde(with = "crate::serde_std::instant")
+    )]
+    #[cfg_attr(feature = "serde-ts-millis", serde(with = "crate::timestamp::millis"))]
+    pub initiate: Instant,
+}
+
+#[cfg_attr(not(feature = "serde"), derive(Debug, Clone, PartialEq, Eq))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)
+)]
+struct Failure {
+    hit_count: u64,
+    #[cfg_attr(
+        all(feature = "serde", not(feature = "chrono-clock")),
+        serde(with = "crate::serde_std::instant")
+    )]
+    #[cfg_attr(feature = "serde-ts-millis", serde(with = "crate::timestamp::millis"))]
+    initiate: Instant,
+}
+
+/// The work run to fill a bucket, at most once per call.
+///
+/// Any `FnOnce` closure is a task, so it may move values into the future it
+/// returns. A stateful `FnMut` task can be lent with `&mut`, keeping its
+/// state across refreshes.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// # #[cfg(feature = "std")]
+/// # {
+/// let mut b = Policy::expire_within_counts(1).into_bucket();
+/// let mut cursor = 0;
+/// let mut next_page = || {
+///     cursor += 1;
+///     cursor
+/// };
+/// assert_eq!(b.call_sync(&mut next_page), 1);
+/// assert_eq!(b.call_sync(&mut next_page), 1);
+/// assert_eq!(b.call_sync(&mut next_page), 2);
+/// # }
+/// ```
+pub trait Task<T> {
+    fn call(self) -> T;
+}
+
+impl<F, T> Task<T> for F
+where
+    F: FnOnce() -> T,
+{
+    fn call(self) -> T {
+        (self)()
+    }
+}
+
+/// Decides whether a cached value is still fresh.
+///
+/// [`Policy`] is the built-in implementation; implement this trait for
+/// expiry rules that do not fit the pour/evaporation model.
+///
+/// # Example
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+/// use uchimizu::{Bucket, Duration, Expiry};
+///
+/// # #[cfg(feature = "std")]
+/// # {
+/// /// Expires as soon as the configuration generation moves on.
+/// struct Generation {
+///     current: Arc<AtomicU64>,
+///     seen: u64,
+/// }
+///
+/// impl Expiry for Generation {
+///     fn is_remaining(&self, _hit_count: u64, _elapsed: Duration) -> bool {
+///         self.current.load(Ordering::SeqCst) == self.seen
+///     }
+/// }
+///
+/// let current = Arc::new(AtomicU64::new(0));
+/// let mut b = Bucket::new(Generation {
+///     current: current.clone(),
+///     seen: 0,
+/// });
+/// assert_eq!(b.call_sync(|| 1), 1);
+/// assert_eq!(b.call_sync(|| 2), 1);
+/// current.store(1, Ordering::SeqCst);
+/// assert_eq!(b.call_sync(|| 3), 3);
+/// # }
+/// ```
+pub trait Expiry {
+    fn is_remaining(&self, hit_count: u64, elapsed: Duration) -> bool;
+
+    /// Whether the value has not yet reached its soft threshold, see
+    /// [`Freshness::Stale`]. Without one, a value is never stale.
+    fn is_soft_remaining(&self, hit_count: u64, elapsed: Duration) -> bool {
+        let _ = (hit_count, elapsed);
+        true
+    }
+
+    /// Whether time has given back everything poured, as with
+    /// [`Policy::replenishing`]. The bucket then restarts its accounting,
+    /// so that more time cannot build up more credit than the policy
+    /// holds. Never, by default.
+    fn is_refilled(&self, hit_count: u64, elapsed: Duration) -> bool {
+        let _ = (hit_count, elapsed);
+        false
+    }
+}
+
+impl Expiry for Policy {
+    fn is_remaining(&self, hit_count: u64, elapsed: Duration) -> bool {
+        self.is_remaining_at(hit_count, elapsed)
+    }
+
+    fn is_soft_remaining(&self, hit_count: u64, elapsed: Duration) -> bool {
+        self.is_soft_remaining_exact(hit_count, u128::from(duration_millis(elapsed)))
+    }
+
+    fn is_refilled(&self, hit_count: u64, elapsed: Duration) -> bool {
+        self.is_refilled_exact(hit_count, u128::from(duration_millis(elapsed)))
+    }
+}
+
+impl<T, P> AsRef<P> for Bucket<T, P> {
+    fn as_ref(&self) -> &P {
+        &self.policy
+    }
+}
+
+impl<T, P> Bucket<T, P>
+where
+    P: Expiry,
+{
+    /// Creates an empty bucket that reads the system clock.
+    ///
+    /// Without the `std` feature there is no system clock, so buckets are
+    /// made with [`Bucket::new_with_clock`] instead.
+    #[cfg(feature = "std")]
+    pub fn new(expiry: P) -> Self {
+        Bucket::starting_at(expiry, now())
+    }
+
+    /// Creates an empty bucket that reads time from `clock`.
+    pub fn new_with_clock(expiry: P, clock: impl Clock + 'static) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(clock);
+        Bucket {
+            clock: Some(clock.clone()),
+            ..Bucket::starting_at(expiry, clock.now())
+        }
+    }
+
+    fn starting_at(expiry: P, initiate: Instant) -> Self {
+        Bucket {
+            #[cfg(feature = "serde")]
+            version: Version,
+            cache: None,
+            policy: expiry,
+            hit_count: 0,
+            initiate,
+            error_policy: None,
+            failure: None,
+            refresh_ahead: None,
+            jitter: None,
+            grace_millis: None,
+            deadline_millis: None,
+            rebased_millis: 0,
+            count_refresh: false,
+            breaker: None,
+            #[cfg(feature = "chrono-clock")]
+            boundary: None,
+            stats: BucketStats::default(),
+            clock: None,
+            evict: None,
+            observers: Observers::default(),
+            #[cfg(feature = "tokio")]
+            publisher: Publisher::default(),
+        }
+    }
+
+    /// Rebuilds a bucket from state saved with [`Bucket::into_parts`].
+    ///
+    /// Nothing is validated: a `hit_count` beyond what the policy allows, or
+    /// an `initiate` long past, just makes the next call refresh. An
+    /// `initiate` in the future counts as no time elapsed.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Bucket, Policy};
+    ///
+    /// let mut b = Policy::expire_within_counts(3).into_bucket();
+    /// b.call_sync(|| 1);
+    /// let (policy, cache, hit_count, initiate) = b.into_parts();
+    /// let mut b = Bucket::from_parts(policy, cache, hit_count, initiate);
+    /// assert_eq!(b.call_sync(|| 2), 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_parts(expiry: P, cache: Option<T>, hit_count: u64, initiate: Instant) -> Self {
+        Bucket {
+            cache,
+            hit_count,
+            initiate,
+            ..Bucket::new(expiry)
+        }
+    }
+
+    /// Splits the bucket into its policy, cached value, hit count and the
+    /// instant its pour accounting started.
+    ///
+    /// That instant is the last refresh, unless a replenishing policy has
+    /// refilled since. Everything else, such as statistics, the error policy, a negative
+    /// cached failure and callbacks, is dropped.
+    pub fn into_parts(self) -> (P, Option<T>, u64, Instant) {
+        let initiate = self.accounted_since();
+        (self.policy, self.cache, self.hit_count, initiate)
+    }
+
+    /// Returns the policy and pour accounting, for persisting how much of
+    /// the budget is consumed when the value itself cannot be.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2).into_bucket();
+    /// b.call_sync(|| 1);
+    /// b.call_sync(|| 1);
+    /// let state = b.state();
+    /// assert_eq!(state.hit_count, 1);
+    ///
+    /// let mut restarted = Policy::expire_within_counts(2).into_bucket();
+    /// restarted.restore_state(state);
+    /// assert_eq!(restarted.call_sync(|| 2), 2);
+    /// assert_eq!(restarted.call_sync(|| 3), 2);
+    /// assert_eq!(restarted.call_sync(|| 4), 4);
+    /// # }
+    /// ```
+    pub fn state(&self) -> BucketState<P>
+    where
+        P: Clone,
+    {
+        BucketState {
+            policy: self.policy.clone(),
+            hit_count: self.hit_count,
+            initiate: self.accounted_since(),
+        }
+    }
+
+    /// Applies state saved with [`Bucket::state`], dropping the cached
+    /// value so that the next call runs the task.
+    ///
+    /// As after [`Bucket::invalidate`], the restored `hit_count` and age
+    /// are kept: an exhausted state makes the next call start over, while a
+    /// remaining one is served for what is left of it once filled.
+    pub fn restore_state(&mut self, state: BucketState<P>) {
+        self.clear();
+        self.policy = state.policy;
+        self.hit_count = state.hit_count;
+        self.initiate = state.initiate;
+        self.rebased_millis = 0;
+        self.deadline_millis = None;
+        self.failure = None;
+    }
+
+    /// Drops the cached value but keeps the pour accounting.
+    ///
+    /// Unlike [`Bucket::refresh`], `hit_count` and the evaporation clock are
+    /// left untouched, so the next call runs the task without replenishing
+    /// the policy.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// b.call_sync(|| 1);
+    /// b.invalidate();
+    /// assert_eq!(b.call_sync(|| 2), 2);
+    /// assert_eq!(b.call_sync(|| 3), 2);
+    /// assert_eq!(b.call_sync(|| 4), 4);
+    /// # }
+    /// ```
+    pub fn invalidate(&mut self) {
+        self.clear();
+        self.observe(|o| o.on_invalidate());
+    }
+
+    /// Like [`Bucket::invalidate`], but hands the cached value to the
+    /// caller instead of dropping it.
+    ///
+    /// Since the value is not dropped, the callback set with
+    /// [`Bucket::on_evict`] is not run for it.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// b.call_sync(|| vec![1, 2]);
+    /// assert_eq!(b.take(), Some(vec![1, 2]));
+    /// assert_eq!(b.take(), None);
+    /// // The pour accounting is kept, as with `invalidate`.
+    /// assert_eq!(b.call_sync(|| vec![3]), vec![3]);
+    /// assert_eq!(b.call_sync(|| vec![4]), vec![3]);
+    /// assert_eq!(b.call_sync(|| vec![5]), vec![5]);
+    /// # }
+    /// ```
+    pub fn take(&mut self) -> Option<T> {
+        let value = self.cache.take();
+        #[cfg(feature = "tokio")]
+        self.publisher.publish(None);
+        self.observe(|o| o.on_invalidate());
+        value
+    }
+
+    /// Consumes the bucket, returning the cached value regardless of
+    /// freshness.
+    ///
+    /// Like dropping the bucket, this does not run the callback set with
+    /// [`Bucket::on_evict`].
+    pub fn into_value(self) -> Option<T> {
+        self.cache
+    }
+
+    pub fn refresh(&mut self) {
+        self.reset();
+        self.observe(|o| o.on_invalidate());
+    }
+
+    /// [`Bucket::invalidate`] without telling observers.
+    pub(crate) fn clear(&mut self) {
+        self.evict();
+        #[cfg(feature = "tokio")]
+        self.publisher.publish(None);
+    }
+
+    /// [`Bucket::refresh`] without telling observers.
+    pub(crate) fn reset(&mut self) {
+        self.restart();
+        #[cfg(feature = "tokio")]
+        self.publisher.publish(None);
+    }
+
+    /// Stores `value` as if a task had just refreshed the bucket with it,
+    /// and returns the value it replaces.
+    ///
+    /// `hit_count` and the evaporation clock restart, as after a refresh.
+    /// The old value is handed back rather than dropped, so the callback
+    /// set with [`Bucket::on_evict`] is not run for it.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut tokens = Policy::expire_within_counts(10).into_bucket();
+    /// assert_eq!(tokens.replace("token-1"), None);
+    /// assert_eq!(tokens.replace("token-2"), Some("token-1"));
+    /// assert_eq!(tokens.hit_count(), 0);
+    /// # }
+    /// ```
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        let old = self.cache.take();
+        self.put(value);
+        old
+    }
+
+    /// Stores `entry` as if a task had just refreshed the bucket with it,
+    /// restarting the pour accounting.
+    pub(crate) fn put(&mut self, entry: T) {
+        self.restart();
+        self.store(entry, 0);
+    }
+
+    /// Restarts the pour accounting and drops the cached value, without
+    /// telling subscribers, since a new value follows.
+    fn restart(&mut self) {
+        self.hit_count = 0;
+        self.evict();
+        self.initiate = self.now();
+        self.rebased_millis = 0;
+        self.failure = None;
+        if let Some(jitter) = self.jitter.as_mut() {
+            jitter.reroll();
+        }
+    }
+
+    /// Replaces the policy, keeping the cached value and pour accounting.
+    ///
+    /// The current `hit_count` and age are judged against the new policy
+    /// from the next call on: tightening it can expire the cached value at
+    /// once, and loosening it can make a stale value fresh again. A
+    /// threshold set with [`Bucket::refresh_ahead`] still refers to the old
+    /// policy.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// b.call_sync(|| 1);
+    /// b.set_policy(Policy::expire_within_counts(3));
+    /// assert_eq!(b.call_sync(|| 2), 1);
+    /// # }
+    /// ```
+    pub fn set_policy(&mut self, expiry: P) {
+        self.policy = expiry;
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_shared(self) -> SharedBucket<T, P> {
+        SharedBucket::from(self)
+    }
+
+    /// Hands the bucket the task it refreshes with, see [`LoadingBucket`].
+    #[cfg(feature = "std")]
+    pub fn with_loader<F, Fut>(self, load: F) -> LoadingBucket<T, P>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        LoadingBucket::new(self, load)
+    }
+
+    /// Shares the bucket and returns a view of it that applies `f` to its
+    /// value.
+    ///
+    /// Further views of the same bucket can be made from
+    /// [`MappedBucket::source`]. See [`MappedBucket`].
+    #[cfg(feature = "std")]
+    pub fn map<U>(self, f: impl Fn(&T) -> U + Send + Sync + 'static) -> MappedBucket<T, U, P> {
+        self.into_shared().map(f)
+    }
+
+    /// Runs `f` with every cached value the bucket drops.
+    ///
+    /// That is when it is replaced by a refresh, dropped by
+    /// [`Bucket::refresh`] or [`Bucket::invalidate`], or evicted from a
+    /// [`BucketMap`]. `f` receives the old value before the new one is
+    /// stored, and is not run if nothing was cached. Dropping the bucket
+    /// itself does not run it. Clones of the bucket share `f`.
+    ///
+    /// `f` must not call back into the bucket.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use uchimizu::Policy;
+    ///
+    /// let dropped = Arc::new(Mutex::new(Vec::new()));
+    /// let mut b = Policy::expire_within_counts(1).into_bucket().on_evict({
+    ///     let dropped = dropped.clone();
+    ///     move |v| dropped.lock().unwrap().push(v)
+    /// });
+    /// b.call_sync(|| 1);
+    /// b.call_sync(|| 2);
+    /// b.call_sync(|| 3);
+    /// b.refresh();
+    /// assert_eq!(*dropped.lock().unwrap(), vec![1, 3]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn on_evict(mut self, f: impl FnMut(T) + Send + 'static) -> Self {
+        self.evict = Some(Evict::new(f));
+        self
+    }
+
+    /// Reports hits, misses and refreshes to `observer`.
+    pub fn with_observer(mut self, observer: Arc<dyn BucketObserver>) -> Self {
+        self.observers.observer = Some(observer);
+        self
+    }
+
+    /// Exports hits, misses and refresh latency to the `metrics` facade,
+    /// labelled `bucket = name`.
+    ///
+    /// The counters are `uchimizu_cache_hits_total` and
+    /// `uchimizu_cache_misses_total`, and the histogram is
+    /// `uchimizu_refresh_duration_seconds`. Unnamed buckets emit nothing.
+    /// Like the clock, the name is not serialized.
+    #[cfg(feature = "metrics")]
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.observers.name = Some(name.into().into());
+        self
+    }
+
+    /// Returns a stream of what the bucket does from now on.
+    ///
+    /// Events are best-effort: a subscriber that falls behind loses the
+    /// oldest ones rather than slowing calls down. The stream is shared
+    /// with clones of the bucket and with a [`SharedBucket`] made from it,
+    /// and ends when all of them are dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio_stream::StreamExt;
+    /// use uchimizu::{CacheEvent, Policy};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// let mut events = b.events();
+    /// b.call(|| async { 1 }).await;
+    /// b.call(|| async { 2 }).await;
+    /// assert_eq!(events.next().await, Some(CacheEvent::Miss));
+    /// assert_eq!(events.next().await, Some(CacheEvent::RefreshStarted));
+    /// assert!(matches!(
+    ///     events.next().await,
+    ///     Some(CacheEvent::RefreshSucceeded { .. })
+    /// ));
+    /// assert_eq!(events.next().await, Some(CacheEvent::Hit));
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn events(&self) -> impl tokio_stream::Stream<Item = CacheEvent> + Send + Unpin + 'static {
+        self.observers.events.subscribe()
+    }
+
+    /// Like [`Bucket::call`], but lends the value to `read` instead of
+    /// cloning it, so `T` need not be `Clone`.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2).into_bucket();
+    /// let len = b.call_ref(|| async { vec![0u8; 1 << 20] }, Vec::len).await;
+    /// assert_eq!(len, 1 << 20);
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_ref<F, Fut, R>(&mut self, task: F, read: impl FnOnce(&T) -> R) -> R
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        self.call_weighted_ref(1, task, read).await
+    }
+
+    async fn call_weighted_ref<F, Fut, R>(
+        &mut self,
+        weight: u64,
+        task: F,
+        read: impl FnOnce(&T) -> R,
+    ) -> R
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        if self.is_servable() {
+            if let Some(entry) = self.serve(weight) {
+                let r = read(entry);
+                self.observe(|o| o.on_hit());
+                return r;
+            }
+        }
+        #[cfg(feature = "tracing")]
+        let span = self.refresh_span();
+        let exhausted = self.miss();
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        let fut = task.call();
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, span);
+        // Nothing is reset before the task completes, so a cancelled call
+        // leaves the stale value and its accounting in place.
+        let entry = fut.await;
+        if exhausted {
+            self.restart();
+        }
+        let r = read(self.store(entry, weight));
+        self.observe_refresh(started);
+        r
+    }
+
+    /// Counts a call that is about to run the task, and returns whether the
+    /// policy was exhausted at that point.
+    fn miss(&mut self) -> bool {
+        self.stats.misses += 1;
+        !self.is_remaining_now()
+    }
+
+    /// Counts a call of `weight` pours served from the cache, even if the
+    /// policy is exhausted.
+    fn serve(&mut self, weight: u64) -> Option<&T> {
+        self.cache.as_ref()?;
+        self.hit_count = self.hit_count.saturating_add(weight);
+        self.stats.hits += 1;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: "uchimizu", hit_count = self.hit_count, "cache hit");
+        self.cache.as_ref()
+    }
+
+    /// Stores a freshly computed value and counts the call of `weight`
+    /// pours that produced it.
+    fn store(&mut self, entry: T, weight: u64) -> &T {
+        self.evict();
+        self.deadline_millis = None;
+        if self.count_refresh {
+            self.hit_count = self.hit_count.saturating_add(weight);
+        }
+        self.stats.refreshes += 1;
+        #[cfg(feature = "tokio")]
+        self.publisher.publish(Some(&entry));
+        self.cache.insert(entry)
+    }
+
+    fn observe(&self, f: impl Fn(&dyn BucketObserver)) {
+        self.observers.each(f);
+    }
+
+    /// Returns when a task starts, if anyone is observing how long it takes.
+    fn start(&self) -> Option<Instant> {
+        if self.observers.is_empty() {
+            return None;
+        }
+        Some(self.now())
+    }
+
+    fn observe_refresh(&self, started: Option<Instant>) {
+        if let Some(started) = started {
+            let elapsed = elapsed_since(started, self.now());
+            self.observe(|o| o.on_refresh_complete(elapsed));
+        }
+    }
+
+    /// Returns the span a refresh runs in, recording the accounting from
+    /// before it is reset.
+    #[cfg(feature = "tracing")]
+    fn refresh_span(&self) -> tracing::Span {
+        tracing::debug_span!(
+            target: "uchimizu",
+            "refresh",
+            hit_count = self.hit_count,
+            elapsed_secs = duration_millis(self.age()) as f64 / 1000.0,
+            hit = false,
+        )
+    }
+
+    /// Drops the cached value, handing it to the eviction callback.
+    fn evict(&mut self) {
+        if let (Some(value), Some(evict)) = (self.cache.take(), &self.evict) {
+            evict.call(value);
+        }
+    }
+
+    /// Reads time from `clock` instead of the system clock.
+    ///
+    /// The evaporation clock restarts at `clock.now()`.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.initiate = clock.now();
+        self.rebased_millis = 0;
+        self.failure = None;
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Stretches or shrinks each refresh cycle by a random factor within
+    /// `±fraction`, so that copies of the same bucket do not all expire at
+    /// once.
+    ///
+    /// Only the evaporation clock is affected. The factor is drawn per
+    /// bucket when it is refreshed and stays fixed until the next refresh.
+    /// Deserialized buckets draw their own factors. `fraction` is clamped to
+    /// `0.0..=1.0`.
+    ///
+    /// # Panics
+    /// Panics if `fraction` is NaN.
+    pub fn with_jitter(mut self, fraction: f32) -> Self {
+        assert!(!fraction.is_nan(), "jitter fraction is NaN");
+        self.jitter = Some(Jitter::new(fraction.clamp(0.0, 1.0)));
+        self
+    }
+
+    /// Serves the stale value when [`Bucket::call_try`] fails, for up to
+    /// `grace` after the policy would have expired by evaporation.
+    ///
+    /// The window is measured from the last refresh, so repeated failures do
+    /// not extend it, and ends early at a hard expiry such as the deadline
+    /// of [`Bucket::call_with_deadline`]. This also applies while a failure
+    /// is negative cached.
+    /// Without a cached value, or past the window, the error is returned as
+    /// usual.
+    ///
+    /// # Panics
+    /// Panics if `grace` is negative or too large to be represented.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// let grace = std::time::Duration::from_secs(60);
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # let grace = chrono::TimeDelta::seconds(60);
+    /// let mut b = Policy::expire_within_millis(10).into_bucket().with_grace(grace);
+    /// b.call_sync(|| 1);
+    /// # tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    /// let r = b.call_try(|| async { Err::<u32, _>("unavailable") }).await;
+    /// assert_eq!(r, Ok(1));
+    /// # }
+    /// # }
+    /// ```
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace_millis = Some(duration_millis_ceil(grace).expect("duration out of range"));
+        self
+    }
+
+    /// Expires every value at the first `boundary` after it was stored, on
+    /// top of the policy.
+    ///
+    /// The boundary is taken from the wall-clock time of the last refresh,
+    /// so a daily value stored at 23:59 UTC expires a minute later, and one
+    /// stored at 00:01 lasts nearly a day.
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::{TimeDelta, TimeZone, Utc};
+    /// use uchimizu::{Boundary, ManualClock, Policy};
+    ///
+    /// let clock = ManualClock::starting_at(Utc.with_ymd_and_hms(2024, 1, 1, 23, 59, 0).unwrap());
+    /// let mut b = Policy::bottom_less()
+    ///     .into_bucket_with_clock(clock.clone())
+    ///     .expire_at_boundary(Boundary::Day);
+    /// assert_eq!(b.call_sync(|| "monday"), "monday");
+    /// clock.advance(TimeDelta::minutes(1));
+    /// assert_eq!(b.call_sync(|| "tuesday"), "tuesday");
+    /// clock.advance(TimeDelta::hours(23));
+    /// assert_eq!(b.call_sync(|| "still tuesday?"), "tuesday");
+    /// ```
+    #[cfg(feature = "chrono-clock")]
+    pub fn expire_at_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = Some(boundary);
+        self
+    }
+
+    /// Returns the milliseconds after the last refresh at which the value
+    /// expires regardless of the policy.
+    fn hard_expiry_millis(&self) -> Option<u64> {
+        #[cfg(feature = "chrono-clock")]
+        let boundary = self
+            .boundary
+            .map(|b| duration_millis(b.next_after(self.initiate) - self.initiate));
+        #[cfg(not(feature = "chrono-clock"))]
+        let boundary = None;
+        match (self.deadline_millis, boundary) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Sets whether the call that refreshes the bucket also counts as a
+    /// pour.
+    ///
+    /// By default it does not, so `Policy::expire_within_counts(n)` serves
+    /// the cached value `n` times after each refresh. Counting it leaves
+    /// `n - 1`, which makes `expire_within_counts(1)` run the task on every
+    /// call.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2)
+    ///     .into_bucket()
+    ///     .count_refresh_as_pour(true);
+    /// assert_eq!(b.call_sync(|| 1), 1);
+    /// assert_eq!(b.call_sync(|| 2), 1);
+    /// assert_eq!(b.call_sync(|| 3), 3);
+    /// # }
+    /// ```
+    pub fn count_refresh_as_pour(mut self, yes: bool) -> Self {
+        self.count_refresh = yes;
+        self
+    }
+
+    /// Remembers failures of [`Bucket::call_try`] according to `policy`.
+    ///
+    /// While the error policy is remaining, expired calls return
+    /// [`Error::NegativeCached`] instead of running the task again.
+    /// A successful refresh clears the failure immediately.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Error, Policy};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::pierced()
+    ///     .into_bucket()
+    ///     .with_error_policy(Policy::expire_within_counts(1));
+    /// let r = b.call_try(|| async { Err::<u32, _>("unavailable") }).await;
+    /// assert_eq!(r, Err(Error::Task("unavailable")));
+    /// let r = b.call_try(|| async { Ok::<_, &str>(1) }).await;
+    /// assert_eq!(r, Err(Error::NegativeCached));
+    /// let r = b.call_try(|| async { Ok::<_, &str>(1) }).await;
+    /// assert_eq!(r, Ok(1));
+    /// # }
+    /// # }
+    /// ```
+    pub fn with_error_policy(mut self, policy: Policy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
+
+    /// Stops running a task that keeps failing.
+    ///
+    /// After `config.failures` consecutive failures of a task run by
+    /// [`Bucket::call_try`] or its variants, the breaker opens: for
+    /// `config.cooldown`, expired calls serve the stale cached value, or
+    /// return [`Error::NegativeCached`] if nothing is cached, without
+    /// running the task. Once the cooldown is over the next call runs the
+    /// task as a probe; a success closes the breaker and a failure opens
+    /// it again. The breaker is checked before the error policy.
+    ///
+    /// # Panics
+    /// Panics if `config.cooldown` is negative or too large to be
+    /// represented.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "std")]
+    /// use uchimizu::{BreakerConfig, BreakerState, ManualClock, Policy};
+    ///
+    /// # #[cfg(not(feature = "std"))]
+    /// # fn main() {}
+    /// # #[cfg(feature = "std")]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// let cooldown = std::time::Duration::from_secs(30);
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # let cooldown = chrono::TimeDelta::seconds(30);
+    /// let clock = ManualClock::new();
+    /// let mut b = Policy::pierced()
+    ///     .into_bucket_with_clock(clock.clone())
+    ///     .with_breaker(BreakerConfig::new(2, cooldown));
+    /// b.call_sync(|| 1);
+    /// let down = || async { Err::<u32, _>("unavailable") };
+    /// assert!(b.call_try(down).await.is_err());
+    /// assert!(b.call_try(down).await.is_err());
+    /// assert_eq!(b.breaker_state(), BreakerState::Open);
+    /// // The task is not run while the breaker is open.
+    /// assert_eq!(b.call_try(|| async { Ok::<_, &str>(2) }).await, Ok(1));
+    ///
+    /// clock.advance(cooldown);
+    /// assert_eq!(b.breaker_state(), BreakerState::HalfOpen);
+    /// assert_eq!(b.call_try(|| async { Ok::<_, &str>(2) }).await, Ok(2));
+    /// assert_eq!(b.breaker_state(), BreakerState::Closed);
+    /// # }
+    /// ```
+    pub fn with_breaker(mut self, config: BreakerConfig) -> Self {
+        self.breaker = Some(Breaker::new(config));
+        self
+    }
+
+    /// Returns the state of the breaker set with [`Bucket::with_breaker`],
+    /// which is always closed without one.
+    pub fn breaker_state(&self) -> BreakerState {
+        match &self.breaker {
+            Some(breaker) => breaker.state(self.now()),
+            None => BreakerState::Closed,
+        }
+    }
+
+    /// Returns the cached value regardless of freshness.
+    ///
+    /// Unlike [`Bucket::call`], this does not count as a pour.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::pierced().into_bucket();
+    /// assert_eq!(b.cached(), None);
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.cached(), Some(&1));
+    /// # }
+    /// ```
+    pub fn cached(&self) -> Option<&T> {
+        self.cache.as_ref()
+    }
+
+    /// Returns the cached value only if the policy is still remaining.
+    ///
+    /// Unlike [`Bucket::call`], this does not count as a pour.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::pierced().into_bucket();
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.fresh(), None);
+    ///
+    /// let mut b = Policy::bottom_less().into_bucket();
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.fresh(), Some(&1));
+    /// # }
+    /// ```
+    pub fn fresh(&self) -> Option<&T> {
+        if !self.is_remaining_now() {
+            return None;
+        }
+        self.cache.as_ref()
+    }
+
+    /// Returns whether the next call would be served from the cache.
+    ///
+    /// Like [`Bucket::fresh`], this does not count as a pour.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// assert!(b.is_expired());
+    /// b.call_sync(|| 1);
+    /// assert!(b.is_fresh());
+    /// b.call_sync(|| 1);
+    /// assert!(!b.is_fresh());
+    /// # }
+    /// ```
+    pub fn is_fresh(&self) -> bool {
+        self.fresh().is_some()
+    }
+
+    /// The negation of [`Bucket::is_fresh`]; an empty bucket is expired.
+    pub fn is_expired(&self) -> bool {
+        !self.is_fresh()
+    }
+
+    /// Classifies the cached value against the soft threshold set with
+    /// [`Policy::with_soft_threshold`] or [`Bucket::refresh_ahead`], and the
+    /// hard expiry that [`Bucket::is_fresh`] checks.
+    ///
+    /// Like [`Bucket::fresh`], this does not count as a pour.
+    pub fn freshness(&self) -> Freshness {
+        if self.is_expired() {
+            Freshness::Expired
+        } else if self.is_due_ahead() {
+            Freshness::Stale
+        } else {
+            Freshness::Fresh
+        }
+    }
+
+    /// Returns the cached value if it is fresh, or why not.
+    ///
+    /// Like [`Bucket::fresh`], this never runs a task and does not count as
+    /// a pour.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{CacheMiss, Policy};
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// assert_eq!(b.try_cached(), Err(CacheMiss::Empty));
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.try_cached(), Ok(&1));
+    /// b.call_sync(|| 1);
+    /// assert!(matches!(b.try_cached(), Err(CacheMiss::Expired { .. })));
+    /// # }
+    /// ```
+    pub fn try_cached(&self) -> Result<&T, CacheMiss> {
+        let entry = self.cache.as_ref().ok_or(CacheMiss::Empty)?;
+        if !self.is_remaining_now() {
+            return Err(CacheMiss::Expired { age: self.age() });
+        }
+        Ok(entry)
+    }
+
+    /// Returns the usage statistics of this bucket.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2).into_bucket();
+    /// for _ in 0..4 {
+    ///     b.call_sync(|| 1);
+    /// }
+    /// assert_eq!(b.stats().hits, 2);
+    /// assert_eq!(b.stats().misses, 2);
+    /// # }
+    /// ```
+    pub fn stats(&self) -> &BucketStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = BucketStats::default();
+    }
+
+    /// Returns how many calls have been served since the last refresh.
+    pub fn hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    /// Counts `n` pours without serving anything, saturating at `u64::MAX`.
+    ///
+    /// The pours add to [`Bucket::hit_count`] and are charged on the next
+    /// call like any other.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(3).into_bucket();
+    /// b.call_sync(|| 1);
+    /// b.pour(3);
+    /// assert_eq!(b.call_sync(|| 2), 2);
+    /// # }
+    /// ```
+    pub fn pour(&mut self, n: u64) {
+        self.hit_count = self.hit_count.saturating_add(n);
+    }
+
+    /// Ages the bucket by `d`, as if that much more time had passed since
+    /// the last refresh.
+    ///
+    /// The evaporation clock is moved back, stopping at the earliest
+    /// instant the clock can represent. A negative `d` does nothing.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_secs(60).into_bucket();
+    /// b.call_sync(|| 1);
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// b.evaporate(std::time::Duration::from_secs(60));
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # b.evaporate(chrono::TimeDelta::seconds(60));
+    /// assert_eq!(b.call_sync(|| 2), 2);
+    /// # }
+    /// ```
+    pub fn evaporate(&mut self, d: Duration) {
+        self.initiate = saturating_instant_sub(self.initiate, d);
+    }
+
+    /// Returns the time elapsed since the last refresh.
+    ///
+    /// If the clock has moved backwards past that point, this is zero.
+    pub fn age(&self) -> Duration {
+        elapsed_since(self.initiate, self.now())
+    }
+
+    /// Returns when the bucket was last refreshed.
+    pub fn last_refreshed_at(&self) -> Instant {
+        self.initiate
+    }
+
+    /// Pulls a last refresh time that lies in the future back to now.
+    ///
+    /// A bucket restored on a machine whose clock is behind is already
+    /// treated as just refreshed, but its evaporation clock only starts once
+    /// the local clock catches up. Calling this after loading starts it
+    /// immediately.
+    pub fn clamp_initiate(&mut self) {
+        let now = self.now();
+        if self.initiate > now {
+            self.initiate = now;
+            self.rebased_millis = 0;
+        }
+    }
+
+    /// Returns the time elapsed since the pour accounting started.
+    fn accounted_age(&self) -> Duration {
+        saturating_sub(self.age(), millis_duration(self.rebased_millis))
+    }
+
+    /// Returns when the pour accounting started.
+    fn accounted_since(&self) -> Instant {
+        self.initiate + millis_duration(self.rebased_millis)
+    }
+
+    /// Returns the age the policy is checked against.
+    fn policy_age(&self) -> Duration {
+        match &self.jitter {
+            Some(jitter) => millis_duration(jitter.shrink(duration_millis(self.accounted_age()))),
+            None => self.accounted_age(),
+        }
+    }
+
+    fn elapsed_millis(&self) -> u64 {
+        duration_millis(self.policy_age())
+    }
+
+    fn now(&self) -> Instant {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            #[cfg(feature = "std")]
+            None => now(),
+            #[cfg(not(feature = "std"))]
+            None => unreachable!("buckets are made with a clock without std"),
+        }
+    }
+
+    /// Restarts the pour accounting from now if the policy is refilled,
+    /// keeping the cached value and when it was refreshed.
+    fn rebase(&mut self) {
+        if !self.policy.is_refilled(self.hit_count, self.policy_age()) {
+            return;
+        }
+        self.hit_count = 0;
+        self.rebased_millis = duration_millis(self.age());
+    }
+
+    /// Decides whether a call is served from the cache, capping first the
+    /// refill earned while idle. Every call path decides through this.
+    fn is_servable(&mut self) -> bool {
+        self.rebase();
+        self.is_remaining_now()
+    }
+
+    fn is_remaining_now(&self) -> bool {
+        self.policy.is_remaining(self.hit_count, self.policy_age())
+            && self
+                .hard_expiry_millis()
+                .is_none_or(|millis| duration_millis(self.age()) < millis)
+    }
+
+    /// Returns whether the refresh-ahead threshold, or else the soft
+    /// threshold of the policy, has been reached.
+    fn is_due_ahead(&self) -> bool {
+        match &self.refresh_ahead {
+            Some(p) => !p.is_remaining_millis(self.hit_count, self.elapsed_millis()),
+            None => !self
+                .policy
+                .is_soft_remaining(self.hit_count, self.policy_age()),
+        }
+    }
+
+    fn is_failure_remaining_now(&self) -> bool {
+        match (&self.error_policy, &self.failure) {
+            (Some(policy), Some(failure)) => policy.is_remaining_millis(
+                failure.hit_count,
+                duration_millis(elapsed_since(failure.initiate, self.now())),
+            ),
+            (_, _) => false,
+        }
+    }
+}
+
+impl<T> Bucket<T> {
+    /// Refreshes early once `ratio` of the policy has been consumed.
+    ///
+    /// Past that point [`SharedBucket::call_swr`] keeps serving the cached
+    /// value but starts a background refresh, so callers never see a miss
+    /// as long as the refresh finishes before the policy expires. Only one
+    /// such refresh runs at a time. `ratio` is clamped to `0.0..=1.0`.
+    ///
+    /// For composite policies the threshold applies to every child.
+    ///
+    /// # Panics
+    /// Panics if `ratio` is NaN.
+    pub fn refresh_ahead(mut self, ratio: f32) -> Self {
+        assert!(!ratio.is_nan(), "refresh-ahead ratio is NaN");
+        self.refresh_ahead = Some(self.policy.portion(ratio.clamp(0.0, 1.0)));
+        self
+    }
+
+    /// Returns how much budget is left, as of now.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(3).into_bucket();
+    /// b.call_sync(|| 1);
+    /// b.call_sync(|| 1);
+    /// assert_eq!(b.remaining(), 2);
+    /// assert_eq!(b.consumed(), 1);
+    /// # }
+    /// ```
+    pub fn remaining(&self) -> u64 {
+        self.policy
+            .remaining_after_millis(self.hit_count, self.elapsed_millis())
+    }
+
+    /// Returns how much budget has been consumed, as of now.
+    pub fn consumed(&self) -> u64 {
+        self.policy
+            .consumed_after(self.hit_count, u128::from(self.elapsed_millis()))
+    }
+
+    /// Returns how long until the policy expires by evaporation.
+    ///
+    /// Returns `None` if the policy never expires by time, and a zero
+    /// duration if it is already exhausted.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let b = Policy::expire_within_secs(10).into_bucket::<()>();
+    /// assert!(b.time_to_expiry().is_some());
+    ///
+    /// let b = Policy::expire_within_counts(10).into_bucket::<()>();
+    /// assert!(b.time_to_expiry().is_none());
+    ///
+    /// let b = Policy::pierced().into_bucket::<()>();
+    /// assert_eq!(b.time_to_expiry(), Some(Default::default()));
+    /// # }
+    /// ```
+    pub fn time_to_expiry(&self) -> Option<Duration> {
+        self.time_to_expiry_from(self.now())
+    }
+
+    /// Returns the wall-clock time at which the policy expires by
+    /// evaporation. See [`Bucket::time_to_expiry`].
+    #[cfg(feature = "chrono-clock")]
+    pub fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let now = self.now();
+        Some(now + self.time_to_expiry_from(now)?)
+    }
+
+    /// Takes a pour from the budget without touching the cached value, for
+    /// using the bucket as a rate limiter.
+    ///
+    /// Once the budget is drained nothing is counted, and the error tells
+    /// when to try again if the policy refills over time, as with
+    /// [`Policy::replenishing`].
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// use uchimizu::{ManualClock, Policy};
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut limiter = Policy::replenishing(2, 1, 1).into_bucket_with_clock::<()>(clock.clone());
+    /// assert!(limiter.try_acquire().is_ok());
+    /// assert!(limiter.try_acquire().is_ok());
+    /// let err = limiter.try_acquire().unwrap_err();
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// let second = std::time::Duration::from_secs(1);
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # let second = chrono::TimeDelta::seconds(1);
+    /// assert_eq!(err.retry_after, Some(second));
+    /// clock.advance(second);
+    /// assert!(limiter.try_acquire().is_ok());
+    /// # }
+    /// ```
+    pub fn try_acquire(&mut self) -> Result<(), Exhausted> {
+        if self.is_servable() {
+            self.pour(1);
+            return Ok(());
+        }
+        Err(Exhausted {
+            retry_after: self.retry_after(),
+        })
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        if self
+            .hard_expiry_millis()
+            .is_some_and(|millis| duration_millis(self.age()) >= millis)
+        {
+            return None;
+        }
+        let elapsed = self.accounted_age();
+        let mut millis = duration_millis(elapsed);
+        if let Some(jitter) = &self.jitter {
+            millis = jitter.shrink(millis);
+        }
+        let mut millis = self
+            .policy
+            .replenished_at_millis(self.hit_count, u128::from(millis))?;
+        if let Some(jitter) = &self.jitter {
+            millis = jitter.stretch(millis);
+        }
+        Some(saturating_sub(millis_duration(millis), elapsed))
+    }
+
+    fn time_to_expiry_from(&self, now: Instant) -> Option<Duration> {
+        let elapsed = elapsed_since(self.initiate, now);
+        let mut millis = duration_millis(elapsed).saturating_sub(self.rebased_millis);
+        if let Some(jitter) = &self.jitter {
+            millis = jitter.shrink(millis);
+        }
+        let by_policy = self
+            .policy
+            .exhausted_at_millis(self.hit_count, u128::from(millis))
+            .map(|millis| match &self.jitter {
+                Some(jitter) => jitter.stretch(millis),
+                None => millis,
+            })
+            .map(|millis| millis.saturating_add(self.rebased_millis));
+        let millis = match (by_policy, self.hard_expiry_millis()) {
+            (Some(a), Some(b)) => a.min(b),
+            (a, b) => a.or(b)?,
+        };
+        Some(saturating_sub(millis_duration(millis), elapsed))
+    }
+}
+
+impl<T, P> Bucket<T, P>
+where
+    T: Clone,
+    P: Expiry,
+{
+    /// Returns a receiver that sees every value the bucket stores, and
+    /// `None` whenever it is refreshed or invalidated.
+    ///
+    /// The receiver starts with the value cached now. Subscribing does not
+    /// count as a pour.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut b = Policy::pierced().into_bucket();
+    /// let mut rx = b.subscribe();
+    /// assert_eq!(*rx.borrow(), None);
+    /// b.call(|| async { "rotated" }).await;
+    /// rx.changed().await.unwrap();
+    /// assert_eq!(*rx.borrow_and_update(), Some("rotated"));
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<Option<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.publisher.subscribe(self.cache.as_ref())
+    }
+
+    /// Serves the cached value while the policy is remaining, and otherwise
+    /// runs the task and caches its output.
+    ///
+    /// The call is cancel-safe: if it is dropped before the task finishes,
+    /// the stale value and the pour accounting are left as they were. A
+    /// panicking task likewise leaves them untouched and the panic is
+    /// propagated to the caller.
+    pub async fn call<F, Fut>(&mut self, task: F) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        self.call_ref(task, T::clone).await
+    }
+
+    /// Like [`Bucket::call`], but the call counts as `weight` pours.
+    ///
+    /// Whether the cached value is served is decided as for a single pour;
+    /// if it is, `weight` is then added to [`Bucket::hit_count`], saturating.
+    /// A call that refreshes is only charged its weight with
+    /// [`Bucket::count_refresh_as_pour`], so by default
+    /// `expire_within_counts(100)` serves ten weight-10 calls after the one
+    /// that ran the task.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(100).into_bucket();
+    /// for _ in 0..11 {
+    ///     assert_eq!(b.call_weighted(10, || async { 1 }).await, 1);
+    /// }
+    /// assert_eq!(b.call_weighted(10, || async { 2 }).await, 2);
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_weighted<F, Fut>(&mut self, weight: u64, task: F) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        self.call_weighted_ref(weight, task, T::clone).await
+    }
+
+    /// Runs the task and stores its output regardless of the policy.
+    ///
+    /// The old value stays cached until the task finishes. Then the pour
+    /// accounting restarts as on [`Bucket::refresh`]; the forced refresh
+    /// itself does not count as a pour, so the new value has the whole
+    /// policy ahead of it. Like any task run, it counts as a miss.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2).into_bucket();
+    /// b.call(|| async { 1 }).await;
+    /// assert_eq!(b.force_refresh_with(|| async { 2 }).await, 2);
+    /// assert_eq!(b.hit_count(), 0);
+    /// assert_eq!(b.call(|| async { 3 }).await, 2);
+    /// assert_eq!(b.call(|| async { 3 }).await, 2);
+    /// # }
+    /// # }
+    /// ```
+    pub async fn force_refresh_with<F, Fut>(&mut self, task: F) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        #[cfg(feature = "tracing")]
+        let span = self.refresh_span();
+        self.stats.misses += 1;
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        let fut = task.call();
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, span);
+        let entry = fut.await;
+        let entry = self.force(entry);
+        self.observe_refresh(started);
+        entry
+    }
+
+    /// Like [`Bucket::call`], but the task also returns the instant at which
+    /// its value expires, whatever the policy says.
+    ///
+    /// This suits values that carry their own expiry, like tokens or signed
+    /// URLs. The deadline applies to that value only; a value stored by any
+    /// other call has none. A deadline that has already passed makes the
+    /// next call refresh again. The deadline is read on the bucket's
+    /// [`Clock`].
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "std")]
+    /// use uchimizu::{Clock, ManualClock, Policy};
+    ///
+    /// # #[cfg(not(feature = "std"))]
+    /// # fn main() {}
+    /// # #[cfg(feature = "std")]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(not(feature = "chrono-clock"))]
+    /// # let secs = std::time::Duration::from_secs;
+    /// # #[cfg(feature = "chrono-clock")]
+    /// # let secs = chrono::TimeDelta::seconds;
+    /// let clock = ManualClock::new();
+    /// let mut b = Policy::bottom_less().into_bucket_with_clock(clock.clone());
+    /// let fetch_token = || async { ("token", clock.now() + secs(60)) };
+    /// assert_eq!(b.call_with_deadline(fetch_token).await, "token");
+    /// clock.advance(secs(59));
+    /// assert_eq!(b.call(|| async { "new" }).await, "token");
+    /// clock.advance(secs(1));
+    /// assert_eq!(b.call(|| async { "new" }).await, "new");
+    /// # }
+    /// ```
+    pub async fn call_with_deadline<F, Fut>(&mut self, task: F) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = (T, Instant)>,
+    {
+        let mut deadline = None;
+        let entry = self
+            .call(|| async {
+                let (entry, at) = task.call().await;
+                deadline = Some(at);
+                entry
+            })
+            .await;
+        if let Some(deadline) = deadline {
+            let millis = duration_millis(elapsed_since(self.initiate, deadline));
+            self.deadline_millis = Some(millis);
+        }
+        entry
+    }
+
+    /// Like [`Bucket::call`], but hands `arg` to the task.
+    ///
+    /// The cached value is not keyed by `arg`: a hit is served no matter
+    /// which argument was given, and `arg` is dropped unused.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// async fn load(user: &str) -> String {
+    ///     format!("hello, {}", user)
+    /// }
+    ///
+    /// let mut b = Policy::expire_within_counts(2).into_bucket();
+    /// let user = String::from("alice");
+    /// assert_eq!(b.call_with(user.as_str(), load).await, "hello, alice");
+    /// assert_eq!(b.call_with("bob", load).await, "hello, alice");
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_with<A, F, Fut>(&mut self, arg: A, task: F) -> T
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.call(move || task(arg)).await
+    }
+
+    /// Synchronous counterpart of [`Bucket::call`].
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(1).into_bucket();
+    /// assert_eq!(b.call_sync(|| 1), 1);
+    /// assert_eq!(b.call_sync(|| 2), 1);
+    /// assert_eq!(b.call_sync(|| 3), 3);
+    /// # }
+    /// ```
+    pub fn call_sync<F>(&mut self, task: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(entry) = self.hit() {
+            self.observe(|o| o.on_hit());
+            return entry;
+        }
+        #[cfg(feature = "tracing")]
+        let _span = self.refresh_span().entered();
+        let exhausted = self.miss();
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        let entry = task();
+        if exhausted {
+            self.restart();
+        }
+        let entry = self.fill(entry);
+        self.observe_refresh(started);
+        entry
+    }
+
+    /// Like [`Bucket::call`], but for tasks that can fail.
+    ///
+    /// Errors are not cached as values: the bucket keeps whatever it held
+    /// before the call. Unless an error policy is set with
+    /// [`Bucket::with_error_policy`], the next call runs the task again.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Error, Policy};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::bottom_less().into_bucket();
+    /// let r = b.call_try(|| async { Err::<u32, _>("unavailable") }).await;
+    /// assert_eq!(r, Err(Error::Task("unavailable")));
+    /// let r = b.call_try(|| async { Ok::<_, &str>(1) }).await;
+    /// assert_eq!(r, Ok(1));
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_try<F, Fut, E>(&mut self, task: F) -> Result<T, Error<E>>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(entry) = self.hit() {
+            self.observe(|o| o.on_hit());
+            return Ok(entry);
+        }
+        if let Some(result) = self.short_circuited() {
+            return result;
+        }
+        let exhausted = self.miss();
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        match task.call().await {
+            Ok(entry) => {
+                let entry = self.complete(entry, exhausted);
+                self.observe_refresh(started);
+                Ok(entry)
+            }
+            Err(e) => self.recover_observed(e),
+        }
+    }
+
+    /// Like [`Bucket::call_try`], but never fails: if the task fails, the
+    /// stale cached value is returned, or `fallback` of the error if nothing
+    /// is cached.
+    ///
+    /// The fallback value is returned as is and not cached, so the next
+    /// call runs the task again.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{Error, Policy};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::pierced().into_bucket();
+    /// let down = || async { Err::<u32, _>("unavailable") };
+    /// let fallback = |e: Error<&str>| if matches!(e, Error::Task(_)) { 0 } else { 1 };
+    /// assert_eq!(b.call_or_else(down, fallback).await, 0);
+    /// assert_eq!(b.cached(), None);
+    /// assert_eq!(b.call_or_else(|| async { Ok(2) }, fallback).await, 2);
+    /// assert_eq!(b.call_or_else(down, fallback).await, 2);
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_or_else<F, Fut, E>(
+        &mut self,
+        task: F,
+        fallback: impl FnOnce(Error<E>) -> T,
+    ) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        match self.call_try(task).await {
+            Ok(entry) => entry,
+            Err(e) => match self.stale() {
+                Some(entry) => {
+                    self.observe(|o| o.on_hit());
+                    entry
+                }
+                None => fallback(e),
+            },
+        }
+    }
+
+    /// Like [`Bucket::call_or_else`], falling back to `T::default()`.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::bottom_less().into_bucket();
+    /// let flags = b.call_or_default(|| async { Err::<Vec<String>, _>("unavailable") }).await;
+    /// assert!(flags.is_empty());
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_or_default<F, Fut, E>(&mut self, task: F) -> T
+    where
+        T: Default,
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.call_or_else(task, |_| T::default()).await
+    }
+
+    /// Like [`Bucket::call_try`], but runs `secondary` if `primary` fails.
+    ///
+    /// A value from either task is cached the same way, and returned with
+    /// the [`CacheStatus`] saying where it came from. If both fail, the
+    /// call counts as a single failure and the two errors are returned
+    /// together, unless a stale value can be served instead, which is
+    /// returned as [`CacheStatus::Stale`].
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::{CacheStatus, Policy};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2).into_bucket();
+    /// let primary = || async { Err::<u32, _>("primary is down") };
+    /// let secondary = || async { Ok::<_, &str>(1) };
+    /// let r = b.call_with_fallback(primary, secondary).await;
+    /// assert_eq!(r, Ok((1, CacheStatus::Secondary)));
+    /// let r = b.call_with_fallback(primary, secondary).await;
+    /// assert_eq!(r, Ok((1, CacheStatus::Hit)));
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_with_fallback<F, Fut, E, G, Gut, S>(
+        &mut self,
+        primary: F,
+        secondary: G,
+    ) -> Result<(T, CacheStatus), Error<FallbackError<E, S>>>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+        G: Task<Gut>,
+        Gut: Future<Output = Result<T, S>>,
+    {
+        if let Some(entry) = self.hit() {
+            self.observe(|o| o.on_hit());
+            return Ok((entry, CacheStatus::Hit));
+        }
+        if let Some(result) = self.short_circuited() {
+            return result.map(|entry| (entry, CacheStatus::Stale));
+        }
+        let exhausted = self.miss();
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        let result = match primary.call().await {
+            Ok(entry) => Ok((entry, CacheStatus::Primary)),
+            Err(primary) => match secondary.call().await {
+                Ok(entry) => Ok((entry, CacheStatus::Secondary)),
+                Err(secondary) => Err(FallbackError { primary, secondary }),
+            },
+        };
+        match result {
+            Ok((entry, status)) => {
+                let entry = self.complete(entry, exhausted);
+                self.observe_refresh(started);
+                Ok((entry, status))
+            }
+            Err(e) => Ok((self.recover_observed(e)?, CacheStatus::Stale)),
+        }
+    }
+
+    /// Like [`Bucket::call_try`], but runs the task again according to
+    /// `retry` before giving up with the last error.
+    ///
+    /// The whole call counts as a single pour and, if every attempt fails,
+    /// a single failure. A success on any attempt is stored like a normal
+    /// refresh. Since it may run more than once, `task` must be `FnMut`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::time::Duration;
+    /// use uchimizu::{Policy, RetryPolicy};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut b = Policy::expire_within_counts(10).into_bucket();
+    /// let tries = Cell::new(0);
+    /// let task = || {
+    ///     tries.set(tries.get() + 1);
+    ///     let n = tries.get();
+    ///     async move { if n < 3 { Err("flaky") } else { Ok(n) } }
+    /// };
+    /// let retry = RetryPolicy::new(3, Duration::from_millis(1));
+    /// assert_eq!(b.call_try_with_retry(retry, task).await, Ok(3));
+    /// assert_eq!(b.hit_count(), 0);
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn call_try_with_retry<F, Fut, E>(
+        &mut self,
+        retry: RetryPolicy,
+        mut task: F,
+    ) -> Result<T, Error<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(entry) = self.hit() {
+            self.observe(|o| o.on_hit());
+            return Ok(entry);
+        }
+        if let Some(result) = self.short_circuited() {
+            return result;
+        }
+        let exhausted = self.miss();
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        let mut attempt = 1;
+        loop {
+            match task().await {
+                Ok(entry) => {
+                    let entry = self.complete(entry, exhausted);
+                    self.observe_refresh(started);
+                    return Ok(entry);
+                }
+                Err(e) if attempt >= retry.attempts => return self.recover_observed(e),
+                Err(_) => {}
+            }
+            attempt += 1;
+            tokio::time::sleep(retry.backoff).await;
+        }
+    }
+
+    /// Like [`Bucket::call`], but gives up on the task after `timeout`.
+    ///
+    /// On timeout the task is dropped and the stale cached value is returned
+    /// without refreshing the bucket, so the next call tries again. If
+    /// nothing is cached, [`TimeoutError`] is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use uchimizu::{Policy, TimeoutError};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut b = Policy::pierced().into_bucket();
+    /// let hang = || async {
+    ///     tokio::time::sleep(Duration::from_secs(60)).await;
+    ///     0
+    /// };
+    /// let timeout = Duration::from_millis(10);
+    /// assert_eq!(b.call_with_timeout(timeout, hang).await, Err(TimeoutError));
+    /// assert_eq!(b.call_with_timeout(timeout, || async { 1 }).await, Ok(1));
+    /// assert_eq!(b.call_with_timeout(timeout, hang).await, Ok(1));
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn call_with_timeout<F, Fut>(
+        &mut self,
+        timeout: std::time::Duration,
+        task: F,
+    ) -> Result<T, TimeoutError>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        if let Some(entry) = self.hit() {
+            self.observe(|o| o.on_hit());
+            return Ok(entry);
+        }
+        let exhausted = self.miss();
+        self.observe(|o| o.on_miss());
+        let started = self.start();
+        match tokio::time::timeout(timeout, task.call()).await {
+            Ok(entry) => {
+                let entry = self.complete(entry, exhausted);
+                self.observe_refresh(started);
+                Ok(entry)
+            }
+            Err(_) => {
+                let entry = self.stale().ok_or(TimeoutError)?;
+                self.observe(|o| o.on_hit());
+                Ok(entry)
+            }
+        }
+    }
+
+    /// Counts a short-circuited call if a recent failure is still cached.
+    fn is_negative_cached(&mut self) -> bool {
+        if !self.is_failure_remaining_now() {
+            return false;
+        }
+        if let Some(failure) = self.failure.as_mut() {
+            failure.hit_count = failure.hit_count.saturating_add(1);
+        }
+        true
+    }
+
+    /// Returns what to serve instead of running a fallible task, if
+    /// anything: the stale value while the breaker is open, or the graced
+    /// one while a failure is negative cached. `None` inside means there is
+    /// nothing to serve.
+    fn short_circuit(&mut self) -> Option<Option<T>> {
+        if self.breaker_state() == BreakerState::Open {
+            return Some(self.stale());
+        }
+        if self.is_negative_cached() {
+            return Some(self.graced());
+        }
+        None
+    }
+
+    fn short_circuited<E>(&mut self) -> Option<Result<T, Error<E>>> {
+        let entry = self.short_circuit()?;
+        if entry.is_some() {
+            self.observe(|o| o.on_hit());
+        }
+        Some(entry.ok_or(Error::NegativeCached))
+    }
+
+    /// Stores the result of a task started by [`Bucket::miss`].
+    fn complete(&mut self, entry: T, exhausted: bool) -> T {
+        self.failure = None;
+        if let Some(breaker) = self.breaker.as_mut() {
+            breaker.record_success();
+        }
+        if exhausted {
+            self.restart();
+        }
+        self.fill(entry)
+    }
+
+    /// Stores the result of a forced refresh, which is not a pour.
+    fn force(&mut self, entry: T) -> T {
+        self.failure = None;
+        if let Some(breaker) = self.breaker.as_mut() {
+            breaker.record_success();
+        }
+        self.restart();
+        let entry = self.fill(entry);
+        self.hit_count = 0;
+        entry
+    }
+
+    /// Records a failure of a task started by [`Bucket::miss`].
+    fn fail<E>(&mut self, e: E) -> Error<E> {
+        self.stats.failures += 1;
+        let now = self.now();
+        if let Some(breaker) = self.breaker.as_mut() {
+            breaker.record_failure(now);
+        }
+        if self.error_policy.is_some() {
+            self.failure = Some(Failure {
+                hit_count: 0,
+                initiate: self.now(),
+            });
+        }
+        Error::Task(e)
+    }
+
+    /// Records a failure, then serves the stale value instead if it is
+    /// within the grace period.
+    fn recover<E>(&mut self, e: E) -> Result<T, Error<E>> {
+        let e = self.fail(e);
+        self.graced().ok_or(e)
+    }
+
+    fn recover_observed<E>(&mut self, e: E) -> Result<T, Error<E>> {
+        let result = self.recover(e);
+        self.observe(|o| o.on_refresh_failed());
+        let entry = result?;
+        self.observe(|o| o.on_hit());
+        Ok(entry)
+    }
+
+    /// Serves the cached value if the policy is remaining once the grace
+    /// period is taken off its age.
+    fn graced(&mut self) -> Option<T> {
+        let grace = self.grace_millis?;
+        if self
+            .hard_expiry_millis()
+            .is_some_and(|millis| duration_millis(self.age()) >= millis)
+        {
+            return None;
+        }
+        let age = self.elapsed_millis().saturating_sub(grace);
+        if !self
+            .policy
+            .is_remaining(self.hit_count, millis_duration(age))
+        {
+            return None;
+        }
+        self.stale()
+    }
+
+    /// Serves the cached value if the policy is still remaining.
+    fn hit(&mut self) -> Option<T> {
+        if !self.is_servable() {
+            return None;
+        }
+        self.stale()
+    }
+
+    /// Serves the cached value even if the policy is exhausted.
+    fn stale(&mut self) -> Option<T> {
+        self.serve(1).cloned()
+    }
+
+    fn fill(&mut self, entry: T) -> T {
+        self.store(entry, 1).clone()
+    }
+}
+
+impl<T, P> Bucket<Arc<T>, P>
+where
+    P: Expiry,
+{
+    /// Like [`Bucket::call`], but wraps the task's output in an [`Arc`]
+    /// once, so that hits only bump a reference count.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// let mut b = Policy::expire_within_counts(2).into_arc_bucket();
+    /// let first = b.call_arc(|| async { vec![0u8; 1 << 20] }).await;
+    /// let second = b.call_arc(|| async { Vec::new() }).await;
+    /// assert!(Arc::ptr_eq(&first, &second));
+    /// # }
+    /// # }
+    /// ```
+    pub async fn call_arc<F, Fut>(&mut self, task: F) -> Arc<T>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        self.call(|| async { Arc::new(task.call().await) }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+
+    /// Without `std` buckets need a clock. The tests that run there only
+    /// count pours, so one that stands still will do.
+    #[cfg(not(feature = "std"))]
+    #[derive(Debug)]
+    struct Still;
+
+    #[cfg(not(feature = "std"))]
+    impl Clock for Still {
+        fn now(&self) -> Instant {
+            Instant::EPOCH
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    trait IntoBucket {
+        fn into_bucket<T>(self) -> Bucket<T>;
+        fn into_arc_bucket<T>(self) -> Bucket<Arc<T>>;
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl IntoBucket for Policy {
+        fn into_bucket<T>(self) -> Bucket<T> {
+            self.into_bucket_with_clock(Still)
+        }
+
+        fn into_arc_bucket<T>(self) -> Bucket<Arc<T>> {
+            self.into_bucket_with_clock(Still)
+        }
+    }
+
+    async fn wait_50_millis() {
+        tokio::time::sleep(core::time::Duration::from_millis(50)).await
+    }
+
+    #[tokio::test]
+    async fn it_works() {
+        let mut b = Policy::new(100, 10, 50).into_bucket();
+        b.call(wait_50_millis).await;
+    }
+
+    #[tokio::test]
+    async fn bottom_less_works() {
+        let mut b = Policy::bottom_less().into_bucket();
+        b.call(wait_50_millis).await;
+    }
+
+    #[tokio::test]
+    async fn pierced_works() {
+        let mut b = Policy::pierced().into_bucket();
+        b.call(wait_50_millis).await;
+    }
+
+    #[tokio::test]
+    async fn expire_within_secs_works() {
+        let mut b = Policy::expire_within_secs(1).into_bucket();
+        b.call(wait_50_millis).await;
+    }
+
+    #[tokio::test]
+    async fn expire_within_counts_works() {
+        let mut b = Policy::expire_within_counts(1).into_bucket();
+        b.call(wait_50_millis).await;
+    }
+
+    #[tokio::test]
     async fn race() {
         let mut b = Policy::expire_within_counts(3).into_bucket();
         tokio::select! {
-            _ = tokio::spawn(async move {
-                b.call(wait_50_millis).await;
-                b.call(wait_50_millis).await;
-            }) => {}
-            _ = tokio::time::sleep(std::time::Duration::from_millis(75)) => panic!(),
-            else => panic!()
+            _ = tokio::spawn(async move {
+                b.call(wait_50_millis).await;
+                b.call(wait_50_millis).await;
+            }) => {}
+            _ = tokio::time::sleep(core::time::Duration::from_millis(75)) => panic!(),
+            else => panic!()
+        }
+    }
+
+    #[tokio::test]
+    async fn call_try_does_not_cache_errors() {
+        let mut b = Policy::expire_within_counts(3).into_bucket();
+        assert_eq!(
+            b.call_try(|| async { Err::<u32, _>(()) }).await,
+            Err(Error::Task(()))
+        );
+        assert_eq!(b.cache, None);
+        assert_eq!(b.hit_count, 0);
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(1) }).await, Ok(1));
+        assert_eq!(b.hit_count, 0);
+    }
+
+    #[tokio::test]
+    async fn call_try_keeps_stale_cache_on_error() {
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        b.call_try(|| async { Ok::<_, ()>(1) }).await.unwrap();
+        b.call_try(|| async { Ok::<_, ()>(2) }).await.unwrap();
+        b.call_try(|| async { Ok::<_, ()>(3) }).await.unwrap();
+        let initiate = b.initiate;
+        assert_eq!(
+            b.call_try(|| async { Err::<u32, _>(()) }).await,
+            Err(Error::Task(()))
+        );
+        assert_eq!(b.cache, Some(1));
+        assert_eq!(b.hit_count, 2);
+        assert_eq!(b.initiate, initiate);
+    }
+
+    #[tokio::test]
+    async fn error_policy_short_circuits_until_expired() {
+        let mut b = Policy::pierced()
+            .into_bucket()
+            .with_error_policy(Policy::expire_within_counts(2));
+        let r = b.call_try(|| async { Err::<u32, _>(()) }).await;
+        assert_eq!(r, Err(Error::Task(())));
+        for _ in 0..2 {
+            let r = b.call_try(|| async { Ok::<_, ()>(1) }).await;
+            assert_eq!(r, Err(Error::NegativeCached));
+        }
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(1) }).await, Ok(1));
+        assert_eq!(b.failure, None);
+    }
+
+    #[tokio::test]
+    async fn error_policy_does_not_shadow_fresh_cache() {
+        let mut b = Policy::expire_within_counts(2)
+            .into_bucket()
+            .with_error_policy(Policy::bottom_less());
+        for _ in 0..3 {
+            b.call_try(|| async { Ok::<_, ()>(1) }).await.unwrap();
+        }
+        let r = b.call_try(|| async { Err::<u32, _>(()) }).await;
+        assert_eq!(r, Err(Error::Task(())));
+        assert_eq!(b.cache, Some(1));
+        b.refresh();
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(2) }).await, Ok(2));
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(3) }).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn call_sync_and_call_share_counters() {
+        let mut b = Policy::expire_within_counts(3).into_bucket();
+        assert_eq!(b.call_sync(|| 1), 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(b.call_sync(|| 3), 1);
+        assert_eq!(b.call(|| async { 4 }).await, 1);
+        assert_eq!(b.hit_count, 3);
+        assert_eq!(b.call_sync(|| 5), 5);
+        assert_eq!(b.hit_count, 0);
+        assert_eq!(b.call(|| async { 6 }).await, 5);
+        assert_eq!(b.hit_count, 1);
+    }
+
+    #[test]
+    fn cached_and_fresh_do_not_pour() {
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        b.call_sync(|| 1);
+        let initiate = b.initiate;
+        for _ in 0..3 {
+            assert_eq!(b.cached(), Some(&1));
+            assert_eq!(b.fresh(), Some(&1));
+        }
+        assert_eq!(b.hit_count, 0);
+        assert_eq!(b.initiate, initiate);
+        b.call_sync(|| 2);
+        b.call_sync(|| 3);
+        assert_eq!(b.cached(), Some(&1));
+        assert_eq!(b.fresh(), None);
+    }
+
+    #[test]
+    fn invalidate_keeps_counters_refresh_resets_them() {
+        let mut b = Policy::expire_within_counts(3).into_bucket();
+        b.call_sync(|| 1);
+        b.call_sync(|| 1);
+        b.invalidate();
+        assert_eq!(b.hit_count, 1);
+        assert_eq!(b.call_sync(|| 2), 2);
+        assert_eq!(b.hit_count, 1);
+        assert_eq!(b.call_sync(|| 3), 2);
+        assert_eq!(b.call_sync(|| 4), 2);
+        assert_eq!(b.hit_count, 3);
+        assert_eq!(b.call_sync(|| 5), 5);
+        assert_eq!(b.hit_count, 0);
+
+        let mut b = Policy::expire_within_counts(3).into_bucket();
+        b.call_sync(|| 1);
+        b.call_sync(|| 1);
+        b.refresh();
+        assert_eq!(b.hit_count, 0);
+        assert_eq!(b.call_sync(|| 2), 2);
+        assert_eq!(b.call_sync(|| 3), 2);
+        assert_eq!(b.call_sync(|| 4), 2);
+        assert_eq!(b.call_sync(|| 5), 2);
+        assert_eq!(b.hit_count, 3);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn remaining_agrees_with_is_remaining() {
+        let p = Policy::new(100, 10, 3);
+        for (hit_count, duration_secs) in [
+            (0, 0),
+            (9, 0),
+            (10, 0),
+            (3, 23),
+            (3, 24),
+            (u64::MAX, 0),
+            (0, u64::MAX),
+            (u64::MAX, u64::MAX),
+        ] {
+            assert_eq!(
+                p.remaining_after(hit_count, duration_secs) > 0,
+                p.is_remaining(hit_count, duration_secs)
+            );
+        }
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        b.call_sync(|| 1);
+        b.call_sync(|| 1);
+        assert!(b.remaining() > 0);
+        b.call_sync(|| 1);
+        assert_eq!(b.remaining(), 0);
+        assert_eq!(b.consumed(), 2);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn is_remaining_does_not_wrap_around() {
+        let p = Policy::new_u64(u64::MAX, 10, 10);
+        // 10 * 1844674407370955162 wraps to 4 in u64 arithmetic.
+        assert!(!p.is_remaining(1844674407370955162, 0));
+        assert!(!p.is_remaining(0, 1844674407370955162));
+        assert!(!p.is_remaining(u64::MAX, u64::MAX));
+        let p = Policy::new_u64(u64::MAX, u64::MAX, u64::MAX);
+        assert!(p.is_remaining(0, 0));
+        assert!(!p.is_remaining(1, 0));
+        assert!(!p.is_remaining(u64::MAX, 1));
+    }
+
+    #[test]
+    fn constructors_take_u32_amounts() {
+        let (count, secs): (u32, u32) = (3, 60);
+        assert_eq!(
+            Policy::expire_within_counts(count),
+            Policy::expire_within_counts_u64(3)
+        );
+        assert_eq!(
+            Policy::expire_within_secs(secs),
+            Policy::expire_within_secs_u64(60)
+        );
+        assert_eq!(
+            Policy::expire_within_counts_or_secs(count, secs),
+            Policy::expire_within_counts_or_secs_u64(3, 60)
+        );
+        assert_eq!(Policy::new(count, 1, 0), Policy::new_u64(3, 1, 0));
+        assert_eq!(
+            Policy::replenishing(count, 1, 1),
+            Policy::replenishing_u64(3, 1, 1)
+        );
+    }
+
+    #[test]
+    fn hit_count_saturates() {
+        let mut b = Policy::expire_within_counts_u64(u64::MAX).into_bucket();
+        b.call_sync(|| 1);
+        b.hit_count = u64::MAX - 1;
+        assert_eq!(b.call_sync(|| 2), 1);
+        assert_eq!(b.hit_count, u64::MAX);
+        assert_eq!(b.call_sync(|| 3), 3);
+
+        let mut b = Policy::bottom_less().into_bucket();
+        b.call_sync(|| 1);
+        b.hit_count = u64::MAX;
+        assert_eq!(b.call_sync(|| 2), 1);
+        assert_eq!(b.hit_count, u64::MAX);
+    }
+
+    #[test]
+    fn time_to_expiry_counts_down() {
+        let mut b = Policy::new(10, 2, 1).into_bucket();
+        let ttl = b.time_to_expiry().unwrap();
+        assert!(ttl <= millis_duration(10_000));
+        assert!(ttl > millis_duration(9_000));
+        b.call_sync(|| 1);
+        b.call_sync(|| 1);
+        let ttl = b.time_to_expiry().unwrap();
+        assert!(ttl <= millis_duration(8_000));
+        assert!(ttl > millis_duration(7_000));
+        for _ in 0..4 {
+            b.call_sync(|| 1);
+        }
+        assert_eq!(b.time_to_expiry(), Some(millis_duration(0)));
+    }
+
+    #[test]
+    fn expire_within_rounds_up() {
+        assert_eq!(
+            Policy::expire_within(millis_duration(1500)),
+            Policy::expire_within_millis(1500)
+        );
+        assert_eq!(
+            Policy::expire_within(millis_duration(0)),
+            Policy::expire_within_millis(0)
+        );
+        #[cfg(not(feature = "chrono-clock"))]
+        {
+            let d = core::time::Duration::from_nanos(1_000_001);
+            assert_eq!(Policy::expire_within(d), Policy::expire_within_millis(2));
+            let d = core::time::Duration::MAX;
+            assert_eq!(
+                Policy::try_expire_within(d),
+                Err(PolicyError::DurationOutOfRange)
+            );
+        }
+        #[cfg(feature = "chrono-clock")]
+        {
+            let d = chrono::TimeDelta::nanoseconds(1_000_001);
+            assert_eq!(Policy::expire_within(d), Policy::expire_within_millis(2));
+            let d = chrono::TimeDelta::milliseconds(-1);
+            assert_eq!(
+                Policy::try_expire_within(d),
+                Err(PolicyError::DurationOutOfRange)
+            );
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn combinators_nest() {
+        let p = Policy::all_of(vec![
+            Policy::any_of(vec![
+                Policy::expire_within_counts(10),
+                Policy::expire_within_secs(10),
+            ]),
+            Policy::expire_within_counts(20),
+        ]);
+        assert!(p.is_remaining(9, 9));
+        assert!(p.is_remaining(10, 0));
+        assert!(p.is_remaining(0, 10));
+        assert!(!p.is_remaining(20, 10));
+        assert!(!p.is_remaining(20, 0));
+        assert_eq!(p.remaining_after(5, 0), 15);
+        assert!(Policy::any_of(vec![]).is_remaining(u64::MAX, u64::MAX));
+        assert!(!Policy::all_of(vec![]).is_remaining(0, 0));
+    }
+
+    #[test]
+    fn combinators_time_to_expiry() {
+        let b = Policy::any_of(vec![
+            Policy::expire_within_counts(10),
+            Policy::expire_within_secs(10),
+            Policy::expire_within_secs(5),
+        ])
+        .into_bucket::<()>();
+        let ttl = b.time_to_expiry().unwrap();
+        assert!(ttl <= millis_duration(5_000) && ttl > millis_duration(4_000));
+
+        let b = Policy::all_of(vec![
+            Policy::expire_within_counts(10),
+            Policy::expire_within_secs(10),
+        ])
+        .into_bucket::<()>();
+        assert_eq!(b.time_to_expiry(), None);
+
+        let b = Policy::all_of(vec![
+            Policy::expire_within_secs(10),
+            Policy::expire_within_secs(5),
+        ])
+        .into_bucket::<()>();
+        let ttl = b.time_to_expiry().unwrap();
+        assert!(ttl <= millis_duration(10_000) && ttl > millis_duration(9_000));
+    }
+
+    #[cfg(all(feature = "serde", not(feature = "chrono-clock")))]
+    #[test]
+    fn serde_std_keeps_elapsed_time() {
+        let mut b = Policy::expire_within_secs(60).into_bucket();
+        b.call_sync(|| 1);
+        b.initiate -= core::time::Duration::from_secs(30);
+        let json = serde_json::to_string(&b).unwrap();
+        let mut restored: Bucket<u32> = serde_json::from_str(&json).unwrap();
+        let age = restored.age();
+        assert!(age >= core::time::Duration::from_secs(30));
+        assert!(age < core::time::Duration::from_secs(31));
+        assert_eq!(restored.hit_count(), 0);
+        assert_eq!(restored.call_sync(|| 2), 1);
+    }
+
+    #[cfg(all(feature = "serde", not(feature = "chrono-clock")))]
+    #[test]
+    fn serde_std_saturates_an_unrepresentable_age() {
+        let mut b = Policy::expire_within_secs(1).into_bucket();
+        b.call_sync(|| 1);
+        let mut json = serde_json::to_value(&b).unwrap();
+        json["initiate"] = serde_json::json!({ "secs": u64::MAX, "nanos": 0 });
+        let mut restored: Bucket<u32> = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            restored.last_refreshed_at(),
+            saturating_instant_sub(now(), Duration::MAX)
+        );
+        assert!(!restored.is_fresh());
+        assert_eq!(restored.call_sync(|| 2), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn policy_serde_round_trip() {
+        let linear = Policy::new(100, 1, 2);
+        assert_eq!(
+            serde_json::to_string(&linear).unwrap(),
+            r#"{"initial_amount":100,"pour_cost":1,"evaporation_cost":2}"#
+        );
+        let p = Policy::all_of(vec![
+            Policy::any_of(vec![linear.clone(), Policy::pierced()]),
+            Policy::bottom_less(),
+        ]);
+        let json = serde_json::to_string(&p).unwrap();
+        assert_eq!(serde_json::from_str::<Policy>(&json).unwrap(), p);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn expire_within_millis_works() {
+        let mut b = Policy::expire_within_millis(100).into_bucket();
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        wait_50_millis().await;
+        wait_50_millis().await;
+        assert_eq!(b.call(|| async { 3 }).await, 3);
+    }
+
+    #[test]
+    fn introspection_accessors() {
+        let mut b = Policy::bottom_less().into_bucket();
+        let initiate = b.last_refreshed_at();
+        b.call_sync(|| 1);
+        b.call_sync(|| 1);
+        assert_eq!(b.hit_count(), 1);
+        assert!(b.age() < millis_duration(1_000));
+        b.refresh();
+        assert_eq!(b.hit_count(), 0);
+        assert!(b.last_refreshed_at() >= initiate);
+    }
+
+    #[tokio::test]
+    async fn stats_count_hits_misses_and_failures() {
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        b.call_sync(|| 1);
+        b.call(|| async { 1 }).await;
+        b.call_sync(|| 1);
+        b.call_try(|| async { Err::<u32, _>(()) })
+            .await
+            .unwrap_err();
+        b.call_try(|| async { Ok::<_, ()>(2) }).await.unwrap();
+        assert_eq!(
+            b.stats(),
+            &BucketStats {
+                hits: 2,
+                misses: 3,
+                refreshes: 2,
+                failures: 1,
+            }
+        );
+        let other = b.clone();
+        b.reset_stats();
+        assert_eq!(b.stats(), &BucketStats::default());
+        assert_eq!(b, other);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn call_with_timeout_keeps_stale_bucket_expired() {
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        let timeout = core::time::Duration::from_secs(1);
+        let hang = || async {
+            tokio::time::sleep(core::time::Duration::from_secs(60)).await;
+            0
+        };
+        assert_eq!(b.call_with_timeout(timeout, || async { 1 }).await, Ok(1));
+        assert_eq!(b.call_with_timeout(timeout, hang).await, Ok(1));
+        let initiate = b.last_refreshed_at();
+        assert_eq!(b.call_with_timeout(timeout, hang).await, Ok(1));
+        assert_eq!(b.last_refreshed_at(), initiate);
+        assert_eq!(b.call_with_timeout(timeout, || async { 2 }).await, Ok(2));
+        assert_eq!(b.stats().refreshes, 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn call_try_with_retry_gives_up_after_attempts() {
+        let mut b = Policy::pierced()
+            .into_bucket()
+            .with_error_policy(Policy::expire_within_counts(1));
+        let tries = core::cell::Cell::new(0);
+        let task = || {
+            tries.set(tries.get() + 1);
+            async { Err::<u32, _>(tries.get()) }
+        };
+        let retry = RetryPolicy::new(4, core::time::Duration::from_secs(1));
+        let started = tokio::time::Instant::now();
+        assert_eq!(
+            b.call_try_with_retry(retry, task).await,
+            Err(Error::Task(4))
+        );
+        assert_eq!(started.elapsed(), core::time::Duration::from_secs(3));
+        assert_eq!(b.stats().misses, 1);
+        assert_eq!(b.stats().failures, 1);
+        assert_eq!(
+            b.call_try_with_retry(retry, task).await,
+            Err(Error::NegativeCached)
+        );
+        assert_eq!(tries.get(), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn grace_serves_stale_on_failure_until_window_ends() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10)
+            .into_bucket_with_clock(clock.clone())
+            .with_grace(millis_duration(5_000));
+        let fail = || async { Err::<u32, _>(()) };
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(1) }).await, Ok(1));
+        clock.advance(millis_duration(14_999));
+        assert_eq!(b.call_try(fail).await, Ok(1));
+        assert_eq!(b.call_try(fail).await, Ok(1));
+        clock.advance(millis_duration(1));
+        assert_eq!(b.call_try(fail).await, Err(Error::Task(())));
+
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(2) }).await, Ok(2));
+        clock.advance(millis_duration(12_000));
+        assert_eq!(b.call_try(fail).await, Ok(2));
+        assert_eq!(b.stats().failures, 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn grace_ends_at_the_hard_deadline() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(1)
+            .into_bucket_with_clock(clock.clone())
+            .with_grace(millis_duration(60_000));
+        let deadline = clock.now() + millis_duration(5_000);
+        b.call_with_deadline(|| async { (1, deadline) }).await;
+        let fail = || async { Err::<u32, _>(()) };
+        clock.advance(millis_duration(4_999));
+        assert_eq!(b.call_try(fail).await, Ok(1));
+        clock.advance(millis_duration(1));
+        assert_eq!(b.call_try(fail).await, Err(Error::Task(())));
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn grace_applies_while_negative_cached() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10)
+            .into_bucket_with_clock(clock.clone())
+            .with_error_policy(Policy::expire_within_counts(5))
+            .with_grace(millis_duration(5_000));
+        b.call_sync(|| 1);
+        clock.advance(millis_duration(11_000));
+        assert_eq!(b.call_try(|| async { Err::<u32, _>(()) }).await, Ok(1));
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(2) }).await, Ok(1));
+        clock.advance(millis_duration(4_000));
+        assert_eq!(
+            b.call_try(|| async { Ok::<_, ()>(2) }).await,
+            Err(Error::NegativeCached)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn on_evict_runs_once_the_new_value_is_ready() {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut b = Policy::expire_within_counts(1).into_bucket().on_evict({
+            let evicted = evicted.clone();
+            move |v| evicted.lock().unwrap().push(v)
+        });
+        b.refresh();
+        b.invalidate();
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert!(evicted.lock().unwrap().is_empty());
+        let seen = evicted.clone();
+        assert_eq!(
+            b.call(|| {
+                assert!(seen.lock().unwrap().is_empty());
+                async { 2 }
+            })
+            .await,
+            2
+        );
+        assert_eq!(*evicted.lock().unwrap(), vec![1]);
+        b.invalidate();
+        assert_eq!(*evicted.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct SpanNames(Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+    #[cfg(feature = "tracing")]
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanNames {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            self.0.lock().unwrap().push(attrs.metadata().name());
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn refresh_span_fires_on_miss_only() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let names = SpanNames::default();
+        let subscriber = tracing_subscriber::registry().with(names.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut b = Policy::expire_within_counts(2).into_bucket();
+            b.call_sync(|| 1);
+            b.call_sync(|| 2);
+        });
+        assert_eq!(*names.0.lock().unwrap(), vec!["refresh"]);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn named_bucket_exports_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let mut b = Policy::expire_within_counts(3)
+                .into_bucket()
+                .named("quotes");
+            for _ in 0..3 {
+                b.call_sync(|| 1);
+            }
+            let mut unnamed = Policy::pierced().into_bucket();
+            unnamed.call_sync(|| 1);
+        });
+        let mut counters = std::collections::HashMap::new();
+        let mut histograms = 0;
+        for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+            let key = key.key();
+            let labels: Vec<_> = key
+                .labels()
+                .map(|l| (l.key().to_string(), l.value().to_string()))
+                .collect();
+            assert_eq!(labels, vec![("bucket".to_string(), "quotes".to_string())]);
+            match value {
+                DebugValue::Counter(n) => {
+                    counters.insert(key.name().to_string(), n);
+                }
+                DebugValue::Histogram(values) => histograms += values.len(),
+                DebugValue::Gauge(_) => unreachable!(),
+            }
+        }
+        assert_eq!(counters["uchimizu_cache_hits_total"], 2);
+        assert_eq!(counters["uchimizu_cache_misses_total"], 1);
+        assert_eq!(histograms, 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[derive(Debug, Default)]
+    struct Recorder(std::sync::Mutex<Vec<String>>);
+
+    #[cfg(feature = "std")]
+    impl BucketObserver for Recorder {
+        fn on_hit(&self) {
+            self.0.lock().unwrap().push("hit".into());
+        }
+
+        fn on_miss(&self) {
+            self.0.lock().unwrap().push("miss".into());
+        }
+
+        fn on_refresh_complete(&self, elapsed: Duration) {
+            let millis = duration_millis(elapsed);
+            self.0.lock().unwrap().push(format!("refresh {}", millis));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn observer_sees_hits_misses_and_refresh_time() {
+        let clock = ManualClock::new();
+        let recorder = Arc::new(Recorder::default());
+        let mut b = Policy::expire_within_counts(2)
+            .into_bucket_with_clock(clock.clone())
+            .with_observer(recorder.clone());
+        b.call_sync(|| {
+            clock.advance(millis_duration(30));
+            1
+        });
+        b.call_sync(|| 2);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec!["miss", "refresh 30", "hit"]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn jitter_is_stable_within_a_cycle() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10)
+            .into_bucket_with_clock(clock.clone())
+            .with_jitter(0.5);
+        b.call_sync(|| 1);
+        let ttl = duration_millis(b.time_to_expiry().unwrap());
+        assert!((5_000..=15_000).contains(&ttl));
+        clock.advance(millis_duration(ttl - 1));
+        for _ in 0..10 {
+            assert!(b.fresh().is_some());
+        }
+        clock.advance(millis_duration(1));
+        for _ in 0..10 {
+            assert!(b.fresh().is_none());
+        }
+        assert_eq!(b.call_sync(|| 2), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn manual_clock_drives_expiry() {
+        let clock = ManualClock::new();
+        let mut b = Policy::any_of(vec![
+            Policy::expire_within_secs(3600),
+            Policy::expire_within_counts(100),
+        ])
+        .into_bucket_with_clock(clock.clone());
+        assert_eq!(b.call_sync(|| 1), 1);
+        clock.advance(millis_duration(3_599_999));
+        assert_eq!(b.call_sync(|| 2), 1);
+        assert_eq!(b.age(), millis_duration(3_599_999));
+        clock.advance(millis_duration(1));
+        assert_eq!(b.time_to_expiry(), Some(millis_duration(0)));
+        assert_eq!(b.call_sync(|| 3), 3);
+        assert_eq!(b.last_refreshed_at(), clock.now());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn manual_clock_drives_error_policy() {
+        let clock = ManualClock::new();
+        let mut b = Policy::pierced()
+            .into_bucket()
+            .with_error_policy(Policy::expire_within_secs(60))
+            .with_clock(clock.clone());
+        b.call_try(|| async { Err::<u32, _>(()) })
+            .await
+            .unwrap_err();
+        let r = b.call_try(|| async { Ok::<_, ()>(1) }).await;
+        assert_eq!(r, Err(Error::NegativeCached));
+        clock.advance(millis_duration(60_000));
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(1) }).await, Ok(1));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn tokio_paused_clock_drives_expiry() {
+        let mut b = Policy::expire_within_secs(3600).into_bucket();
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        tokio::time::advance(core::time::Duration::from_secs(3600)).await;
+        assert_eq!(b.call(|| async { 3 }).await, 3);
+    }
+
+    #[cfg(feature = "chrono-clock")]
+    #[test]
+    fn age_is_zero_when_initiate_is_in_the_future() {
+        let mut b = Policy::bottom_less().into_bucket::<()>();
+        b.initiate = now() + millis_duration(60_000);
+        assert_eq!(b.age(), millis_duration(0));
+    }
+
+    /// Loads a snapshot of a bucket that was refreshed once with "hello".
+    #[cfg(feature = "serde")]
+    fn load_fixture(json: &str) -> Bucket<String> {
+        let b: Bucket<String> = serde_json::from_str(json).unwrap();
+        assert_eq!(b.cached().map(String::as_str), Some("hello"));
+        assert_eq!(b.hit_count(), 1);
+        assert_eq!(b.policy, Policy::expire_within_secs(60));
+        assert_eq!(b.error_policy, Some(Policy::expire_within_counts(3)));
+        assert_eq!(b.stats().refreshes, 1);
+        b
+    }
+
+    #[cfg(all(feature = "serde", feature = "chrono-clock"))]
+    #[test]
+    fn snapshot_fixtures_load() {
+        for json in [
+            include_str!("fixtures/bucket_unversioned.json"),
+            include_str!("fixtures/bucket_v1.json"),
+        ] {
+            load_fixture(json);
+        }
+    }
+
+    #[cfg(all(feature = "serde", not(feature = "chrono-clock")))]
+    #[test]
+    fn elapsed_snapshot_fixtures_load() {
+        for json in [
+            include_str!("fixtures/bucket_unversioned_elapsed.json"),
+            include_str!("fixtures/bucket_v1_elapsed.json"),
+        ] {
+            let b = load_fixture(json);
+            assert!(b.age() < core::time::Duration::from_secs(1));
+            assert!(b.is_fresh());
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "chrono-clock"))]
+    #[test]
+    fn snapshot_writes_current_version() {
+        let b = Policy::pierced().into_bucket::<()>();
+        let json: serde_json::Value = serde_json::to_value(&b).unwrap();
+        assert_eq!(json["version"], snapshot::VERSION);
+    }
+
+    #[cfg(all(feature = "serde", feature = "chrono-clock"))]
+    #[test]
+    fn snapshot_rejects_unknown_version() {
+        let json =
+            include_str!("fixtures/bucket_v1.json").replace(r#""version": 1"#, r#""version": 7"#);
+        let err = serde_json::from_str::<Bucket<String>>(&json).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unsupported bucket snapshot version 7, expected at most 1"));
+    }
+
+    #[cfg(all(feature = "serde", feature = "chrono-clock"))]
+    #[test]
+    fn future_initiate_survives_round_trip() {
+        let mut b = Policy::expire_within_secs(10).into_bucket();
+        b.call_sync(|| 1);
+        b.initiate = now() + millis_duration(3_600_000);
+        let json = serde_json::to_string(&b).unwrap();
+        let mut restored: Bucket<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.age(), millis_duration(0));
+        assert_eq!(restored.remaining(), 10_000);
+        assert_eq!(restored.call_sync(|| 2), 1);
+
+        restored.clamp_initiate();
+        assert!(restored.last_refreshed_at() <= now());
+        assert!(restored.time_to_expiry().unwrap() <= millis_duration(10_000));
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn time_based_policies_expire_by_the_given_clock() {
+        use core::sync::atomic::{AtomicU64, Ordering};
+
+        static MILLIS: AtomicU64 = AtomicU64::new(0);
+
+        #[derive(Debug)]
+        struct Ticks;
+
+        impl Clock for Ticks {
+            fn now(&self) -> Instant {
+                Instant::EPOCH + millis_duration(MILLIS.load(Ordering::SeqCst))
+            }
+        }
+
+        let mut b = Bucket::new_with_clock(Policy::expire_within_secs(1), Ticks);
+        assert_eq!(b.call_sync(|| 1), 1);
+        MILLIS.store(999, Ordering::SeqCst);
+        assert_eq!(b.call_sync(|| 2), 1);
+        MILLIS.store(1_000, Ordering::SeqCst);
+        assert_eq!(b.call_sync(|| 3), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn from_parts_refreshes_when_over_budget() {
+        let mut b = Bucket::from_parts(Policy::expire_within_counts(2), Some(1), 5, now());
+        assert_eq!(b.call_sync(|| 2), 2);
+        let (_, cache, hit_count, _) = b.into_parts();
+        assert_eq!((cache, hit_count), (Some(2), 0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn set_policy_revives_and_expires() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10).into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 1);
+        clock.advance(millis_duration(20_000));
+        assert_eq!(b.fresh(), None);
+
+        b.set_policy(Policy::expire_within_secs(60));
+        assert_eq!(b.fresh(), Some(&1));
+        assert_eq!(b.call_sync(|| 2), 1);
+
+        b.set_policy(Policy::expire_within_secs(10));
+        assert_eq!(b.call_sync(|| 3), 3);
+    }
+
+    #[test]
+    fn composite_accessors_and_modifiers() {
+        let p = Policy::any_of(vec![
+            Policy::expire_within_counts(3),
+            Policy::expire_within_secs(60),
+        ]);
+        assert_eq!(p.initial_amount(), None);
+        assert_eq!(
+            p.with_pour_cost(2),
+            Policy::any_of(vec![Policy::new(3, 2, 0), Policy::new(60_000, 2, 1000)])
+        );
+        let p = Policy::all_of(vec![p, Policy::new_u64(u64::MAX, 0, 0)]);
+        assert_eq!(p.scaled(2), Err(PolicyError::Overflow));
+    }
+
+    #[tokio::test]
+    async fn task_can_move_a_one_shot_value() {
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        let request = String::from("once");
+        assert_eq!(b.call(move || async move { request }).await, "once");
+        assert_eq!(b.call(|| async { String::new() }).await, "once");
+    }
+
+    #[tokio::test]
+    async fn stateful_task_runs_once_per_refresh() {
+        let mut b = Policy::expire_within_counts(1).into_bucket();
+        let mut cursor = 0;
+        let mut task = || {
+            cursor += 1;
+            let page = cursor;
+            async move { page }
+        };
+        let mut pages = Vec::new();
+        for _ in 0..4 {
+            pages.push(b.call(&mut task).await);
+        }
+        assert_eq!(pages, vec![1, 1, 2, 2]);
+        assert_eq!(cursor, 2);
+    }
+
+    #[tokio::test]
+    async fn call_ref_does_not_need_clone() {
+        #[derive(Debug, PartialEq)]
+        struct Config(Vec<u8>);
+
+        let mut b = Policy::expire_within_counts(2).into_bucket();
+        let load = || async { Config(vec![1, 2, 3]) };
+        assert_eq!(b.call_ref(load, |c| c.0.len()).await, 3);
+        assert_eq!(b.call_ref(load, |c| c.0[0]).await, 1);
+        assert_eq!(b.stats().hits, 1);
+        assert_eq!(b.stats().refreshes, 1);
+    }
+
+    #[tokio::test]
+    async fn call_arc_shares_a_value_that_is_not_clone() {
+        struct Parsed(#[allow(dead_code)] u32);
+
+        let mut b = Policy::expire_within_counts(3).into_arc_bucket();
+        let first = b.call_arc(|| async { Parsed(1) }).await;
+        let second = b.call_arc(|| async { Parsed(2) }).await;
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(Arc::strong_count(&first), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn try_cached_reports_age_without_pouring() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10).into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 1);
+        assert_eq!(b.try_cached(), Ok(&1));
+        clock.advance(millis_duration(12_000));
+        assert_eq!(
+            b.try_cached(),
+            Err(CacheMiss::Expired {
+                age: millis_duration(12_000)
+            })
+        );
+        assert_eq!(b.hit_count(), 0);
+        assert_eq!(b.stats().hits, 0);
+    }
+
+    #[test]
+    fn single_pour_exhausts_bucket() {
+        let mut b = Policy::expire_within_counts(10).into_bucket();
+        b.call_sync(|| 1);
+        b.pour(10);
+        assert_eq!(b.fresh(), None);
+        assert_eq!(b.call_sync(|| 2), 2);
+        b.pour(u64::MAX);
+        assert_eq!(b.hit_count(), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn heavy_call_saturates_and_exhausts() {
+        let mut b = Policy::expire_within_counts(100).into_bucket();
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call_weighted(u64::MAX, || async { 2 }).await, 1);
+        assert_eq!(b.hit_count(), u64::MAX);
+        assert_eq!(b.call(|| async { 3 }).await, 3);
+        assert_eq!(b.hit_count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn evaporate_saturates() {
+        let mut b = Policy::expire_within_secs(60).into_bucket();
+        b.call_sync(|| 1);
+        b.evaporate(millis_duration(30_000));
+        assert!(b.age() >= millis_duration(30_000));
+        assert_eq!(b.call_sync(|| 2), 1);
+        b.evaporate(millis_duration(u64::MAX));
+        assert!(b.age() > millis_duration(60_000));
+        assert_eq!(b.call_sync(|| 3), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn past_deadline_refreshes_on_next_call() {
+        let clock = ManualClock::new();
+        let mut b = Policy::bottom_less().into_bucket_with_clock(clock.clone());
+        let past = clock.now();
+        clock.advance(millis_duration(1_000));
+        let runs = core::cell::Cell::new(0);
+        let task = || async {
+            runs.set(runs.get() + 1);
+            (runs.get(), past)
+        };
+        assert_eq!(b.call_with_deadline(task).await, 1);
+        assert_eq!(b.time_to_expiry(), Some(millis_duration(0)));
+        assert_eq!(b.call_with_deadline(task).await, 2);
+        assert_eq!(b.call(|| async { 3 }).await, 3);
+        assert_eq!(b.call(|| async { 4 }).await, 3);
+        assert_eq!(b.time_to_expiry(), None);
+    }
+
+    #[cfg(feature = "chrono-clock")]
+    #[test]
+    fn boundary_bounds_time_to_expiry() {
+        use chrono::TimeZone;
+
+        let start = chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 1, 0).unwrap();
+        let clock = ManualClock::starting_at(start);
+        let mut b = Policy::expire_within_secs(3600)
+            .into_bucket_with_clock(clock.clone())
+            .expire_at_boundary(Boundary::Hour);
+        b.call_sync(|| 1);
+        assert_eq!(b.time_to_expiry(), Some(chrono::TimeDelta::minutes(59)));
+        clock.advance(chrono::TimeDelta::minutes(59));
+        assert_eq!(b.fresh(), None);
+        assert_eq!(b.call_sync(|| 2), 2);
+        assert_eq!(b.time_to_expiry(), Some(chrono::TimeDelta::hours(1)));
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn call_survives_clock_stepping_backwards() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut b = Policy::expire_within_secs(10).into_bucket_with_clock(clock.clone());
+        clock.advance(millis_duration(5_000));
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        clock.set(start);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(3) }).await, Ok(1));
+        assert_eq!(b.time_to_expiry(), Some(millis_duration(10_000)));
+    }
+
+    #[cfg(feature = "chrono-clock")]
+    #[tokio::test]
+    async fn call_survives_future_initiate() {
+        let mut b = Policy::expire_within_secs(10).into_bucket();
+        b.call_sync(|| 1);
+        b.initiate = now() + millis_duration(3_600_000);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(b.call_sync(|| 3), 1);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn huge_evaporation_cost_does_not_overflow() {
+        const CENTURY_SECS: u64 = 100 * 365 * 24 * 3600;
+        let p = Policy::new_u64(u64::MAX, 1, u64::from(u32::MAX));
+        assert!(p.is_remaining(u64::from(u32::MAX), CENTURY_SECS));
+        assert_eq!(
+            p.remaining_after(0, CENTURY_SECS),
+            u64::MAX - u64::from(u32::MAX) * CENTURY_SECS
+        );
+        assert!(!p.is_remaining(0, u64::MAX));
+
+        let p = Policy::new_u64(u64::MAX, 0, u64::MAX);
+        assert!(p.is_remaining_millis(0, 999));
+        assert!(!p.is_remaining_millis(0, 1000));
+        assert_eq!(p.remaining_after(u64::MAX, u64::MAX), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn custom_curve_drives_time_to_expiry() {
+        let clock = ManualClock::new();
+        let policy =
+            Policy::new(100, 1, 0).with_evaporation_fn(|millis| (millis / 1000).saturating_pow(2));
+        let mut b = policy.into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 1);
+        assert_eq!(b.time_to_expiry(), Some(millis_duration(10_000)));
+        clock.advance(millis_duration(9_000));
+        assert_eq!(b.call_sync(|| 2), 1);
+        clock.advance(millis_duration(1_000));
+        assert_eq!(b.call_sync(|| 3), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn custom_curve_refuses_to_serialize() {
+        let p = Policy::expire_within_secs(1).with_evaporation_fn(|millis| millis);
+        let err = serde_json::to_string(&p).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("custom evaporation curves cannot be serialized"));
+        assert_eq!(
+            serde_json::to_string(&Policy::expire_within_secs(1)).unwrap(),
+            r#"{"initial_amount":1000,"pour_cost":0,"evaporation_cost":1000}"#
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn replenishing_round_trips_and_never_expires_by_time() {
+        let p = Policy::replenishing(10, 2, 1);
+        assert_eq!(p.remaining_after(5, 0), 0);
+        assert_eq!(p.remaining_after(5, 4), 4);
+        assert_eq!(p.remaining_after(0, u64::MAX), 10);
+
+        let clock = ManualClock::new();
+        let mut b = p.clone().into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 1);
+        assert_eq!(b.time_to_expiry(), None);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&p).unwrap();
+            assert_eq!(
+                json,
+                r#"{"initial_amount":10,"pour_cost":2,"evaporation_cost":1,"curve":"refill"}"#
+            );
+            assert_eq!(serde_json::from_str::<Policy>(&json).unwrap(), p);
+        }
+    }
+
+    #[test]
+    fn try_acquire_does_not_count_when_drained() {
+        let mut b = Policy::expire_within_counts(2).into_bucket::<()>();
+        assert_eq!(b.try_acquire(), Ok(()));
+        assert_eq!(b.try_acquire(), Ok(()));
+        assert_eq!(b.try_acquire(), Err(Exhausted { retry_after: None }));
+        assert_eq!(b.hit_count(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn try_acquire_caps_refill_earned_while_idle() {
+        let clock = ManualClock::new();
+        let mut b = Policy::replenishing(3, 1, 1).into_bucket_with_clock::<()>(clock.clone());
+        clock.advance(millis_duration(3_600_000));
+        let burst = (0..10).take_while(|_| b.try_acquire().is_ok()).count();
+        assert_eq!(burst, 3);
+        clock.advance(millis_duration(1_500));
+        assert_eq!(b.try_acquire(), Ok(()));
+        assert!(b.try_acquire().is_err());
+
+        let mut b = Policy::replenishing(3, 1, 1).into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 0);
+        clock.advance(millis_duration(3_600_000));
+        let served = (1..10).take_while(|&i| b.call_sync(|| i) == 0).count();
+        assert_eq!(served, 3);
+
+        let mut b = Policy::replenishing(3, 1, 1).into_bucket_with_clock(clock.clone());
+        b.call(|| async { 0 }).await;
+        clock.advance(millis_duration(3_600_000));
+        let mut served = 0;
+        for i in 1..10 {
+            if b.call(|| async move { i }).await != 0 {
+                break;
+            }
+            served += 1;
+        }
+        assert_eq!(served, 3);
+        // The refill is capped without moving the last refresh.
+        let mut b = Policy::replenishing(3, 1, 1).into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 0);
+        let refreshed = b.last_refreshed_at();
+        clock.advance(millis_duration(3_600_000));
+        assert_eq!(b.call_sync(|| 1), 0);
+        assert_eq!(b.last_refreshed_at(), refreshed);
+        assert_eq!(b.age(), millis_duration(3_600_000));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn try_acquire_retry_after_for_composite_refill() {
+        let clock = ManualClock::new();
+        let mut b = Policy::any_of(vec![
+            Policy::replenishing(1, 1, 2),
+            Policy::replenishing(1, 1, 1),
+        ])
+        .into_bucket_with_clock::<()>(clock.clone());
+        assert_eq!(b.try_acquire(), Ok(()));
+        let err = b.try_acquire().unwrap_err();
+        assert_eq!(err.retry_after, Some(millis_duration(1_000)));
+        clock.advance(millis_duration(600));
+        let err = b.try_acquire().unwrap_err();
+        assert_eq!(err.retry_after, Some(millis_duration(400)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn freshness_predicates_agree_with_call() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10)
+            .into_bucket_with_clock(clock.clone())
+            .with_jitter(0.5);
+        b.call_sync(|| 0);
+        for step in 1..=40 {
+            clock.advance(millis_duration(500));
+            let fresh = b.is_fresh();
+            assert_eq!(b.is_expired(), !fresh);
+            assert_eq!(b.call_sync(|| step) != step, fresh);
+        }
+    }
+
+    #[test]
+    fn refresh_pour_setting_changes_task_runs() {
+        for (counted, expected) in [(true, 4), (false, 3)] {
+            let mut b = Policy::expire_within_counts(3)
+                .into_bucket()
+                .count_refresh_as_pour(counted);
+            let mut runs = 0;
+            for _ in 0..10 {
+                b.call_sync(|| runs += 1);
+            }
+            assert_eq!(runs, expected, "count_refresh_as_pour({})", counted);
+        }
+    }
+
+    #[test]
+    fn single_count_serves_the_cache_once() {
+        let mut b = Policy::expire_within_counts(1).into_bucket();
+        let mut runs = 0;
+        let served: Vec<u32> = (0..5)
+            .map(|_| {
+                b.call_sync(|| {
+                    runs += 1;
+                    runs
+                })
+            })
+            .collect();
+        assert_eq!(served, vec![1, 1, 2, 2, 3]);
+        assert_eq!(runs, 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn cancelled_call_keeps_stale_value_and_counters() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10).into_bucket_with_clock(clock.clone());
+        b.call(|| async { 1 }).await;
+        b.call(|| async { 1 }).await;
+        clock.advance(millis_duration(20_000));
+        let initiate = b.last_refreshed_at();
+        tokio::select! {
+            biased;
+            _ = b.call(core::future::pending) => unreachable!(),
+            _ = core::future::ready(()) => {}
+        }
+        assert_eq!(b.cache, Some(1));
+        assert_eq!(b.hit_count(), 1);
+        assert_eq!(b.last_refreshed_at(), initiate);
+        assert_eq!(b.age(), millis_duration(20_000));
+        assert_eq!(b.stats().misses, 2);
+        assert_eq!(b.call(|| async { 2 }).await, 2);
+        assert_eq!(b.hit_count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panicking_task_leaves_bucket_untouched() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10).into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 1);
+        b.call_sync(|| 1);
+        clock.advance(millis_duration(20_000));
+        let initiate = b.last_refreshed_at();
+
+        let r = catch_unwind(AssertUnwindSafe(|| b.call_sync(|| panic!("sync"))));
+        assert!(r.is_err());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            rt.block_on(b.call(|| async { panic!("async") }))
+        }));
+        assert!(r.is_err());
+
+        assert_eq!(b.cache, Some(1));
+        assert_eq!(b.hit_count(), 1);
+        assert_eq!(b.last_refreshed_at(), initiate);
+        assert_eq!(b.call_sync(|| 2), 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn subscribers_see_refreshes_and_invalidations() {
+        let mut b = Policy::expire_within_counts(1).into_bucket();
+        let mut rx = b.subscribe();
+        assert_eq!(*rx.borrow_and_update(), None);
+        b.call(|| async { 1 }).await;
+        b.call(|| async { 2 }).await;
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Some(1));
+        b.force_refresh_with(|| async { 3 }).await;
+        assert_eq!(*rx.borrow_and_update(), Some(3));
+        let _ = b.call_try(|| async { Err::<u32, _>(()) }).await;
+        assert!(!rx.has_changed().unwrap());
+        b.invalidate();
+        assert_eq!(*rx.borrow_and_update(), None);
+        assert_eq!(b.call_sync(|| 4), 4);
+        assert_eq!(*b.subscribe().borrow(), Some(4));
+        b.refresh();
+        assert_eq!(*rx.borrow(), None);
+        assert_eq!(*b.clone().subscribe().borrow(), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn events_drop_the_oldest_for_slow_subscribers() {
+        use tokio_stream::StreamExt;
+
+        let mut b = Policy::bottom_less().into_bucket();
+        let events = b.events();
+        for _ in 0..1_000 {
+            b.call_sync(|| 1);
+        }
+        b.invalidate();
+        drop(b);
+        let received: Vec<_> = events.collect().await;
+        assert!(received.len() < 1_000);
+        assert_eq!(received.last(), Some(&CacheEvent::Invalidated));
+        assert!(received[..received.len() - 1]
+            .iter()
+            .all(|e| *e == CacheEvent::Hit));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn fractional_policy_is_fixed_point() {
+        let p = Policy::new_fractional(1.0, 0.25, 0.5).unwrap();
+        assert_eq!(p, Policy::new(1_000_000_000, 250_000_000, 500_000_000));
+        assert!(p.is_remaining(3, 0));
+        assert!(!p.is_remaining(4, 0));
+        // Half the budget evaporates in a second, and two pours are
+        // exactly the rest.
+        assert!(p.is_remaining_millis(1, 999));
+        assert!(!p.is_remaining_millis(2, 1000));
+
+        assert_eq!(
+            Policy::new_fractional(0.0, 1.0, 0.0),
+            Err(PolicyError::ZeroInitialAmount)
+        );
+        assert_eq!(
+            Policy::new_fractional(1.0, -0.5, 0.0),
+            Err(PolicyError::InvalidFraction)
+        );
+        assert_eq!(
+            Policy::new_fractional(1.0, 0.0, f64::INFINITY),
+            Err(PolicyError::Overflow)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn freshness_boundaries() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(10)
+            .with_soft_threshold(8_000)
+            .into_bucket_with_clock(clock.clone());
+        assert_eq!(b.freshness(), Freshness::Expired);
+        b.call_sync(|| 1);
+        clock.advance(millis_duration(7_999));
+        assert_eq!(b.freshness(), Freshness::Fresh);
+        // Reaching either threshold exactly counts as crossing it.
+        clock.advance(millis_duration(1));
+        assert_eq!(b.freshness(), Freshness::Stale);
+        clock.advance(millis_duration(1_999));
+        assert_eq!(b.freshness(), Freshness::Stale);
+        assert_eq!(b.call_sync(|| 2), 1);
+        clock.advance(millis_duration(1));
+        assert_eq!(b.freshness(), Freshness::Expired);
+
+        // Without a soft threshold a value is never stale.
+        let mut b = Policy::expire_within_secs(10).into_bucket_with_clock(clock.clone());
+        b.call_sync(|| 1);
+        clock.advance(millis_duration(9_999));
+        assert_eq!(b.freshness(), Freshness::Fresh);
+    }
+
+    #[test]
+    fn soft_threshold_of_composites() {
+        let p = Policy::any_of(vec![
+            Policy::expire_within_counts(10),
+            Policy::expire_within_secs(10),
+        ])
+        .with_soft_threshold(0.5);
+        assert!(p.is_soft_remaining(4, millis_duration(4_999)));
+        assert!(!p.is_soft_remaining(5, millis_duration(0)));
+        assert!(!p.is_soft_remaining(0, millis_duration(5_000)));
+        // The fraction is taken of each child, and amounts are capped.
+        assert_eq!(
+            Policy::expire_within_counts(3).with_soft_threshold(1.0),
+            Policy::expire_within_counts(3).with_soft_threshold(u64::MAX)
+        );
+    }
+
+    #[tokio::test]
+    async fn call_or_else_does_not_cache_the_fallback() {
+        let mut b = Policy::pierced().into_bucket();
+        let down = || async { Err::<u32, _>("unavailable") };
+        assert_eq!(b.call_or_default(down).await, 0);
+        assert_eq!(b.call_or_else(down, |_| 7).await, 7);
+        assert_eq!(b.cached(), None);
+        assert_eq!(b.stats().failures, 2);
+
+        assert_eq!(b.call_or_default(|| async { Ok::<_, &str>(1) }).await, 1);
+        // The task runs again, and the stale value is served when it fails.
+        assert_eq!(b.call_or_else(down, |_| 7).await, 1);
+        assert_eq!(b.stats().failures, 3);
+    }
+
+    #[tokio::test]
+    async fn call_with_fallback_attaches_the_secondary_error() {
+        use core::error::Error as _;
+        use core::fmt;
+
+        #[derive(Debug)]
+        struct Down(&'static str);
+        impl fmt::Display for Down {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} is down", self.0)
+            }
         }
+        impl core::error::Error for Down {}
+
+        let mut b = Policy::pierced().into_bucket();
+        let primary = || async { Err::<u32, _>(Down("primary")) };
+        let secondary = || async { Err::<u32, _>(Down("secondary")) };
+        let Err(Error::Task(e)) = b.call_with_fallback(primary, secondary).await else {
+            panic!("both tasks failed");
+        };
+        assert_eq!(e.to_string(), "primary is down");
+        assert_eq!(e.source().unwrap().to_string(), "secondary is down");
+        assert_eq!(b.stats().failures, 1);
+        assert_eq!(b.cached(), None);
+
+        let r = b
+            .call_with_fallback(|| async { Ok::<_, Down>(1) }, secondary)
+            .await;
+        assert_eq!(r.unwrap(), (1, CacheStatus::Primary));
+        assert_eq!(b.cached(), Some(&1));
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn call_with_fallback_reports_a_graced_value_as_stale() {
+        let clock = ManualClock::new();
+        let mut b = Policy::expire_within_secs(1)
+            .into_bucket_with_clock(clock.clone())
+            .with_grace(millis_duration(10_000))
+            .with_error_policy(Policy::expire_within_counts(1));
+        b.call_sync(|| 1);
+        clock.advance(millis_duration(2_000));
+        let primary = || async { Err::<u32, _>("primary is down") };
+        let secondary = || async { Err::<u32, _>("secondary is down") };
+        let r = b.call_with_fallback(primary, secondary).await;
+        assert_eq!(r, Ok((1, CacheStatus::Stale)));
+        // Negative cached now, and still within the grace period.
+        let r = b.call_with_fallback(primary, secondary).await;
+        assert_eq!(r, Ok((1, CacheStatus::Stale)));
+        assert_eq!(b.stats().failures, 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn breaker_opens_on_consecutive_failures_only() {
+        let clock = ManualClock::new();
+        let cooldown = millis_duration(1_000);
+        let mut b = Policy::pierced()
+            .into_bucket_with_clock(clock.clone())
+            .with_breaker(BreakerConfig::new(2, cooldown));
+        let down = || async { Err::<u32, _>("unavailable") };
+        let up = || async { Ok::<_, &str>(1) };
+        assert_eq!(b.call_try(down).await, Err(Error::Task("unavailable")));
+        assert_eq!(b.call_try(up).await, Ok(1));
+        assert!(b.call_try(down).await.is_err());
+        assert_eq!(b.breaker_state(), BreakerState::Closed);
+        assert!(b.call_try(down).await.is_err());
+        assert_eq!(b.breaker_state(), BreakerState::Open);
+        assert_eq!(b.stats().failures, 3);
+        assert_eq!(b.call_try(down).await, Ok(1));
+
+        // A failed probe opens the breaker again for a full cooldown.
+        clock.advance(millis_duration(999));
+        assert_eq!(b.breaker_state(), BreakerState::Open);
+        clock.advance(millis_duration(1));
+        assert!(b.call_try(down).await.is_err());
+        assert_eq!(b.stats().failures, 4);
+        assert_eq!(b.breaker_state(), BreakerState::Open);
+
+        // Without a cached value an open breaker fails fast.
+        b.invalidate();
+        assert_eq!(b.call_try(up).await, Err(Error::NegativeCached));
+        assert_eq!(b.stats().failures, 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn take_does_not_run_the_evict_callback() {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut b = Policy::pierced().into_bucket().on_evict({
+            let evicted = evicted.clone();
+            move |v| evicted.lock().unwrap().push(v)
+        });
+        b.call_sync(|| 1);
+        assert_eq!(b.take(), Some(1));
+        b.call_sync(|| 2);
+        b.call_sync(|| 3);
+        assert_eq!(b.into_value(), Some(3));
+        assert_eq!(*evicted.lock().unwrap(), vec![2]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn replace_restarts_the_accounting() {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut b = Policy::expire_within_counts(2)
+            .into_bucket_with(1)
+            .on_evict({
+                let evicted = evicted.clone();
+                move |v| evicted.lock().unwrap().push(v)
+            });
+        assert_eq!(b.call_sync(|| 0), 1);
+        assert_eq!(b.call_sync(|| 0), 1);
+        assert_eq!(b.replace(2), Some(1));
+        assert_eq!(b.call_sync(|| 0), 2);
+        assert_eq!(b.call_sync(|| 0), 2);
+        assert_eq!(b.call_sync(|| 3), 3);
+        assert_eq!(*evicted.lock().unwrap(), vec![2]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_serializes_without_the_value() {
+        struct Pool;
+
+        let mut b = Policy::any_of(vec![
+            Policy::expire_within_counts(3),
+            Policy::expire_within_secs(60),
+        ])
+        .into_bucket();
+        b.call_sync(|| Arc::new(Pool));
+        b.call_sync(|| Arc::new(Pool));
+        b.call_sync(|| Arc::new(Pool));
+        let json = serde_json::to_string(&b.state()).unwrap();
+
+        let mut restarted = Policy::bottom_less().into_bucket::<Arc<Pool>>();
+        restarted.restore_state(serde_json::from_str(&json).unwrap());
+        assert!(restarted.cached().is_none());
+        assert_eq!(restarted.hit_count(), 2);
+        assert_eq!(restarted.policy, b.policy);
+        let first = restarted.call_sync(|| Arc::new(Pool));
+        let second = restarted.call_sync(|| Arc::new(Pool));
+        let third = restarted.call_sync(|| Arc::new(Pool));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&second, &third));
     }
 }
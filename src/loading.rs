@@ -0,0 +1,250 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::{Bucket, Expiry, Policy, SharedBucket};
+
+type Load<T> = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = T> + Send>> + Send + Sync>;
+
+fn boxed<T, F, Fut>(load: F) -> Load<T>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    Arc::new(move || Box::pin(load()))
+}
+
+/// A [`Bucket`] that owns the task it refreshes with, made with
+/// [`Bucket::with_loader`] or [`Policy::into_loading_bucket`].
+///
+/// Every call site gets the same value from the same loader, so two of them
+/// cannot disagree on how the value is computed.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut config = Policy::expire_within_counts(1).into_loading_bucket(|| async { 42 });
+/// assert_eq!(config.get().await, 42);
+/// assert_eq!(config.get().await, 42);
+/// # }
+/// ```
+pub struct LoadingBucket<T, P = Policy> {
+    bucket: Bucket<T, P>,
+    load: Load<T>,
+}
+
+impl<T, P> fmt::Debug for LoadingBucket<T, P>
+where
+    T: fmt::Debug,
+    P: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadingBucket")
+            .field("bucket", &self.bucket)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, P> LoadingBucket<T, P> {
+    pub(crate) fn new<F, Fut>(bucket: Bucket<T, P>, load: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        LoadingBucket {
+            bucket,
+            load: boxed(load),
+        }
+    }
+
+    pub fn bucket(&self) -> &Bucket<T, P> {
+        &self.bucket
+    }
+
+    /// Runs the loader once and stores its value, so that the first
+    /// [`LoadingBucket::get`] is already a hit.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let config = Policy::expire_within_secs(60)
+    ///     .into_loading_bucket(|| async { 42 })
+    ///     .primed()
+    ///     .await;
+    /// assert_eq!(config.bucket().cached(), Some(&42));
+    /// # }
+    /// ```
+    pub async fn primed(mut self) -> Self
+    where
+        P: Expiry,
+    {
+        let entry = (self.load)().await;
+        self.bucket.put(entry);
+        self
+    }
+
+    /// Shares the bucket together with its loader.
+    pub fn into_shared(self) -> SharedLoadingBucket<T, P>
+    where
+        P: Expiry,
+    {
+        SharedLoadingBucket {
+            bucket: self.bucket.into_shared(),
+            load: self.load,
+        }
+    }
+}
+
+impl<T, P> LoadingBucket<T, P>
+where
+    T: Clone,
+    P: Expiry,
+{
+    /// See [`Bucket::call`].
+    pub async fn get(&mut self) -> T {
+        let load = &self.load;
+        self.bucket.call(|| load()).await
+    }
+
+    /// See [`Bucket::force_refresh_with`].
+    pub async fn force_refresh(&mut self) -> T {
+        let load = &self.load;
+        self.bucket.force_refresh_with(|| load()).await
+    }
+}
+
+/// A [`SharedBucket`] that owns the task it refreshes with, made with
+/// [`LoadingBucket::into_shared`].
+///
+/// Clones share the bucket and the loader, so one can be kept in
+/// application state and read from every handler.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let keys = Policy::expire_within_secs(3600)
+///     .into_loading_bucket(|| async { vec!["key-1"] })
+///     .into_shared();
+/// let handler = keys.clone();
+/// assert_eq!(handler.get().await, ["key-1"]);
+/// # }
+/// ```
+pub struct SharedLoadingBucket<T, P = Policy> {
+    bucket: SharedBucket<T, P>,
+    load: Load<T>,
+}
+
+impl<T, P> Clone for SharedLoadingBucket<T, P> {
+    fn clone(&self) -> Self {
+        SharedLoadingBucket {
+            bucket: self.bucket.clone(),
+            load: self.load.clone(),
+        }
+    }
+}
+
+impl<T, P> fmt::Debug for SharedLoadingBucket<T, P>
+where
+    T: fmt::Debug,
+    P: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedLoadingBucket")
+            .field("bucket", &self.bucket)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, P> SharedLoadingBucket<T, P> {
+    pub fn bucket(&self) -> &SharedBucket<T, P> {
+        &self.bucket
+    }
+}
+
+impl<T, P> SharedLoadingBucket<T, P>
+where
+    T: Clone,
+    P: Expiry,
+{
+    /// See [`SharedBucket::call`].
+    pub async fn get(&self) -> T {
+        self.bucket.call(|| (self.load)()).await
+    }
+
+    /// See [`SharedBucket::force_refresh_with`].
+    pub async fn force_refresh(&self) -> T {
+        self.bucket.force_refresh_with(|| (self.load)()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use crate::Policy;
+
+    fn counter() -> (
+        Arc<AtomicU32>,
+        impl Fn() -> std::future::Ready<u32> + Send + Sync,
+    ) {
+        let runs = Arc::new(AtomicU32::new(0));
+        let load = {
+            let runs = runs.clone();
+            move || std::future::ready(runs.fetch_add(1, Ordering::SeqCst) + 1)
+        };
+        (runs, load)
+    }
+
+    #[tokio::test]
+    async fn loader_runs_once_per_refresh() {
+        let (runs, load) = counter();
+        let mut b = Policy::expire_within_counts(2).into_loading_bucket(load);
+        for _ in 0..3 {
+            assert_eq!(b.get().await, 1);
+        }
+        assert_eq!(b.get().await, 2);
+        assert_eq!(b.force_refresh().await, 3);
+        assert_eq!(b.get().await, 3);
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(b.bucket().hit_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn primed_bucket_does_not_load_again() {
+        let (runs, load) = counter();
+        let mut b = Policy::expire_within_counts(2)
+            .into_loading_bucket(load)
+            .primed()
+            .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(b.get().await, 1);
+        assert_eq!(b.get().await, 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(b.get().await, 2);
+    }
+
+    #[tokio::test]
+    async fn shared_clones_share_value_and_loader() {
+        let (runs, load) = counter();
+        let a = Policy::bottom_less()
+            .into_loading_bucket(load)
+            .into_shared();
+        let b = a.clone();
+        assert_eq!(a.get().await, 1);
+        assert_eq!(b.get().await, 1);
+        assert_eq!(b.force_refresh().await, 2);
+        assert_eq!(a.get().await, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,718 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::evict::KeyedEvict;
+use crate::{Bucket, Duration, Expiry, Policy};
+
+/// A [`Bucket`] per key, all created from the same policy.
+///
+/// Buckets are created lazily on the first call for their key, and each
+/// keeps its own accounting. With [`BucketMap::with_capacity`] the least
+/// recently called key is evicted once the map is full.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut m = Policy::expire_within_counts(10).into_bucket_map();
+/// let load = |key: &u32| {
+///     let value = key * 10;
+///     async move { value }
+/// };
+/// assert_eq!(m.call(1, load).await, 10);
+/// assert_eq!(m.call(2, load).await, 20);
+/// assert_eq!(m.len(), 2);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BucketMap<K, T, P = Policy> {
+    policy: P,
+    buckets: HashMap<K, Slot<T, P>>,
+    /// Keys by the tick of their last call, oldest first.
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+    capacity: Option<usize>,
+    evict: Option<KeyedEvict<K, T>>,
+    weigher: Option<Weigher<K, T>>,
+    /// The total weight of the cached values, as last weighed.
+    weight: u64,
+}
+
+type Weigh<K, T> = Arc<dyn Fn(&K, &T) -> u64 + Send + Sync>;
+
+struct Weigher<K, T> {
+    max_weight: u64,
+    f: Weigh<K, T>,
+}
+
+impl<K, T> Clone for Weigher<K, T> {
+    fn clone(&self) -> Self {
+        Weigher {
+            max_weight: self.max_weight,
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<K, T> fmt::Debug for Weigher<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Weigher")
+            .field("max_weight", &self.max_weight)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state of one key's bucket, from [`BucketMap::iter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryInfo<'a, T> {
+    /// The cached value, fresh or not.
+    pub value: Option<&'a T>,
+    /// See [`Bucket::is_fresh`].
+    pub is_fresh: bool,
+    /// See [`Bucket::hit_count`].
+    pub hit_count: u64,
+    /// See [`Bucket::age`].
+    pub age: Duration,
+}
+
+/// The outcome of [`BucketMap::get_many`].
+#[derive(Debug, Clone)]
+pub struct Batch<K, T> {
+    /// The value of every key that was fresh or loaded.
+    pub values: HashMap<K, T>,
+    /// The keys that were missing or expired and that the loader did not
+    /// return, in the order they were asked for.
+    pub missing: Vec<K>,
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T, P> {
+    bucket: Bucket<T, P>,
+    used: u64,
+    weight: u64,
+}
+
+impl<K, T, P> BucketMap<K, T, P>
+where
+    K: Eq + Hash,
+    P: Expiry + Clone,
+{
+    pub fn new(policy: P) -> Self {
+        BucketMap {
+            policy,
+            buckets: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            capacity: None,
+            evict: None,
+            weigher: None,
+            weight: 0,
+        }
+    }
+
+    /// Keeps at most `max_entries` keys, evicting the least recently called
+    /// one to make room for a new key.
+    ///
+    /// # Panics
+    /// Panics if `max_entries` is zero.
+    pub fn with_capacity(mut self, max_entries: usize) -> Self {
+        assert!(max_entries > 0, "capacity must be positive");
+        self.capacity = Some(max_entries);
+        self.evict_down_to(max_entries);
+        self
+    }
+
+    /// Keeps the total weight of the cached values at most `max_weight`,
+    /// evicting the least recently called keys whenever a stored value
+    /// pushes it over.
+    ///
+    /// `weigher` is called once for every value a bucket stores. A value
+    /// heavier than `max_weight` on its own is evicted as well.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut m = Policy::bottom_less()
+    ///     .into_bucket_map()
+    ///     .with_weigher(10, |_key: &u32, value: &Vec<u8>| value.len() as u64);
+    /// m.call(1, |_| async { vec![0; 6] }).await;
+    /// m.call(2, |_| async { vec![0; 6] }).await;
+    /// assert_eq!(m.len(), 1);
+    /// assert_eq!(m.weight(), 6);
+    /// # }
+    /// ```
+    pub fn with_weigher(
+        mut self,
+        max_weight: u64,
+        weigher: impl Fn(&K, &T) -> u64 + Send + Sync + 'static,
+    ) -> Self
+    where
+        K: Clone,
+    {
+        self.weigher = Some(Weigher {
+            max_weight,
+            f: Arc::new(weigher),
+        });
+        let keys: Vec<K> = self.buckets.keys().cloned().collect();
+        for key in keys {
+            self.reweigh(&key);
+        }
+        self
+    }
+
+    /// Runs `f` with every cached value dropped by any of the buckets, along
+    /// with its key. See [`Bucket::on_evict`].
+    ///
+    /// This includes the values of keys evicted for capacity and of
+    /// [`BucketMap::clear`].
+    pub fn on_evict(mut self, f: impl FnMut(K, T) + Send + 'static) -> Self
+    where
+        K: Clone + Send + 'static,
+        T: 'static,
+    {
+        let evict = KeyedEvict::new(f);
+        for (key, slot) in self.buckets.iter_mut() {
+            slot.bucket.evict = Some(evict.bind(key));
+        }
+        self.evict = Some(evict);
+        self
+    }
+
+    /// Returns the number of keys that have a bucket.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Returns the total weight of the cached values, which is zero unless
+    /// a weigher is set with [`BucketMap::with_weigher`].
+    pub fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    /// Iterates over every key with the state of its bucket, in no
+    /// particular order.
+    ///
+    /// Nothing is poured and no task is run.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut m = Policy::expire_within_counts(1).into_bucket_map();
+    /// m.call("a", |_| async { 1 }).await;
+    /// for (key, entry) in m.iter() {
+    ///     assert_eq!((*key, entry.value, entry.is_fresh), ("a", Some(&1), true));
+    /// }
+    /// # }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&K, EntryInfo<'_, T>)> {
+        self.buckets.iter().map(|(key, slot)| {
+            let bucket = &slot.bucket;
+            let info = EntryInfo {
+                value: bucket.cached(),
+                is_fresh: bucket.is_fresh(),
+                hit_count: bucket.hit_count(),
+                age: bucket.age(),
+            };
+            (key, info)
+        })
+    }
+
+    /// Iterates over the values that the next call would be served, in no
+    /// particular order. See [`Bucket::fresh`].
+    pub fn values_fresh(&self) -> impl Iterator<Item = &T> {
+        self.buckets.values().filter_map(|slot| slot.bucket.fresh())
+    }
+
+    /// See [`Bucket::invalidate`].
+    pub fn invalidate(&mut self, key: &K) {
+        if let Some(slot) = self.buckets.get_mut(key) {
+            slot.bucket.invalidate();
+            self.weight -= std::mem::take(&mut slot.weight);
+        }
+    }
+
+    /// Drops the buckets whose policy is exhausted, returning how many were
+    /// dropped.
+    ///
+    /// Their values go to the [`BucketMap::on_evict`] callback.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut m = Policy::expire_within_counts(1).into_bucket_map();
+    /// m.call(1, |_| async { 1 }).await;
+    /// m.call(2, |_| async { 2 }).await;
+    /// m.call(2, |_| async { 2 }).await;
+    /// assert_eq!(m.purge_expired(), 1);
+    /// assert_eq!(m.len(), 1);
+    /// # }
+    /// ```
+    pub fn purge_expired(&mut self) -> usize
+    where
+        K: Clone,
+    {
+        let before = self.len();
+        self.retain(|_, bucket| bucket.is_remaining_now());
+        before - self.len()
+    }
+
+    /// Keeps only the buckets for which `f` returns `true`, like
+    /// [`HashMap::retain`].
+    ///
+    /// The values of dropped buckets go to the [`BucketMap::on_evict`]
+    /// callback.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &Bucket<T, P>) -> bool)
+    where
+        K: Clone,
+    {
+        let dropped: Vec<K> = self
+            .buckets
+            .iter()
+            .filter(|(key, slot)| !f(key, &slot.bucket))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in dropped {
+            if let Some(slot) = self.buckets.remove(&key) {
+                self.recency.remove(&slot.used);
+                self.drop_slot(key, slot);
+            }
+        }
+    }
+
+    /// Drops every bucket.
+    pub fn clear(&mut self) {
+        for (key, slot) in std::mem::take(&mut self.buckets) {
+            self.drop_slot(key, slot);
+        }
+        self.recency.clear();
+    }
+
+    /// Calls the bucket of `key`, creating it first if needed.
+    ///
+    /// `task` is given the key, so one loader can serve every key.
+    pub async fn call<F, Fut>(&mut self, key: K, task: F) -> T
+    where
+        K: Clone,
+        T: Clone,
+        F: FnOnce(&K) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let bucket = self.touch(&key);
+        let refreshes = bucket.stats.refreshes;
+        let entry = bucket.call_with(&key, task).await;
+        if self.buckets[&key].bucket.stats.refreshes != refreshes {
+            self.reweigh(&key);
+        }
+        entry
+    }
+
+    /// Stores `value` for `key` as a freshly refreshed entry, creating the
+    /// bucket first if needed, without running a task.
+    ///
+    /// The pour accounting of the bucket restarts, and the old value goes
+    /// to the [`BucketMap::on_evict`] callback.
+    ///
+    /// # Example
+    /// ```
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut m = Policy::expire_within_counts(1).into_bucket_map();
+    /// m.insert("a", 1);
+    /// assert_eq!(m.call("a", |_| async { 2 }).await, 1);
+    /// # }
+    /// ```
+    pub fn insert(&mut self, key: K, value: T)
+    where
+        K: Clone,
+    {
+        self.touch(&key).put(value);
+        self.reweigh(&key);
+    }
+
+    /// Looks up many keys at once, loading only those that are missing or
+    /// expired with a single call to `loader`.
+    ///
+    /// Fresh keys are served from their buckets and count as a pour, as
+    /// with [`BucketMap::call`]. `loader` is not called if every key is
+    /// fresh. Keys it does not return keep their stale state and are listed
+    /// in [`Batch::missing`]; values for keys that were not asked for are
+    /// ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashMap;
+    /// use uchimizu::Policy;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut m = Policy::expire_within_counts(10).into_bucket_map();
+    /// m.call(1, |_| async { 10 }).await;
+    /// let batch = m
+    ///     .get_many([1, 2, 3], |keys| async move {
+    ///         assert_eq!(keys, [2, 3]);
+    ///         HashMap::from([(2, 20)])
+    ///     })
+    ///     .await;
+    /// assert_eq!(batch.values, HashMap::from([(1, 10), (2, 20)]));
+    /// assert_eq!(batch.missing, [3]);
+    /// # }
+    /// ```
+    pub async fn get_many<F, Fut>(
+        &mut self,
+        keys: impl IntoIterator<Item = K>,
+        loader: F,
+    ) -> Batch<K, T>
+    where
+        K: Clone,
+        T: Clone,
+        F: FnOnce(Vec<K>) -> Fut,
+        Fut: Future<Output = HashMap<K, T>>,
+    {
+        let mut values = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut pending = Vec::new();
+        for key in keys {
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            let bucket = self.touch(&key);
+            match bucket.hit() {
+                Some(entry) => {
+                    values.insert(key, entry);
+                }
+                None => {
+                    let exhausted = bucket.miss();
+                    pending.push((key, exhausted));
+                }
+            }
+        }
+        let mut missing = Vec::new();
+        if pending.is_empty() {
+            return Batch { values, missing };
+        }
+        let mut loaded = loader(pending.iter().map(|(key, _)| key.clone()).collect()).await;
+        for (key, exhausted) in pending {
+            match loaded.remove(&key) {
+                Some(entry) => {
+                    // The bucket may have been evicted for capacity meanwhile.
+                    let entry = self.touch(&key).complete(entry, exhausted);
+                    self.reweigh(&key);
+                    values.insert(key, entry);
+                }
+                None => missing.push(key),
+            }
+        }
+        Batch { values, missing }
+    }
+
+    #[cfg(feature = "http")]
+    pub(crate) fn bucket(&self, key: &K) -> Option<&Bucket<T, P>> {
+        self.buckets.get(key).map(|slot| &slot.bucket)
+    }
+
+    /// Marks `key` as the most recently used and returns its bucket.
+    pub(crate) fn touch(&mut self, key: &K) -> &mut Bucket<T, P>
+    where
+        K: Clone,
+    {
+        self.tick += 1;
+        if let Some(slot) = self.buckets.get(key) {
+            self.recency.remove(&slot.used);
+        } else if let Some(capacity) = self.capacity {
+            self.evict_down_to(capacity - 1);
+        }
+        self.recency.insert(self.tick, key.clone());
+        let slot = self.buckets.entry(key.clone()).or_insert_with(|| {
+            let mut bucket = Bucket::new(self.policy.clone());
+            bucket.evict = self.evict.as_ref().map(|evict| evict.bind(key));
+            Slot {
+                bucket,
+                used: 0,
+                weight: 0,
+            }
+        });
+        slot.used = self.tick;
+        &mut slot.bucket
+    }
+
+    fn evict_down_to(&mut self, len: usize) {
+        while self.buckets.len() > len {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(slot) = self.buckets.remove(&key) {
+                self.drop_slot(key, slot);
+            }
+        }
+    }
+
+    /// Weighs the value just stored for `key`, then evicts the least
+    /// recently called keys until the map is within its maximum weight.
+    pub(crate) fn reweigh(&mut self, key: &K) {
+        let Some(weigher) = &self.weigher else {
+            return;
+        };
+        let Some(slot) = self.buckets.get_mut(key) else {
+            return;
+        };
+        let weight = match &slot.bucket.cache {
+            Some(value) => (weigher.f)(key, value),
+            None => 0,
+        };
+        self.weight = (self.weight - slot.weight).saturating_add(weight);
+        slot.weight = weight;
+        let max_weight = weigher.max_weight;
+        while self.weight > max_weight {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(slot) = self.buckets.remove(&key) {
+                self.drop_slot(key, slot);
+            }
+        }
+    }
+
+    fn drop_slot(&mut self, key: K, slot: Slot<T, P>) {
+        self.weight -= slot.weight;
+        self.evict_slot(key, slot);
+    }
+
+    fn evict_slot(&self, key: K, slot: Slot<T, P>) {
+        if let (Some(value), Some(evict)) = (slot.bucket.cache, &self.evict) {
+            evict.call(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn keys_have_independent_buckets() {
+        let mut m = Policy::expire_within_counts(2).into_bucket_map();
+        let runs = Cell::new(0);
+        let load = |key: &&str| {
+            runs.set(runs.get() + 1);
+            let value = format!("{}{}", key, runs.get());
+            async move { value }
+        };
+        assert_eq!(m.call("a", load).await, "a1");
+        assert_eq!(m.call("a", load).await, "a1");
+        assert_eq!(m.call("b", load).await, "b2");
+        assert_eq!(m.call("a", load).await, "a1");
+        assert_eq!(m.call("b", load).await, "b2");
+        assert_eq!(m.call("a", load).await, "a3");
+        assert_eq!(m.len(), 2);
+
+        m.invalidate(&"b");
+        assert_eq!(m.call("b", load).await, "b4");
+        m.clear();
+        assert!(m.is_empty());
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_called_key() {
+        let mut m = Policy::bottom_less().into_bucket_map().with_capacity(3);
+        let runs = Cell::new(0);
+        let load = |key: &u32| {
+            runs.set(runs.get() + 1);
+            let value = *key;
+            async move { value }
+        };
+        for key in [1, 2, 3] {
+            m.call(key, load).await;
+        }
+        m.call(1, load).await;
+        m.call(4, load).await;
+        assert_eq!(m.len(), 3);
+        assert_eq!(runs.get(), 4);
+
+        for key in [1, 3, 4] {
+            assert_eq!(m.call(key, load).await, key);
+        }
+        assert_eq!(runs.get(), 4);
+        assert_eq!(m.call(2, load).await, 2);
+        assert_eq!(runs.get(), 5);
+        assert_eq!(m.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn on_evict_receives_key_and_old_value() {
+        let evicted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut m = Policy::expire_within_counts(1)
+            .into_bucket_map()
+            .with_capacity(2)
+            .on_evict({
+                let evicted = evicted.clone();
+                move |key, value| evicted.lock().unwrap().push((key, value))
+            });
+        let runs = Cell::new(0);
+        let load = |key: &u32| {
+            runs.set(runs.get() + 1);
+            let value = key * 100 + runs.get();
+            async move { value }
+        };
+        m.call(1, load).await;
+        m.call(1, load).await;
+        m.call(1, load).await;
+        assert_eq!(*evicted.lock().unwrap(), vec![(1, 101)]);
+        m.call(2, load).await;
+        m.call(3, load).await;
+        assert_eq!(*evicted.lock().unwrap(), vec![(1, 101), (1, 102)]);
+        m.clear();
+        let mut evicted = evicted.lock().unwrap().clone();
+        evicted[2..].sort();
+        assert_eq!(evicted, vec![(1, 101), (1, 102), (2, 203), (3, 304)]);
+    }
+
+    #[tokio::test]
+    async fn get_many_loads_only_missing_keys() {
+        let mut m = Policy::expire_within_counts(1).into_bucket_map();
+        m.call(1, |_| async { 10 }).await;
+        m.call(2, |_| async { 20 }).await;
+        m.call(2, |_| async { 20 }).await;
+        let asked = Cell::new(Vec::new());
+        let batch = m
+            .get_many([1, 2, 3, 4, 3], |keys| {
+                asked.set(keys);
+                async { HashMap::from([(2, 21), (3, 30), (5, 50)]) }
+            })
+            .await;
+        assert_eq!(asked.take(), vec![2, 3, 4]);
+        assert_eq!(batch.values, HashMap::from([(1, 10), (2, 21), (3, 30)]));
+        assert_eq!(batch.missing, vec![4]);
+        assert_eq!(m.len(), 4);
+
+        let batch = m.get_many([2, 3], |_| async { unreachable!() }).await;
+        assert_eq!(batch.values, HashMap::from([(2, 21), (3, 30)]));
+    }
+
+    #[tokio::test]
+    async fn iter_reports_state_without_pouring() {
+        let mut m = Policy::expire_within_counts(1).into_bucket_map();
+        for key in ["a", "b", "b", "c"] {
+            m.call(key, |key| {
+                let value = key.len();
+                async move { value }
+            })
+            .await;
+        }
+        m.invalidate(&"c");
+        for _ in 0..2 {
+            let mut entries: Vec<_> = m
+                .iter()
+                .map(|(key, e)| (*key, e.value.copied(), e.is_fresh, e.hit_count))
+                .collect();
+            entries.sort();
+            assert_eq!(
+                entries,
+                vec![
+                    ("a", Some(1), true, 0),
+                    ("b", Some(1), false, 1),
+                    ("c", None, false, 0),
+                ]
+            );
+        }
+        assert_eq!(m.values_fresh().collect::<Vec<_>>(), vec![&1]);
+    }
+
+    #[tokio::test]
+    async fn retain_and_purge_evict_dropped_values() {
+        let evicted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut m = Policy::expire_within_counts(1)
+            .into_bucket_map()
+            .with_capacity(3)
+            .on_evict({
+                let evicted = evicted.clone();
+                move |key, value| evicted.lock().unwrap().push((key, value))
+            });
+        let load = |key: &u32| {
+            let value = *key;
+            async move { value }
+        };
+        for key in [1, 2, 2, 3, 3] {
+            m.call(key, load).await;
+        }
+        assert_eq!(m.purge_expired(), 2);
+        assert_eq!(m.purge_expired(), 0);
+        let mut dropped = evicted.lock().unwrap().clone();
+        dropped.sort();
+        assert_eq!(dropped, vec![(2, 2), (3, 3)]);
+
+        m.retain(|key, _| *key != 1);
+        assert!(m.is_empty());
+        assert_eq!(evicted.lock().unwrap().len(), 3);
+
+        // Purged keys no longer count towards the capacity.
+        for key in [4, 5, 6] {
+            m.call(key, load).await;
+        }
+        assert_eq!(m.len(), 3);
+        assert_eq!(evicted.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn weigher_keeps_total_weight_under_the_cap() {
+        let evicted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut m = Policy::expire_within_counts(1)
+            .into_bucket_map()
+            .with_weigher(100, |_: &u32, value: &u64| *value)
+            .on_evict({
+                let evicted = evicted.clone();
+                move |key, _| evicted.lock().unwrap().push(key)
+            });
+        for (key, weight) in [(1, 40), (2, 40), (3, 40), (1, 10), (4, 90), (5, 150)] {
+            m.call(key, |_| async move { weight }).await;
+            assert!(m.weight() <= 100, "{} after key {}", m.weight(), key);
+        }
+        assert_eq!(*evicted.lock().unwrap(), vec![1, 2, 3, 1, 4, 5]);
+        assert_eq!(m.weight(), 0);
+
+        m.call(6, |_| async { 30 }).await;
+        m.call(6, |_| async { 60 }).await;
+        assert_eq!(m.weight(), 30);
+        m.call(6, |_| async { 60 }).await;
+        assert_eq!(m.weight(), 60);
+        m.invalidate(&6);
+        assert_eq!(m.weight(), 0);
+    }
+
+    #[tokio::test]
+    async fn insert_replaces_without_running_the_task() {
+        let evicted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut m = Policy::expire_within_counts(1).into_bucket_map().on_evict({
+            let evicted = evicted.clone();
+            move |key, value| evicted.lock().unwrap().push((key, value))
+        });
+        m.call(1, |_| async { 10 }).await;
+        m.call(1, |_| async { 11 }).await;
+        m.insert(1, 12);
+        m.insert(2, 20);
+        assert_eq!(m.call(1, |_| async { 13 }).await, 12);
+        assert_eq!(m.call(2, |_| async { 21 }).await, 20);
+        assert_eq!(m.call(2, |_| async { 22 }).await, 22);
+        assert_eq!(*evicted.lock().unwrap(), vec![(1, 10), (2, 20)]);
+    }
+}
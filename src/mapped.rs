@@ -0,0 +1,111 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::{Expiry, Policy, SharedBucket, Task};
+
+/// A projection of a [`SharedBucket`], made with [`SharedBucket::map`].
+///
+/// Calling a view calls the source bucket and applies the projection to its
+/// value. Views over the same source share its freshness state, so however
+/// many views there are, an expired value is refreshed once.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let source = Policy::expire_within_counts(10).into_shared_bucket();
+/// let token = source.map(|(token, _): &(String, u64)| token.clone());
+/// let expiry = source.map(|(_, expiry): &(String, u64)| *expiry);
+///
+/// let fetch = || async { (String::from("secret"), 3600) };
+/// assert_eq!(token.call(fetch).await, "secret");
+/// assert_eq!(expiry.call(|| async { unreachable!() }).await, 3600);
+/// # }
+/// ```
+pub struct MappedBucket<T, U, P = Policy> {
+    source: SharedBucket<T, P>,
+    f: Arc<dyn Fn(&T) -> U + Send + Sync>,
+}
+
+impl<T, U, P> Clone for MappedBucket<T, U, P> {
+    fn clone(&self) -> Self {
+        MappedBucket {
+            source: self.source.clone(),
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<T, U, P> fmt::Debug for MappedBucket<T, U, P>
+where
+    T: fmt::Debug,
+    P: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedBucket")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, U, P> MappedBucket<T, U, P> {
+    pub(crate) fn new(
+        source: SharedBucket<T, P>,
+        f: impl Fn(&T) -> U + Send + Sync + 'static,
+    ) -> Self {
+        MappedBucket {
+            source,
+            f: Arc::new(f),
+        }
+    }
+
+    /// Returns the bucket this is a view of.
+    pub fn source(&self) -> &SharedBucket<T, P> {
+        &self.source
+    }
+}
+
+impl<T, U, P> MappedBucket<T, U, P>
+where
+    T: Clone,
+    P: Expiry,
+{
+    /// See [`SharedBucket::call`].
+    pub async fn call<F, Fut>(&self, task: F) -> U
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        let entry = self.source.call(task).await;
+        (self.f)(&entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::Policy;
+
+    #[tokio::test]
+    async fn views_share_a_single_refresh() {
+        let refreshes = AtomicUsize::new(0);
+        let fetch = || async {
+            let n = refreshes.fetch_add(1, Ordering::SeqCst) as u32;
+            (n, n * 10)
+        };
+        let source = Policy::expire_within_counts(3).into_bucket();
+        let first = source.map(|(a, _): &(u32, u32)| *a);
+        let second = first.source().map(|(_, b): &(u32, u32)| *b);
+        for _ in 0..2 {
+            assert_eq!(first.call(fetch).await, 0);
+            assert_eq!(second.call(fetch).await, 0);
+        }
+        assert_eq!(first.call(fetch).await, 1);
+        assert_eq!(second.call(fetch).await, 10);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 2);
+    }
+}
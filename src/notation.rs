@@ -0,0 +1,324 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::{Curve, Linear, ParsePolicyError, Policy, Rule};
+
+/// Writes the policy in the notation read by [`Policy::from_str`].
+///
+/// The presets are written by name, anything else by its parameters. A
+/// policy with a custom curve is written as `custom`, which does not parse
+/// back.
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.rule {
+            Rule::Linear(l) => l.fmt(f),
+            Rule::AnyOf { any_of } => write_list(f, "any", any_of),
+            Rule::AllOf { all_of } => write_list(f, "all", all_of),
+        }
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_budget(f)?;
+        match (&self.curve, self.soft_threshold) {
+            (Curve::Custom(_), _) | (_, None) => Ok(()),
+            (_, Some(threshold)) => write!(f, "~{}", threshold),
+        }
+    }
+}
+
+impl Linear {
+    fn fmt_budget(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Linear {
+            initial_amount,
+            pour_cost,
+            evaporation_cost,
+            ..
+        } = self;
+        match (&self.curve, initial_amount, pour_cost, evaporation_cost) {
+            (Curve::Custom(_), _, _, _) => write!(f, "custom"),
+            (Curve::Refill, _, _, _) => {
+                write!(
+                    f,
+                    "refill:{}/{}/{}",
+                    initial_amount, pour_cost, evaporation_cost
+                )
+            }
+            (Curve::Linear, 1, 0, 0) => write!(f, "bottomless"),
+            (Curve::Linear, 0, 1, 1) => write!(f, "pierced"),
+            (Curve::Linear, count, 1, 0) => write!(f, "counts:{}", count),
+            (Curve::Linear, millis, 0, 1000) if millis % 1000 == 0 => {
+                write!(f, "secs:{}", millis / 1000)
+            }
+            (Curve::Linear, millis, 0, 1000) => write!(f, "millis:{}", millis),
+            (Curve::Linear, _, _, _) => {
+                write!(f, "{}/{}/{}", initial_amount, pour_cost, evaporation_cost)
+            }
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, name: &str, policies: &[Policy]) -> fmt::Result {
+    write!(f, "{}(", name)?;
+    for (i, p) in policies.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", p)?;
+    }
+    write!(f, ")")
+}
+
+/// Reads a policy from its compact notation, for configuration such as
+/// environment variables.
+///
+/// | Notation               | Policy                                    |
+/// |------------------------|-------------------------------------------|
+/// | `bottomless`           | [`Policy::bottom_less`]                   |
+/// | `pierced`              | [`Policy::pierced`]                       |
+/// | `counts:N`             | [`Policy::expire_within_counts`]          |
+/// | `secs:N`               | [`Policy::expire_within_secs`]            |
+/// | `millis:N`             | [`Policy::expire_within_millis`]          |
+/// | `initial/pour/evap`    | [`Policy::new`]                           |
+/// | `refill:cap/pour/rate` | [`Policy::replenishing`]                  |
+/// | `any(p,..)`            | [`Policy::any_of`]                        |
+/// | `all(p,..)`            | [`Policy::all_of`]                        |
+/// | `p~N`                  | [`Policy::with_soft_threshold`] of `N`    |
+///
+/// Whitespace between tokens is ignored. What [`Display`](fmt::Display)
+/// writes parses back to the same policy.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// let policy: Policy = std::env::var("CACHE_POLICY")
+///     .unwrap_or_else(|_| "any(counts:100, secs:300)".to_string())
+///     .parse()
+///     .unwrap();
+/// # if std::env::var("CACHE_POLICY").is_err() {
+/// assert!(policy.is_remaining_millis(99, 299_000));
+/// assert_eq!(policy.to_string(), "any(counts:100,secs:300)");
+/// # }
+///
+/// let err = "secs:five".parse::<Policy>().unwrap_err();
+/// assert_eq!(err.token(), "five");
+/// assert_eq!(err.to_string(), "unexpected `five` in policy, expected a number");
+/// ```
+impl FromStr for Policy {
+    type Err = ParsePolicyError;
+
+    fn from_str(s: &str) -> Result<Policy, ParsePolicyError> {
+        let mut parser = Parser { rest: s };
+        let policy = parser.policy()?;
+        match parser.next() {
+            "" => Ok(policy),
+            token => Err(ParsePolicyError::new(token, "the end of the policy")),
+        }
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Takes the next token: a punctuation mark, a word, or `""` at the end.
+    fn next(&mut self) -> &'a str {
+        self.rest = self.rest.trim_start();
+        let len = match self.rest.chars().next() {
+            None => 0,
+            Some(c) if is_punct(c) => c.len_utf8(),
+            Some(_) => self
+                .rest
+                .find(|c: char| is_punct(c) || c.is_whitespace())
+                .unwrap_or(self.rest.len()),
+        };
+        let (token, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        token
+    }
+
+    fn peek(&self) -> &'a str {
+        Parser { rest: self.rest }.next()
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), ParsePolicyError> {
+        match self.next() {
+            token if token == punct => Ok(()),
+            token => Err(ParsePolicyError::new(token, punct)),
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, ParsePolicyError> {
+        let token = self.next();
+        token
+            .parse()
+            .map_err(|_| ParsePolicyError::new(token, "a number"))
+    }
+
+    fn triple(&mut self, first: u64) -> Result<(u64, u64, u64), ParsePolicyError> {
+        self.expect("/")?;
+        let second = self.number()?;
+        self.expect("/")?;
+        Ok((first, second, self.number()?))
+    }
+
+    fn policy(&mut self) -> Result<Policy, ParsePolicyError> {
+        let policy = self.budget()?;
+        if self.peek() != "~" {
+            return Ok(policy);
+        }
+        self.next();
+        Ok(policy.with_soft_threshold(self.number()?))
+    }
+
+    fn budget(&mut self) -> Result<Policy, ParsePolicyError> {
+        let token = self.next();
+        match token {
+            "bottomless" => Ok(Policy::bottom_less()),
+            "pierced" => Ok(Policy::pierced()),
+            "counts" | "secs" | "millis" | "refill" => {
+                self.expect(":")?;
+                let n = self.number()?;
+                Ok(match token {
+                    "counts" => Policy::expire_within_counts_u64(n),
+                    "secs" => Policy::expire_within_secs_u64(n),
+                    "millis" => Policy::expire_within_millis(n),
+                    _ => {
+                        let (capacity, pour_cost, refill_per_sec) = self.triple(n)?;
+                        Policy::replenishing_u64(capacity, pour_cost, refill_per_sec)
+                    }
+                })
+            }
+            "any" | "all" => {
+                let policies = self.list()?;
+                Ok(match token {
+                    "any" => Policy::any_of(policies),
+                    _ => Policy::all_of(policies),
+                })
+            }
+            _ => match token.parse() {
+                Ok(n) => {
+                    let (initial_amount, pour_cost, evaporation_cost) = self.triple(n)?;
+                    Ok(Policy::new_u64(initial_amount, pour_cost, evaporation_cost))
+                }
+                Err(_) => Err(ParsePolicyError::new(token, "a policy")),
+            },
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<Policy>, ParsePolicyError> {
+        self.expect("(")?;
+        let mut policies = Vec::new();
+        if self.peek() == ")" {
+            self.next();
+            return Ok(policies);
+        }
+        loop {
+            policies.push(self.policy()?);
+            match self.next() {
+                "," => {}
+                ")" => return Ok(policies),
+                token => return Err(ParsePolicyError::new(token, "`,` or `)`")),
+            }
+        }
+    }
+}
+
+pub(crate) fn is_punct(c: char) -> bool {
+    matches!(c, '(' | ')' | ',' | ':' | '/' | '~')
+}
+
+impl ParsePolicyError {
+    fn new(token: &str, expected: &'static str) -> ParsePolicyError {
+        ParsePolicyError {
+            token: token.to_string(),
+            expected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn round_trips() {
+        for p in [
+            Policy::bottom_less(),
+            Policy::pierced(),
+            Policy::expire_within_counts(100),
+            Policy::expire_within_secs(300),
+            Policy::expire_within_millis(1500),
+            Policy::new(300, 1, 1),
+            Policy::new(0, 0, 0),
+            Policy::replenishing(10, 1, 2),
+            Policy::expire_within_secs(300).with_soft_threshold(0.8),
+            Policy::replenishing(10, 1, 2).with_soft_threshold(3),
+            Policy::any_of(vec![]),
+            Policy::all_of(vec![
+                Policy::expire_within_counts(1),
+                Policy::any_of(vec![Policy::pierced(), Policy::new_u64(u64::MAX, 2, 3)]),
+            ]),
+        ] {
+            let s = p.to_string();
+            assert_eq!(s.parse::<Policy>(), Ok(p), "{}", s);
+        }
+    }
+
+    #[test]
+    fn writes_presets_by_name() {
+        assert_eq!(Policy::bottom_less().to_string(), "bottomless");
+        assert_eq!(Policy::expire_within_counts(5).to_string(), "counts:5");
+        assert_eq!(Policy::expire_within_secs(5).to_string(), "secs:5");
+        assert_eq!(Policy::expire_within_millis(5).to_string(), "millis:5");
+        assert_eq!(Policy::new(300, 1, 1).to_string(), "300/1/1");
+        assert_eq!(Policy::replenishing(3, 1, 1).to_string(), "refill:3/1/1");
+        assert_eq!(
+            Policy::expire_within_counts(5)
+                .with_soft_threshold(4)
+                .to_string(),
+            "counts:5~4"
+        );
+        let custom = Policy::new(100, 1, 0).with_evaporation_fn(|millis| millis);
+        assert_eq!(custom.to_string(), "custom");
+        assert!("custom".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn ignores_whitespace() {
+        assert_eq!(
+            " all( counts : 3 ,300 / 1/1 ) ".parse::<Policy>(),
+            Ok(Policy::all_of(vec![
+                Policy::expire_within_counts(3),
+                Policy::new(300, 1, 1),
+            ]))
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_token() {
+        let err = |s: &str| {
+            let e = s.parse::<Policy>().unwrap_err();
+            (e.token().to_string(), e.to_string())
+        };
+        assert_eq!(
+            err("forever"),
+            (
+                "forever".to_string(),
+                "unexpected `forever` in policy, expected a policy".to_string()
+            )
+        );
+        assert_eq!(err("counts 3").0, "3");
+        assert_eq!(err("300/1").1, "unexpected end of policy, expected `/`");
+        assert_eq!(err("secs:-1").0, "-1");
+        assert_eq!(err("any(pierced;").0, "pierced;");
+        assert_eq!(err("pierced)").0, ")");
+        assert_eq!(err("").1, "unexpected end of policy, expected a policy");
+    }
+}
@@ -0,0 +1,114 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::Duration;
+
+/// Hooks called as a [`Bucket`](crate::Bucket) serves calls.
+///
+/// Every method does nothing by default. [`SharedBucket`](crate::SharedBucket)
+/// calls them without holding its lock.
+///
+/// # Example
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+/// use uchimizu::{BucketObserver, Policy};
+///
+/// # #[cfg(feature = "std")]
+/// # {
+/// #[derive(Debug, Default)]
+/// struct Hits(AtomicU64);
+///
+/// impl BucketObserver for Hits {
+///     fn on_hit(&self) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let hits = Arc::new(Hits::default());
+/// let mut b = Policy::expire_within_counts(3)
+///     .into_bucket()
+///     .with_observer(hits.clone());
+/// for _ in 0..3 {
+///     b.call_sync(|| 1);
+/// }
+/// assert_eq!(hits.0.load(Ordering::Relaxed), 2);
+/// # }
+/// ```
+pub trait BucketObserver: fmt::Debug + Send + Sync {
+    /// A cached value was served.
+    fn on_hit(&self) {}
+
+    /// The task is about to run.
+    fn on_miss(&self) {}
+
+    /// The task finished and its value was stored, `elapsed` after it
+    /// started.
+    fn on_refresh_complete(&self, _elapsed: Duration) {}
+
+    /// The task failed.
+    fn on_refresh_failed(&self) {}
+
+    /// The cached value was dropped by `invalidate` or `refresh`.
+    fn on_invalidate(&self) {}
+}
+
+/// Everything a bucket reports to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Observers {
+    pub(crate) observer: Option<Arc<dyn BucketObserver>>,
+    #[cfg(feature = "metrics")]
+    pub(crate) name: Option<Arc<str>>,
+    #[cfg(feature = "tokio")]
+    pub(crate) events: crate::events::Events,
+}
+
+impl Observers {
+    pub(crate) fn is_empty(&self) -> bool {
+        #[cfg(feature = "metrics")]
+        if self.name.is_some() {
+            return false;
+        }
+        #[cfg(feature = "tokio")]
+        if self.events.sender().is_some() {
+            return false;
+        }
+        self.observer.is_none()
+    }
+
+    pub(crate) fn each(&self, f: impl Fn(&dyn BucketObserver)) {
+        if let Some(observer) = &self.observer {
+            f(observer.as_ref());
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(name) = &self.name {
+            f(&Metrics(name));
+        }
+        #[cfg(feature = "tokio")]
+        if let Some(sender) = self.events.sender() {
+            f(&sender);
+        }
+    }
+}
+
+/// Exports to the `metrics` facade, labelled with the bucket's name.
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+struct Metrics<'a>(&'a Arc<str>);
+
+#[cfg(feature = "metrics")]
+impl BucketObserver for Metrics<'_> {
+    fn on_hit(&self) {
+        metrics::counter!("uchimizu_cache_hits_total", "bucket" => self.0.clone()).increment(1);
+    }
+
+    fn on_miss(&self) {
+        metrics::counter!("uchimizu_cache_misses_total", "bucket" => self.0.clone()).increment(1);
+    }
+
+    fn on_refresh_complete(&self, elapsed: Duration) {
+        let secs = crate::duration_millis(elapsed) as f64 / 1000.0;
+        metrics::histogram!("uchimizu_refresh_duration_seconds", "bucket" => self.0.clone())
+            .record(secs);
+    }
+}
@@ -0,0 +1,145 @@
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use redis::aio::MultiplexedConnection;
+
+use crate::{duration_millis, Bucket, Store, StoreError};
+
+/// Writes the bucket unless the stored one was refreshed at the same time or
+/// later, so that concurrent refreshes on several replicas keep the newest.
+const SAVE_SCRIPT: &str = r"
+local stored = tonumber(redis.call('HGET', KEYS[1], 'refreshed_at') or '-1')
+if stored >= tonumber(ARGV[2]) then
+    return 0
+end
+redis.call('HSET', KEYS[1], 'bucket', ARGV[1], 'refreshed_at', ARGV[2])
+return 1
+";
+
+/// Shares a bucket between processes through a Redis hash at `key`.
+///
+/// The bucket is stored as JSON alongside the wall-clock time of its last
+/// refresh. A save is skipped if the stored bucket is at least as recent, so
+/// one replica's refresh is never overwritten by an older one.
+#[derive(Clone)]
+pub struct RedisStore {
+    conn: MultiplexedConnection,
+    key: String,
+}
+
+impl std::fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisStore")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisStore {
+    pub fn new(conn: MultiplexedConnection, key: impl Into<String>) -> RedisStore {
+        RedisStore {
+            conn,
+            key: key.into(),
+        }
+    }
+
+    /// Connects to the server at `url`.
+    pub async fn connect(url: &str, key: impl Into<String>) -> Result<RedisStore, StoreError> {
+        let client = redis::Client::open(url).map_err(backend)?;
+        let conn = client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(backend)?;
+        Ok(RedisStore::new(conn, key))
+    }
+}
+
+fn backend(e: redis::RedisError) -> StoreError {
+    StoreError::Backend(Box::new(e))
+}
+
+/// Returns the Unix time in milliseconds at which `bucket` was refreshed.
+fn refreshed_at_millis<T>(bucket: &Bucket<T>) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let now: u64 = now.as_millis().try_into().unwrap_or(u64::MAX);
+    now.saturating_sub(duration_millis(bucket.age()))
+}
+
+impl<T> Store<T> for RedisStore
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + Send,
+{
+    fn load(&self) -> impl Future<Output = Result<Option<Bucket<T>>, StoreError>> + Send {
+        let mut conn = self.conn.clone();
+        let key = self.key.clone();
+        async move {
+            let json: Option<Vec<u8>> = redis::cmd("HGET")
+                .arg(&key)
+                .arg("bucket")
+                .query_async(&mut conn)
+                .await
+                .map_err(backend)?;
+            let Some(json) = json else {
+                return Ok(None);
+            };
+            let bucket = serde_json::from_slice(&json).map_err(|e| StoreError::Format(e.into()))?;
+            Ok(Some(bucket))
+        }
+    }
+
+    fn save(&self, bucket: &Bucket<T>) -> impl Future<Output = Result<(), StoreError>> + Send {
+        let mut conn = self.conn.clone();
+        let key = self.key.clone();
+        let json = serde_json::to_vec(bucket).map_err(|e| StoreError::Format(e.into()));
+        let refreshed_at = refreshed_at_millis(bucket);
+        async move {
+            let _: i64 = redis::Script::new(SAVE_SCRIPT)
+                .key(&key)
+                .arg(json?)
+                .arg(refreshed_at)
+                .invoke_async(&mut conn)
+                .await
+                .map_err(backend)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Policy;
+
+    /// Runs against the server at `UCHIMIZU_REDIS_URL`, if set.
+    async fn store(key: &str) -> Option<RedisStore> {
+        let url = std::env::var("UCHIMIZU_REDIS_URL").ok()?;
+        let store = RedisStore::connect(&url, key).await.unwrap();
+        let _: () = redis::cmd("DEL")
+            .arg(key)
+            .query_async(&mut store.conn.clone())
+            .await
+            .unwrap();
+        Some(store)
+    }
+
+    #[tokio::test]
+    async fn older_refresh_does_not_clobber_newer() {
+        let Some(store) = store("uchimizu-test-clobber").await else {
+            return;
+        };
+        assert!(Store::<u32>::load(&store).await.unwrap().is_none());
+
+        let mut older = Policy::bottom_less().into_bucket();
+        older.call_sync(|| 1);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut newer = Policy::bottom_less().into_bucket();
+        newer.call_sync(|| 2);
+
+        store.save(&newer).await.unwrap();
+        store.save(&older).await.unwrap();
+        let loaded: Bucket<u32> = store.load().await.unwrap().unwrap();
+        assert_eq!(loaded.cached(), Some(&2));
+    }
+}
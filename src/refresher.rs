@@ -0,0 +1,212 @@
+use std::future::Future;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::task::{Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// When a refresher started with
+/// [`SharedBucket::spawn_refresher_with`](crate::SharedBucket::spawn_refresher_with)
+/// or
+/// [`SharedBucket::spawn_refresher_blocking_with`](crate::SharedBucket::spawn_refresher_blocking_with)
+/// runs its task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshSchedule {
+    /// How long before the value expires to refresh it. At most half of the
+    /// value's lifetime is taken, so a refresh never follows another at
+    /// once.
+    pub lead: Duration,
+    /// How long to wait before running the task again after it failed.
+    pub retry_after: Duration,
+}
+
+impl RefreshSchedule {
+    pub fn new(lead: Duration, retry_after: Duration) -> RefreshSchedule {
+        RefreshSchedule { lead, retry_after }
+    }
+
+    /// How long to wait after a refresh, given the new value's lifetime in
+    /// milliseconds. A value that is already expired is retried like a
+    /// failure, rather than refreshed in a busy loop.
+    pub(crate) fn next_after(&self, lifetime_millis: u64) -> Duration {
+        if lifetime_millis == 0 {
+            return self.retry_after;
+        }
+        let lifetime = Duration::from_millis(lifetime_millis);
+        lifetime - self.lead.min(lifetime / 2)
+    }
+}
+
+/// Refreshes one second before expiry, and retries one second after a
+/// failure.
+impl Default for RefreshSchedule {
+    fn default() -> Self {
+        RefreshSchedule::new(Duration::from_secs(1), Duration::from_secs(1))
+    }
+}
+
+/// Stops the background refresher when stopped or dropped.
+///
+/// The refresher is not polled again once stopped, so a refresh already
+/// running is cancelled.
+#[derive(Debug)]
+pub struct RefresherHandle {
+    abort: Arc<AbortSignal>,
+}
+
+impl RefresherHandle {
+    pub(crate) fn new(abort: Arc<AbortSignal>) -> RefresherHandle {
+        RefresherHandle { abort }
+    }
+
+    pub fn stop(self) {}
+
+    /// Returns whether the refresher has ended, which it does on its own
+    /// once the policy no longer expires by time.
+    pub fn is_finished(&self) -> bool {
+        self.abort.lock().finished
+    }
+}
+
+impl Drop for RefresherHandle {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.abort.lock();
+            state.aborted = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Ends the future of a refresher once its [`RefresherHandle`] is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct AbortSignal {
+    state: Mutex<AbortState>,
+}
+
+#[derive(Debug, Default)]
+struct AbortState {
+    aborted: bool,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+impl AbortSignal {
+    fn lock(&self) -> std::sync::MutexGuard<'_, AbortState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wraps `fut` to end early once aborted.
+    pub(crate) fn wrap(
+        self: Arc<Self>,
+        mut fut: crate::BoxFuture<'static, ()>,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        std::future::poll_fn(move |cx| {
+            let mut state = self.lock();
+            if state.aborted {
+                state.finished = true;
+                return Poll::Ready(());
+            }
+            state.waker = Some(cx.waker().clone());
+            drop(state);
+            let poll = fut.as_mut().poll(cx);
+            if poll.is_ready() {
+                self.lock().finished = true;
+            }
+            poll
+        })
+    }
+}
+
+/// Stops the refresher thread of
+/// [`SharedBucket::spawn_refresher_blocking_with`](crate::SharedBucket::spawn_refresher_blocking_with)
+/// when stopped or dropped.
+///
+/// The thread sleeps on a condition variable, so it wakes up as soon as it
+/// is stopped rather than when its sleep is over. A refresh already
+/// running is finished first.
+#[derive(Debug)]
+pub struct BlockingRefresherHandle {
+    stop: Arc<StopSignal>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BlockingRefresherHandle {
+    pub(crate) fn new(stop: Arc<StopSignal>, thread: JoinHandle<()>) -> BlockingRefresherHandle {
+        BlockingRefresherHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the refresher and waits for its thread to end.
+    pub fn stop(mut self) {
+        self.stop.raise();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Waits for the refresher to end on its own, which it does once the
+    /// policy no longer expires by time, and returns the panic of the task
+    /// if there was one.
+    pub fn join(mut self) -> std::thread::Result<()> {
+        match self.thread.take() {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether the refresher has ended.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+/// Stops the refresher without waiting for its thread.
+impl Drop for BlockingRefresherHandle {
+    fn drop(&mut self) {
+        self.stop.raise();
+    }
+}
+
+/// Tells a refresher thread to stop, waking it up if it sleeps.
+#[derive(Debug, Default)]
+pub(crate) struct StopSignal {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl StopSignal {
+    fn raise(&self) {
+        *self.stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.wake.notify_all();
+    }
+
+    /// Sleeps for `timeout` unless stopped first, and returns whether it
+    /// was stopped.
+    pub(crate) fn sleep(&self, timeout: Duration) -> bool {
+        let stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        let (stopped, _) = self
+            .wake
+            .wait_timeout_while(stopped, timeout, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+        *stopped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lead_is_capped_at_half_the_lifetime() {
+        let s = RefreshSchedule::new(Duration::from_secs(1), Duration::from_secs(2));
+        assert_eq!(s.next_after(10_000), Duration::from_secs(9));
+        assert_eq!(s.next_after(1_000), Duration::from_millis(500));
+        assert_eq!(s.next_after(1), Duration::from_micros(500));
+        assert_eq!(s.next_after(0), Duration::from_secs(2));
+    }
+}
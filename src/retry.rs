@@ -0,0 +1,18 @@
+use core::time::Duration;
+
+/// How [`Bucket::call_try_with_retry`](crate::Bucket::call_try_with_retry)
+/// retries a failing task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times the task is run at most, including the first attempt.
+    /// Zero is treated as one.
+    pub attempts: u32,
+    /// How long to sleep between attempts.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(attempts: u32, backoff: Duration) -> RetryPolicy {
+        RetryPolicy { attempts, backoff }
+    }
+}
@@ -0,0 +1,24 @@
+//! Serialization of monotonic instants for `serde` without `chrono-clock`.
+//!
+//! An [`Instant`](std::time::Instant) means nothing outside the process that
+//! took it, so it is written as the time elapsed since then, measured on the
+//! system clock, and restored relative to the time of deserialization. An
+//! elapsed time too long to subtract from now restores the earliest instant
+//! the clock can represent, so the value is as old as it can be made.
+
+pub(crate) mod instant {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{Duration, Instant};
+
+    pub(crate) fn serialize<S: Serializer>(instant: &Instant, s: S) -> Result<S::Ok, S::Error> {
+        crate::now()
+            .saturating_duration_since(*instant)
+            .serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Instant, D::Error> {
+        let elapsed = Duration::deserialize(d)?;
+        Ok(crate::saturating_instant_sub(crate::now(), elapsed))
+    }
+}
@@ -0,0 +1,1223 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use crate::observer::Observers;
+use crate::refresher::AbortSignal;
+use crate::refresher::StopSignal;
+#[cfg(feature = "tokio")]
+use crate::CacheEvent;
+use crate::{
+    elapsed_since, Bucket, BucketObserver, CacheMiss, Error, Expiry, Instant, MappedBucket,
+    NoSpawner, Policy, Spawner, Task,
+};
+use crate::{BlockingRefresherHandle, RefreshSchedule, RefresherHandle};
+
+/// A cloneable handle to a [`Bucket`] that can be called through `&self`.
+///
+/// Clones share the same bucket. The internal lock is only held while the
+/// bucket's state is read or updated, never while the task runs.
+///
+/// Concurrent calls on an expired bucket are coalesced: the first caller
+/// runs its task while the others wait for it and are then served the new
+/// value. If that task fails, panics or is cancelled, the waiting callers
+/// retry and one of them runs its own task.
+///
+/// # Example
+/// ```
+/// use uchimizu::Policy;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let b = Policy::expire_within_counts(10).into_shared_bucket();
+/// let handle = b.clone();
+/// assert_eq!(b.call(|| async { 1 }).await, 1);
+/// assert_eq!(handle.call(|| async { 2 }).await, 1);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SharedBucket<T, P = Policy> {
+    inner: Arc<Mutex<Inner<T, P>>>,
+    observers: Observers,
+}
+
+#[derive(Debug)]
+struct Inner<T, P> {
+    bucket: Bucket<T, P>,
+    flight: Option<Arc<Flight>>,
+    spawner: Option<Arc<dyn Spawner>>,
+}
+
+impl<T, P> Clone for SharedBucket<T, P> {
+    fn clone(&self) -> Self {
+        SharedBucket {
+            inner: self.inner.clone(),
+            observers: self.observers.clone(),
+        }
+    }
+}
+
+impl<T, P> From<Bucket<T, P>> for SharedBucket<T, P> {
+    fn from(bucket: Bucket<T, P>) -> Self {
+        SharedBucket {
+            observers: bucket.observers.clone(),
+            inner: Arc::new(Mutex::new(Inner {
+                bucket,
+                flight: None,
+                spawner: None,
+            })),
+        }
+    }
+}
+
+impl<T, P> SharedBucket<T, P> {
+    pub fn policy(&self) -> P
+    where
+        P: Clone,
+    {
+        self.lock().bucket.policy.clone()
+    }
+
+    /// Runs background refreshes with `spawner`, for this handle and every
+    /// clone of it.
+    ///
+    /// Without one, the spawner of the enabled runtime feature is used, see
+    /// [`Spawner`].
+    pub fn with_spawner(self, spawner: impl Spawner + 'static) -> Self {
+        self.lock().spawner = Some(Arc::new(spawner));
+        self
+    }
+
+    /// Returns a view that applies `f` to this bucket's value.
+    ///
+    /// See [`MappedBucket`].
+    pub fn map<U>(&self, f: impl Fn(&T) -> U + Send + Sync + 'static) -> MappedBucket<T, U, P> {
+        MappedBucket::new(self.clone(), f)
+    }
+
+    /// See [`Bucket::events`].
+    #[cfg(feature = "tokio")]
+    pub fn events(&self) -> impl tokio_stream::Stream<Item = CacheEvent> + Send + Unpin + 'static {
+        self.observers.events.subscribe()
+    }
+
+    /// Locks the bucket, ignoring poisoning: the lock is never held while
+    /// the task runs, and the bucket is committed in single steps, so a
+    /// panic elsewhere cannot leave it half updated.
+    fn lock(&self) -> MutexGuard<'_, Inner<T, P>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn observe(&self, f: impl Fn(&dyn BucketObserver)) {
+        self.observers.each(f);
+    }
+
+    /// Returns the configured spawner, or the default one.
+    fn spawner(&self) -> Result<Arc<dyn Spawner>, NoSpawner> {
+        let spawner = self.lock().spawner.clone();
+        spawner
+            .or_else(crate::spawn::default_spawner)
+            .ok_or(NoSpawner)
+    }
+}
+
+impl<T, P> SharedBucket<T, P>
+where
+    P: Expiry,
+{
+    /// See [`Bucket::refresh`].
+    pub fn refresh(&self) {
+        self.lock().bucket.reset();
+        self.observe(|o| o.on_invalidate());
+    }
+
+    /// See [`Bucket::invalidate`].
+    pub fn invalidate(&self) {
+        self.lock().bucket.clear();
+        self.observe(|o| o.on_invalidate());
+    }
+}
+
+impl<T, P> SharedBucket<T, P>
+where
+    T: Clone,
+    P: Expiry,
+{
+    /// See [`Bucket::call`].
+    pub async fn call<F, Fut>(&self, task: F) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        loop {
+            match self.begin(false) {
+                Begin::Hit(entry) => {
+                    self.observe(|o| o.on_hit());
+                    return entry;
+                }
+                Begin::NegativeCached => unreachable!(),
+                Begin::Wait(wait) => wait.await,
+                Begin::Lead(leader) => {
+                    self.observe(|o| o.on_miss());
+                    let fut = task.call();
+                    #[cfg(feature = "tracing")]
+                    let fut = tracing::Instrument::instrument(fut, leader.span.clone());
+                    let entry = fut.await;
+                    return leader.complete(entry);
+                }
+            }
+        }
+    }
+
+    /// See [`Bucket::call_with`].
+    pub async fn call_with<A, F, Fut>(&self, arg: A, task: F) -> T
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.call(move || task(arg)).await
+    }
+
+    /// See [`Bucket::call_try`].
+    pub async fn call_try<F, Fut, E>(&self, task: F) -> Result<T, Error<E>>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        loop {
+            match self.begin(true) {
+                Begin::Hit(entry) => {
+                    self.observe(|o| o.on_hit());
+                    return Ok(entry);
+                }
+                Begin::NegativeCached => return Err(Error::NegativeCached),
+                Begin::Wait(wait) => wait.await,
+                Begin::Lead(leader) => {
+                    self.observe(|o| o.on_miss());
+                    return match task.call().await {
+                        Ok(entry) => Ok(leader.complete(entry)),
+                        Err(e) => leader.recover(e),
+                    };
+                }
+            }
+        }
+    }
+
+    /// See [`Bucket::force_refresh_with`].
+    ///
+    /// If a refresh is already in flight, this waits for it and returns its
+    /// value instead of running `task`. Should that refresh fail or be
+    /// cancelled, `task` is run after all.
+    pub async fn force_refresh_with<F, Fut>(&self, task: F) -> T
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        loop {
+            let begin = {
+                let mut inner = self.lock();
+                match &inner.flight {
+                    Some(flight) => Err((
+                        Wait {
+                            flight: flight.clone(),
+                        },
+                        inner.bucket.stats.refreshes,
+                    )),
+                    None => {
+                        inner.bucket.stats.misses += 1;
+                        Ok(self.lead(&mut inner, true))
+                    }
+                }
+            };
+            match begin {
+                Err((wait, refreshes)) => {
+                    wait.await;
+                    let inner = self.lock();
+                    if inner.bucket.stats.refreshes != refreshes {
+                        if let Some(entry) = inner.bucket.cached() {
+                            return entry.clone();
+                        }
+                    }
+                }
+                Ok(leader) => {
+                    self.observe(|o| o.on_miss());
+                    let fut = task.call();
+                    #[cfg(feature = "tracing")]
+                    let fut = tracing::Instrument::instrument(fut, leader.span.clone());
+                    let entry = fut.await;
+                    return leader.force(entry);
+                }
+            }
+        }
+    }
+
+    /// Serves a stale value instead of waiting for the refresh.
+    ///
+    /// If the policy is exhausted but a value is cached, that value is
+    /// returned immediately and the task is spawned in the background, see
+    /// [`SharedBucket::with_spawner`]; the refreshed value and counters are
+    /// committed when it finishes. Only one refresh runs at a time. A bucket
+    /// without any cached value waits like [`SharedBucket::call`].
+    ///
+    /// The same background refresh is started early once the value is
+    /// stale, as set with [`Bucket::refresh_ahead`] or
+    /// [`Policy::with_soft_threshold`](crate::Policy::with_soft_threshold).
+    ///
+    /// # Errors
+    /// Returns [`NoSpawner`] without running the task if there is no
+    /// spawner to refresh in the background with.
+    ///
+    /// # Panics
+    /// With the default [`TokioSpawner`](crate::TokioSpawner), panics if it
+    /// has to refresh in the background outside of a tokio runtime.
+    pub async fn call_swr<F, Fut>(&self, task: F) -> Result<T, NoSpawner>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+        P: Send + 'static,
+    {
+        let spawner = self.spawner()?;
+        let served = {
+            let mut inner = self.lock();
+            if let Some(entry) = inner.bucket.hit() {
+                let due = inner.bucket.is_due_ahead() && inner.flight.is_none();
+                let leader = due.then(|| {
+                    inner.bucket.stats.misses += 1;
+                    self.lead(&mut inner, true)
+                });
+                Some((entry, leader))
+            } else if let Some(entry) = inner.bucket.stale() {
+                let leader = inner.flight.is_none().then(|| {
+                    let exhausted = inner.bucket.miss();
+                    self.lead(&mut inner, exhausted)
+                });
+                Some((entry, leader))
+            } else {
+                None
+            }
+        };
+        let Some((entry, leader)) = served else {
+            return Ok(self.call(task).await);
+        };
+        self.observe(|o| o.on_hit());
+        if let Some(leader) = leader {
+            self.observe(|o| o.on_miss());
+            let fut = task.call();
+            spawner.spawn(Box::pin(async move {
+                let entry = fut.await;
+                leader.complete(entry);
+            }));
+        }
+        Ok(entry)
+    }
+
+    /// Runs `task` and stores its value regardless of the policy, recording
+    /// a failure instead if it fails.
+    async fn refresh_try<F, Fut, E>(&self, task: F) -> Result<(), ()>
+    where
+        F: Task<Fut>,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let leader = {
+            let mut inner = self.lock();
+            inner.bucket.stats.misses += 1;
+            self.lead(&mut inner, true)
+        };
+        self.observe(|o| o.on_miss());
+        match task.call().await {
+            Ok(entry) => {
+                leader.force(entry);
+                Ok(())
+            }
+            Err(e) => {
+                leader.fail(e);
+                Err(())
+            }
+        }
+    }
+
+    fn begin(&self, check_negative: bool) -> Begin<T, P> {
+        let mut inner = self.lock();
+        if let Some(entry) = inner.bucket.hit() {
+            return Begin::Hit(entry);
+        }
+        if let Some(flight) = &inner.flight {
+            return Begin::Wait(Wait {
+                flight: flight.clone(),
+            });
+        }
+        if let Some(entry) = check_negative
+            .then(|| inner.bucket.short_circuit())
+            .flatten()
+        {
+            return match entry {
+                Some(entry) => Begin::Hit(entry),
+                None => Begin::NegativeCached,
+            };
+        }
+        let exhausted = inner.bucket.miss();
+        Begin::Lead(self.lead(&mut inner, exhausted))
+    }
+
+    fn lead(&self, inner: &mut Inner<T, P>, exhausted: bool) -> Leader<T, P> {
+        #[cfg(feature = "tracing")]
+        let span = inner.bucket.refresh_span();
+        let flight = Arc::new(Flight::default());
+        inner.flight = Some(flight.clone());
+        Leader {
+            shared: self.clone(),
+            flight,
+            exhausted,
+            started: inner.bucket.start(),
+            #[cfg(feature = "tracing")]
+            span,
+        }
+    }
+
+    /// See [`Bucket::cached`].
+    pub fn cached(&self) -> Option<T> {
+        self.lock().bucket.cached().cloned()
+    }
+
+    /// See [`Bucket::fresh`].
+    pub fn fresh(&self) -> Option<T> {
+        self.lock().bucket.fresh().cloned()
+    }
+
+    /// See [`Bucket::subscribe`].
+    #[cfg(feature = "tokio")]
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<Option<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.lock().bucket.subscribe()
+    }
+
+    /// See [`Bucket::try_cached`].
+    pub fn try_cached(&self) -> Result<T, CacheMiss> {
+        self.lock().bucket.try_cached().cloned()
+    }
+}
+
+impl<T> SharedBucket<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Like [`SharedBucket::spawn_refresher_with`], with the default
+    /// [`RefreshSchedule`].
+    ///
+    /// # Errors
+    /// Returns [`NoSpawner`] if there is no spawner to run the refresher
+    /// on.
+    ///
+    /// # Panics
+    /// With the default [`TokioSpawner`](crate::TokioSpawner), panics if
+    /// called outside of a tokio runtime.
+    pub fn spawn_refresher<F, Fut, E>(&self, task: F) -> Result<RefresherHandle, NoSpawner>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send,
+    {
+        self.spawn_refresher_with(RefreshSchedule::default(), task)
+    }
+
+    /// Keeps the bucket warm by running `task` in the background shortly
+    /// before the value expires by time.
+    ///
+    /// An empty or expired bucket is refreshed at once. Readers can then
+    /// use [`SharedBucket::cached`] or [`SharedBucket::try_cached`] and
+    /// never wait for the task. A failed refresh is counted in the stats
+    /// and retried after [`RefreshSchedule::retry_after`]; log the error in
+    /// `task` if it matters. The refresher ends on its own if the policy
+    /// does not expire by time, and otherwise runs until the handle is
+    /// stopped or dropped. It runs on, and sleeps with, the bucket's
+    /// [`Spawner`].
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use uchimizu::{Policy, RefreshSchedule};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+    /// # {
+    /// let keys = Policy::expire_within_secs(3600).into_shared_bucket();
+    /// let schedule = RefreshSchedule::new(Duration::from_secs(60), Duration::from_secs(5));
+    /// let refresher = keys
+    ///     .spawn_refresher_with(schedule, || async { Ok::<_, ()>("key-1") })
+    ///     .unwrap();
+    /// tokio::time::sleep(Duration::from_millis(10)).await;
+    /// assert_eq!(keys.cached(), Some("key-1"));
+    /// refresher.stop();
+    /// # }
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`NoSpawner`] if there is no spawner to run the refresher
+    /// on.
+    ///
+    /// # Panics
+    /// With the default [`TokioSpawner`](crate::TokioSpawner), panics if
+    /// called outside of a tokio runtime.
+    pub fn spawn_refresher_with<F, Fut, E>(
+        &self,
+        schedule: RefreshSchedule,
+        mut task: F,
+    ) -> Result<RefresherHandle, NoSpawner>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send,
+    {
+        let shared = self.clone();
+        let spawner = self.spawner()?;
+        let sleeper = spawner.clone();
+        let refresher = async move {
+            let mut wait = if shared.lock().bucket.is_fresh() {
+                match shared.lifetime_millis() {
+                    Some(millis) => schedule.next_after(millis),
+                    None => return,
+                }
+            } else {
+                std::time::Duration::ZERO
+            };
+            loop {
+                sleeper.sleep(wait).await;
+                wait = match shared.refresh_try(&mut task).await {
+                    Ok(()) => match shared.lifetime_millis() {
+                        Some(millis) => schedule.next_after(millis),
+                        None => return,
+                    },
+                    Err(()) => schedule.retry_after,
+                };
+            }
+        };
+        let abort = Arc::new(AbortSignal::default());
+        spawner.spawn(Box::pin(abort.clone().wrap(Box::pin(refresher))));
+        Ok(RefresherHandle::new(abort))
+    }
+}
+
+impl<T> SharedBucket<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Like [`SharedBucket::spawn_refresher_blocking_with`], with the
+    /// default [`RefreshSchedule`].
+    pub fn spawn_refresher_blocking<F>(&self, task: F) -> BlockingRefresherHandle
+    where
+        F: FnMut() -> T + Send + 'static,
+    {
+        self.spawn_refresher_blocking_with(RefreshSchedule::default(), task)
+    }
+
+    /// Keeps the bucket warm by running `task` on its own thread shortly
+    /// before the value expires by time, for programs without an async
+    /// runtime.
+    ///
+    /// Like [`SharedBucket::spawn_refresher_with`], an empty or expired
+    /// bucket is refreshed at once, readers use [`SharedBucket::cached`] or
+    /// [`SharedBucket::try_cached`], and the refresher ends on its own if
+    /// the policy does not expire by time. A panic in `task` ends it too,
+    /// and is returned by [`BlockingRefresherHandle::join`].
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use uchimizu::{Policy, RefreshSchedule};
+    ///
+    /// let keys = Policy::expire_within_secs(3600).into_shared_bucket();
+    /// let schedule = RefreshSchedule::new(Duration::from_secs(60), Duration::from_secs(5));
+    /// let refresher = keys.spawn_refresher_blocking_with(schedule, || "key-1");
+    /// while keys.cached().is_none() {
+    ///     std::thread::sleep(Duration::from_millis(1));
+    /// }
+    /// assert_eq!(keys.cached(), Some("key-1"));
+    /// refresher.stop();
+    /// ```
+    pub fn spawn_refresher_blocking_with<F>(
+        &self,
+        schedule: RefreshSchedule,
+        mut task: F,
+    ) -> BlockingRefresherHandle
+    where
+        F: FnMut() -> T + Send + 'static,
+    {
+        let shared = self.clone();
+        let stop = Arc::new(StopSignal::default());
+        let signal = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let mut wait = if shared.lock().bucket.is_fresh() {
+                match shared.lifetime_millis() {
+                    Some(millis) => schedule.next_after(millis),
+                    None => return,
+                }
+            } else {
+                std::time::Duration::ZERO
+            };
+            while !signal.sleep(wait) {
+                shared.refresh_blocking(&mut task);
+                wait = match shared.lifetime_millis() {
+                    Some(millis) => schedule.next_after(millis),
+                    None => return,
+                };
+            }
+        });
+        BlockingRefresherHandle::new(stop, thread)
+    }
+
+    /// Runs `task` and stores its value regardless of the policy.
+    fn refresh_blocking(&self, task: &mut impl FnMut() -> T) {
+        let leader = {
+            let mut inner = self.lock();
+            inner.bucket.stats.misses += 1;
+            self.lead(&mut inner, true)
+        };
+        self.observe(|o| o.on_miss());
+        leader.force(task());
+    }
+
+    fn lifetime_millis(&self) -> Option<u64> {
+        let ttl = self.lock().bucket.time_to_expiry()?;
+        Some(crate::duration_millis(ttl))
+    }
+}
+
+enum Begin<T, P> {
+    Hit(T),
+    NegativeCached,
+    Wait(Wait),
+    Lead(Leader<T, P>),
+}
+
+/// The caller that runs the task for everyone else.
+///
+/// Dropping it, whether after completing or because the caller was
+/// cancelled, releases the waiting callers.
+struct Leader<T, P> {
+    shared: SharedBucket<T, P>,
+    flight: Arc<Flight>,
+    exhausted: bool,
+    started: Option<Instant>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<T, P> Leader<T, P>
+where
+    T: Clone,
+    P: Expiry,
+{
+    fn complete(self, entry: T) -> T {
+        let exhausted = self.exhausted;
+        self.store(|bucket| bucket.complete(entry, exhausted))
+    }
+
+    fn force(self, entry: T) -> T {
+        self.store(|bucket| bucket.force(entry))
+    }
+
+    fn store(self, f: impl FnOnce(&mut Bucket<T, P>) -> T) -> T {
+        let (entry, elapsed) = {
+            let mut inner = self.shared.lock();
+            let entry = f(&mut inner.bucket);
+            let elapsed = self
+                .started
+                .map(|started| elapsed_since(started, inner.bucket.now()));
+            (entry, elapsed)
+        };
+        if let Some(elapsed) = elapsed {
+            self.shared.observe(|o| o.on_refresh_complete(elapsed));
+        }
+        entry
+    }
+
+    fn fail<E>(self, e: E) {
+        self.shared.lock().bucket.fail(e);
+        self.shared.observe(|o| o.on_refresh_failed());
+    }
+
+    fn recover<E>(self, e: E) -> Result<T, Error<E>> {
+        let result = self.shared.lock().bucket.recover(e);
+        self.shared.observe(|o| o.on_refresh_failed());
+        if result.is_ok() {
+            self.shared.observe(|o| o.on_hit());
+        }
+        result
+    }
+}
+
+impl<T, P> Drop for Leader<T, P> {
+    fn drop(&mut self) {
+        let mut inner = self.shared.lock();
+        if inner
+            .flight
+            .as_ref()
+            .is_some_and(|f| Arc::ptr_eq(f, &self.flight))
+        {
+            inner.flight = None;
+        }
+        drop(inner);
+        self.flight.finish();
+    }
+}
+
+/// A refresh in progress that other callers can wait for.
+#[derive(Debug, Default)]
+struct Flight {
+    state: Mutex<FlightState>,
+}
+
+#[derive(Debug, Default)]
+struct FlightState {
+    done: bool,
+    wakers: Vec<Waker>,
+}
+
+impl Flight {
+    fn state(&self) -> MutexGuard<'_, FlightState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn finish(&self) {
+        let mut state = self.state();
+        state.done = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+struct Wait {
+    flight: Arc<Flight>,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.flight.state();
+        if state.done {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn clones_share_state() {
+        let a = Policy::expire_within_counts(3).into_shared_bucket();
+        let b = a.clone();
+        assert_eq!(a.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(a.cached(), Some(1));
+        b.refresh();
+        assert_eq!(a.cached(), None);
+        assert_eq!(a.call(|| async { 3 }).await, 3);
+        assert_eq!(b.policy(), Policy::expire_within_counts(3));
+    }
+
+    #[tokio::test]
+    async fn lock_is_not_held_across_task() {
+        let b = Policy::pierced().into_shared_bucket();
+        b.call(|| async { 1 }).await;
+        let handle = b.clone();
+        let entry = b
+            .call(move || {
+                let handle = handle.clone();
+                async move { handle.cached().unwrap() + 1 }
+            })
+            .await;
+        assert_eq!(entry, 2);
+    }
+
+    #[tokio::test]
+    async fn usable_across_tasks() {
+        let b = Policy::bottom_less().into_shared_bucket();
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let b = b.clone();
+                tokio::spawn(async move { b.call(move || async move { i }).await })
+            })
+            .collect();
+        for h in handles {
+            h.await.unwrap();
+        }
+        assert!(b.cached().is_some());
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_are_coalesced() {
+        let b = Policy::expire_within_counts(100).into_shared_bucket();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let b = b.clone();
+                let runs = runs.clone();
+                tokio::spawn(async move {
+                    b.call(move || {
+                        let runs = runs.clone();
+                        async move {
+                            runs.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                            1
+                        }
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.await.unwrap(), 1);
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn force_refresh_coalesces_with_flight() {
+        let b = Policy::expire_within_counts(100).into_shared_bucket();
+        let leader = {
+            let b = b.clone();
+            tokio::spawn(async move {
+                b.call(|| async {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    1
+                })
+                .await
+            })
+        };
+        tokio::task::yield_now().await;
+        let forced = b.force_refresh_with(|| async { unreachable!() }).await;
+        assert_eq!(forced, 1);
+        assert_eq!(leader.await.unwrap(), 1);
+
+        assert_eq!(b.force_refresh_with(|| async { 2 }).await, 2);
+        assert_eq!(b.lock().bucket.hit_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn followers_retry_when_leader_fails() {
+        let b = Policy::expire_within_counts(100).into_shared_bucket();
+        let leader = {
+            let b = b.clone();
+            tokio::spawn(async move {
+                b.call_try(|| async {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    Err::<u32, _>(())
+                })
+                .await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let r = b.call_try(|| async { Ok::<_, ()>(2) }).await;
+        assert_eq!(leader.await.unwrap(), Err(Error::Task(())));
+        assert_eq!(r, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn followers_retry_when_leader_is_cancelled() {
+        let b = Policy::expire_within_counts(100).into_shared_bucket();
+        let leader = b.call(|| async {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            1
+        });
+        tokio::select! {
+            _ = leader => panic!(),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(10)) => {}
+        }
+        assert_eq!(b.call(|| async { 2 }).await, 2);
+    }
+
+    #[tokio::test]
+    async fn panicking_leader_releases_followers() {
+        let b = Policy::pierced().into_shared_bucket();
+        b.call(|| async { 1 }).await;
+        let leader = {
+            let b = b.clone();
+            tokio::spawn(async move {
+                b.call(|| async {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    panic!("task failed");
+                })
+                .await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let follower = {
+            let b = b.clone();
+            tokio::spawn(async move { b.call(|| async { 2 }).await })
+        };
+        assert!(leader.await.unwrap_err().is_panic());
+        assert_eq!(follower.await.unwrap(), 2);
+        assert!(b.lock().flight.is_none());
+        assert!(!b.inner.is_poisoned());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn call_swr_serves_stale_while_refreshing() {
+        let b = Policy::expire_within_counts(2).into_shared_bucket();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let runs = runs.clone();
+            move || {
+                let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    if n > 1 {
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    }
+                    n
+                }
+            }
+        };
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(b.call_swr(task).await, Ok(2));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn call_swr_refreshes_ahead() {
+        let clock = crate::ManualClock::new();
+        let b = Policy::expire_within_secs(10)
+            .into_bucket_with_clock(clock.clone())
+            .refresh_ahead(0.8)
+            .into_shared();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let runs = runs.clone();
+            move || {
+                let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    n
+                }
+            }
+        };
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        clock.advance(crate::millis_duration(7_000));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        clock.advance(crate::millis_duration(1_000));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let stats = b.lock().bucket.stats.clone();
+        assert_eq!((stats.hits, stats.misses), (3, 2));
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        clock.advance(crate::millis_duration(7_000));
+        assert_eq!(b.call_swr(task).await, Ok(2));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn call_swr_refreshes_at_the_soft_threshold() {
+        let clock = crate::ManualClock::new();
+        let b = Policy::expire_within_secs(10)
+            .with_soft_threshold(8_000)
+            .into_bucket_with_clock(clock.clone())
+            .into_shared();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let runs = runs.clone();
+            move || {
+                let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                async move { n }
+            }
+        };
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        clock.advance(crate::millis_duration(7_999));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        clock.advance(crate::millis_duration(1));
+        assert_eq!(b.call_swr(task.clone()).await, Ok(1));
+        tokio::task::yield_now().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert_eq!(b.call_swr(task).await, Ok(2));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn refresher_keeps_value_warm_through_failures() {
+        use std::time::Duration;
+
+        let b = Policy::expire_within_millis(100).into_shared_bucket();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let runs = runs.clone();
+            move || {
+                let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    if n == 2 {
+                        Err(())
+                    } else {
+                        Ok(n)
+                    }
+                }
+            }
+        };
+        let schedule = RefreshSchedule::new(Duration::from_millis(40), Duration::from_millis(10));
+        let handle = b.spawn_refresher_with(schedule, task).unwrap();
+        for _ in 0..30 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(b.try_cached().is_ok());
+        }
+        handle.stop();
+        assert!(runs.load(Ordering::SeqCst) >= 5);
+        assert_eq!(b.lock().bucket.stats().failures, 1);
+        let stopped = runs.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), stopped);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn refresher_ends_if_policy_never_expires_by_time() {
+        let b = Policy::expire_within_counts(1).into_shared_bucket();
+        let handle = b.spawn_refresher(|| async { Ok::<_, ()>(1) }).unwrap();
+        let ended = async {
+            while !handle.is_finished() {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(1), ended)
+            .await
+            .unwrap();
+        assert_eq!(b.cached(), Some(1));
+    }
+
+    #[test]
+    fn blocking_refresher_keeps_value_warm_and_stops_promptly() {
+        use std::time::{Duration, Instant};
+
+        let b = Policy::expire_within_millis(100).into_shared_bucket();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let runs = runs.clone();
+            move || runs.fetch_add(1, Ordering::SeqCst) + 1
+        };
+        let schedule = RefreshSchedule::new(Duration::from_millis(40), Duration::from_millis(10));
+        let handle = b.spawn_refresher_blocking_with(schedule, task);
+        while b.cached().is_none() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        for _ in 0..20 {
+            std::thread::sleep(Duration::from_millis(10));
+            assert!(b.try_cached().is_ok());
+        }
+        assert!(runs.load(Ordering::SeqCst) >= 3);
+
+        let started = Instant::now();
+        handle.stop();
+        assert!(started.elapsed() < Duration::from_millis(50));
+        let stopped = runs.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(runs.load(Ordering::SeqCst), stopped);
+    }
+
+    #[test]
+    fn blocking_refresher_ends_if_policy_never_expires_by_time() {
+        let b = Policy::expire_within_counts(1).into_shared_bucket();
+        let handle = b.spawn_refresher_blocking(|| 1);
+        assert!(handle.join().is_ok());
+        assert_eq!(b.cached(), Some(1));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn events_report_failures_and_invalidations() {
+        use tokio_stream::StreamExt;
+
+        let b = Policy::pierced().into_shared_bucket();
+        let events = b.events();
+        let _ = b.call_try(|| async { Err::<u32, _>(()) }).await;
+        b.invalidate();
+        b.refresh();
+        drop(b);
+        let received: Vec<_> = events.collect().await;
+        assert_eq!(
+            received,
+            [
+                CacheEvent::Miss,
+                CacheEvent::RefreshStarted,
+                CacheEvent::RefreshFailed,
+                CacheEvent::Invalidated,
+                CacheEvent::Invalidated,
+            ]
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct Reentrant {
+        bucket: std::sync::OnceLock<SharedBucket<u32>>,
+        hits: AtomicUsize,
+    }
+
+    impl BucketObserver for Reentrant {
+        fn on_hit(&self) {
+            assert_eq!(self.bucket.get().unwrap().cached(), Some(1));
+            self.hits.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_runs_outside_the_lock() {
+        let observer = Arc::new(Reentrant::default());
+        let b = Policy::bottom_less()
+            .into_bucket()
+            .with_observer(observer.clone())
+            .into_shared();
+        observer.bucket.set(b.clone()).unwrap();
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(observer.hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn call_try_records_failures() {
+        let b = Policy::pierced()
+            .into_bucket()
+            .with_error_policy(Policy::expire_within_counts(1))
+            .into_shared();
+        let r = b.call_try(|| async { Err::<u32, _>(()) }).await;
+        assert_eq!(r, Err(Error::Task(())));
+        let r = b.call_try(|| async { Ok::<_, ()>(1) }).await;
+        assert_eq!(r, Err(Error::NegativeCached));
+        assert_eq!(b.call_try(|| async { Ok::<_, ()>(1) }).await, Ok(1));
+    }
+
+    #[derive(Clone, Default)]
+    struct Queue(Arc<Mutex<Vec<crate::BoxFuture<'static, ()>>>>);
+
+    impl std::fmt::Debug for Queue {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("Queue(..)")
+        }
+    }
+
+    impl Spawner for Queue {
+        fn spawn(&self, fut: crate::BoxFuture<'static, ()>) {
+            self.0.lock().unwrap().push(fut);
+        }
+    }
+
+    #[tokio::test]
+    async fn call_swr_refreshes_on_the_given_spawner() {
+        let queue = Queue::default();
+        let b = Policy::expire_within_counts(1)
+            .into_shared_bucket()
+            .with_spawner(queue.clone());
+        assert_eq!(b.call_swr(|| async { 1 }).await, Ok(1));
+        assert_eq!(b.call_swr(|| async { 2 }).await, Ok(1));
+        assert_eq!(b.call_swr(|| async { 3 }).await, Ok(1));
+        let queued = std::mem::take(&mut *queue.0.lock().unwrap());
+        assert_eq!(queued.len(), 1);
+        for fut in queued {
+            fut.await;
+        }
+        assert_eq!(b.cached(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn spawn_refresher_runs_on_the_given_spawner() {
+        let queue = Queue::default();
+        let b = Policy::expire_within_secs(3600)
+            .into_shared_bucket()
+            .with_spawner(queue.clone());
+        let refresher = b.spawn_refresher(|| async { Ok::<_, ()>(1) }).unwrap();
+        let mut queued = std::mem::take(&mut *queue.0.lock().unwrap());
+        assert_eq!(queued.len(), 1);
+        // Drives the refresher, which sleeps on a thread of its own.
+        while b.cached().is_none() {
+            std::future::poll_fn(|cx| {
+                let _ = queued[0].as_mut().poll(cx);
+                Poll::Ready(())
+            })
+            .await;
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        assert_eq!(b.cached(), Some(1));
+        assert!(!refresher.is_finished());
+        drop(refresher);
+        queued.pop().unwrap().await;
+    }
+
+    #[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+    #[tokio::test]
+    async fn spawning_needs_a_spawner() {
+        let b = Policy::expire_within_counts(1).into_shared_bucket();
+        assert_eq!(b.call_swr(|| async { 1 }).await, Err(NoSpawner));
+        let refresher = b.spawn_refresher(|| async { Ok::<_, ()>(2) });
+        assert!(matches!(refresher, Err(NoSpawner)));
+        assert_eq!(b.cached(), None);
+
+        let b = b.with_spawner(Queue::default());
+        assert_eq!(b.call_swr(|| async { 3 }).await, Ok(3));
+    }
+
+    /// Refreshes in the background, and with a refresher, on `spawner`,
+    /// blocking on its executor with `block_on`.
+    #[cfg(any(feature = "async-std", feature = "smol"))]
+    fn refreshes_on(
+        spawner: impl Spawner + Clone + 'static,
+        block_on: fn(crate::BoxFuture<'static, ()>),
+    ) {
+        use std::time::Duration;
+        let b = Policy::expire_within_counts(1)
+            .into_shared_bucket()
+            .with_spawner(spawner.clone());
+        block_on(Box::pin(async move {
+            assert_eq!(b.call_swr(|| async { 1 }).await, Ok(1));
+            assert_eq!(b.call_swr(|| async { 2 }).await, Ok(1));
+            assert_eq!(b.call_swr(|| async { 3 }).await, Ok(1));
+            while b.cached() != Some(3) {
+                spawner.sleep(Duration::from_millis(1)).await;
+            }
+
+            let b = Policy::expire_within_secs(3600)
+                .into_shared_bucket()
+                .with_spawner(spawner.clone());
+            let schedule = RefreshSchedule::new(Duration::from_secs(1), Duration::from_secs(1));
+            let refresher = b
+                .spawn_refresher_with(schedule, || async { Ok::<_, ()>(4) })
+                .unwrap();
+            while b.cached().is_none() {
+                spawner.sleep(Duration::from_millis(1)).await;
+            }
+            assert_eq!(b.cached(), Some(4));
+            assert!(!refresher.is_finished());
+        }));
+    }
+
+    #[cfg(feature = "async-std")]
+    #[test]
+    fn refreshes_on_async_std() {
+        refreshes_on(crate::AsyncStdSpawner, async_std::task::block_on);
+    }
+
+    #[cfg(feature = "smol")]
+    #[test]
+    fn refreshes_on_smol() {
+        refreshes_on(crate::SmolSpawner, smol::block_on);
+    }
+}
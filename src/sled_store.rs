@@ -0,0 +1,110 @@
+use std::future::Future;
+
+use crate::{Bucket, Store, StoreError};
+
+/// Saves buckets as JSON in a [`sled::Tree`], one entry per name.
+///
+/// Many named buckets can share a tree. A stored value that no longer
+/// deserializes, e.g. after the cached type changed, is treated as if
+/// nothing was saved, so the bucket starts cold instead of failing on every
+/// load. The tree is read and written with blocking i/o.
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    tree: sled::Tree,
+    name: sled::IVec,
+}
+
+impl SledStore {
+    pub fn new(tree: sled::Tree, name: impl AsRef<[u8]>) -> SledStore {
+        SledStore {
+            tree,
+            name: name.as_ref().into(),
+        }
+    }
+
+    fn load_sync<T>(&self) -> Result<Option<Bucket<T>>, StoreError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let Some(json) = self.tree.get(&self.name).map_err(backend)? else {
+            return Ok(None);
+        };
+        match serde_json::from_slice(&json) {
+            Ok(bucket) => Ok(Some(bucket)),
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    target: "uchimizu",
+                    name = %String::from_utf8_lossy(&self.name),
+                    error = %_e,
+                    "discarding malformed bucket"
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    fn save_sync<T>(&self, bucket: &Bucket<T>) -> Result<(), StoreError>
+    where
+        T: serde::Serialize,
+    {
+        let json = serde_json::to_vec(bucket).map_err(|e| StoreError::Format(e.into()))?;
+        self.tree.insert(&self.name, json).map_err(backend)?;
+        self.tree.flush().map_err(backend)?;
+        Ok(())
+    }
+}
+
+fn backend(e: sled::Error) -> StoreError {
+    match e {
+        sled::Error::Io(e) => StoreError::Io(e),
+        e => StoreError::Backend(Box::new(e)),
+    }
+}
+
+impl<T> Store<T> for SledStore
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + Send,
+{
+    fn load(&self) -> impl Future<Output = Result<Option<Bucket<T>>, StoreError>> + Send {
+        std::future::ready(self.load_sync())
+    }
+
+    fn save(&self, bucket: &Bucket<T>) -> impl Future<Output = Result<(), StoreError>> + Send {
+        std::future::ready(self.save_sync(bucket))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Policy;
+
+    fn tree() -> sled::Tree {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.open_tree("buckets").unwrap()
+    }
+
+    #[tokio::test]
+    async fn names_are_independent() {
+        let tree = tree();
+        let a = SledStore::new(tree.clone(), "a");
+        let b = SledStore::new(tree, "b");
+
+        let mut bucket = Policy::bottom_less().into_bucket();
+        bucket.call_sync(|| 1u32);
+        a.save(&bucket).await.unwrap();
+
+        let loaded: Bucket<u32> = a.load().await.unwrap().unwrap();
+        assert_eq!(loaded.cached(), Some(&1));
+        assert!(Store::<u32>::load(&b).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn malformed_value_is_a_cold_cache() {
+        let tree = tree();
+        tree.insert("a", "not json").unwrap();
+        let store = SledStore::new(tree, "a");
+        assert!(Store::<u32>::load(&store).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,29 @@
+//! The version tag of serialized buckets.
+//!
+//! Snapshots written before the tag existed have the same layout as
+//! version 1 and load as such.
+
+/// The layout version written into every serialized [`Bucket`](crate::Bucket).
+pub(crate) const VERSION: u64 = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Version;
+
+impl serde::Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(VERSION)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Version, D::Error> {
+        let version = u64::deserialize(d)?;
+        if version != VERSION {
+            return Err(serde::de::Error::custom(format_args!(
+                "unsupported bucket snapshot version {}, expected at most {}",
+                version, VERSION
+            )));
+        }
+        Ok(Version)
+    }
+}
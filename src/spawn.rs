@@ -0,0 +1,189 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// A future that can be sent to another thread, as taken by
+/// [`Spawner::spawn`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Runs background work, such as the refresh started by
+/// [`SharedBucket::call_swr`](crate::SharedBucket::call_swr) or the
+/// refresher of
+/// [`SharedBucket::spawn_refresher_with`](crate::SharedBucket::spawn_refresher_with),
+/// on an executor of choice.
+///
+/// Set one with
+/// [`SharedBucket::with_spawner`](crate::SharedBucket::with_spawner).
+/// When none is set, the spawner of the `tokio`, `async-std` or `smol`
+/// feature is used, in that order. Without any of them, the methods that
+/// spawn return [`NoSpawner`](crate::NoSpawner).
+///
+/// # Example
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use uchimizu::{BoxFuture, Policy, Spawner};
+///
+/// /// Queues the futures for the caller to drive.
+/// #[derive(Clone, Default)]
+/// struct Deferred(Arc<Mutex<Vec<BoxFuture<'static, ()>>>>);
+///
+/// impl std::fmt::Debug for Deferred {
+///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         f.write_str("Deferred(..)")
+///     }
+/// }
+///
+/// impl Spawner for Deferred {
+///     fn spawn(&self, fut: BoxFuture<'static, ()>) {
+///         self.0.lock().unwrap().push(fut);
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let spawner = Deferred::default();
+/// let b = Policy::expire_within_counts(1)
+///     .into_shared_bucket()
+///     .with_spawner(spawner.clone());
+/// assert_eq!(b.call_swr(|| async { 1 }).await, Ok(1));
+/// assert_eq!(b.call_swr(|| async { 2 }).await, Ok(1));
+/// assert_eq!(b.call_swr(|| async { 3 }).await, Ok(1));
+/// let queued = std::mem::take(&mut *spawner.0.lock().unwrap());
+/// for fut in queued {
+///     fut.await;
+/// }
+/// assert_eq!(b.cached(), Some(3));
+/// # }
+/// ```
+pub trait Spawner: fmt::Debug + Send + Sync {
+    fn spawn(&self, fut: BoxFuture<'static, ()>);
+
+    /// Returns a future that is ready once `duration` has passed, used by
+    /// refreshers to wait for the next refresh.
+    ///
+    /// By default each sleep waits on a thread of its own; spawners for an
+    /// executor with a timer should use that instead.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(ThreadSleep {
+            duration,
+            state: None,
+        })
+    }
+}
+
+/// The spawner of a [`SharedBucket`](crate::SharedBucket) without one set.
+#[cfg(feature = "tokio")]
+pub(crate) fn default_spawner() -> Option<Arc<dyn Spawner>> {
+    Some(Arc::new(TokioSpawner))
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) fn default_spawner() -> Option<Arc<dyn Spawner>> {
+    Some(Arc::new(AsyncStdSpawner))
+}
+
+#[cfg(all(feature = "smol", not(any(feature = "tokio", feature = "async-std"))))]
+pub(crate) fn default_spawner() -> Option<Arc<dyn Spawner>> {
+    Some(Arc::new(SmolSpawner))
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+pub(crate) fn default_spawner() -> Option<Arc<dyn Spawner>> {
+    None
+}
+
+/// The default [`Spawner::sleep`]: a thread sleeps for the duration and
+/// then wakes the task.
+struct ThreadSleep {
+    duration: Duration,
+    state: Option<Arc<Mutex<SleepState>>>,
+}
+
+#[derive(Default)]
+struct SleepState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let duration = self.duration;
+        let state = self.state.get_or_insert_with(|| {
+            let state = Arc::new(Mutex::new(SleepState::default()));
+            let woken = state.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                let mut woken = woken.lock().unwrap_or_else(PoisonError::into_inner);
+                woken.done = true;
+                if let Some(waker) = woken.waker.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.done {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Spawns onto the current tokio runtime.
+///
+/// # Panics
+/// [`Spawner::spawn`] panics if called outside of a tokio runtime.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSpawner;
+
+#[cfg(feature = "tokio")]
+impl Spawner for TokioSpawner {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        tokio::spawn(fut);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Spawns onto the global async-std executor.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdSpawner;
+
+#[cfg(feature = "async-std")]
+impl Spawner for AsyncStdSpawner {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        async_std::task::spawn(fut);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+/// Spawns onto smol's global executor.
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolSpawner;
+
+#[cfg(feature = "smol")]
+impl Spawner for SmolSpawner {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        smol::spawn(fut).detach();
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            smol::Timer::after(duration).await;
+        })
+    }
+}
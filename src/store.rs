@@ -0,0 +1,235 @@
+use std::fmt;
+use std::future::Future;
+
+use crate::{Bucket, Policy, StoreError, Task};
+
+/// Somewhere a [`Bucket`] can be saved to and loaded from.
+pub trait Store<T> {
+    /// Returns the saved bucket, or `None` if nothing has been saved yet.
+    fn load(&self) -> impl Future<Output = Result<Option<Bucket<T>>, StoreError>> + Send;
+
+    fn save(&self, bucket: &Bucket<T>) -> impl Future<Output = Result<(), StoreError>> + Send;
+}
+
+/// A [`Bucket`] that survives restarts by going through a [`Store`].
+///
+/// The bucket is loaded on the first call and saved after every refresh.
+/// The policy always comes from here rather than from the store, so a
+/// changed policy applies to a previously saved bucket. If loading fails,
+/// or nothing was saved, a fresh bucket is used.
+///
+/// Every load and save error is passed to the error handler.
+pub struct PersistentBucket<T, S> {
+    policy: Policy,
+    store: S,
+    bucket: Option<Bucket<T>>,
+    on_error: Box<dyn FnMut(StoreError) + Send>,
+}
+
+impl<T, S> fmt::Debug for PersistentBucket<T, S>
+where
+    T: fmt::Debug,
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistentBucket")
+            .field("policy", &self.policy)
+            .field("store", &self.store)
+            .field("bucket", &self.bucket)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, S> PersistentBucket<T, S>
+where
+    S: Store<T>,
+{
+    pub fn new(
+        policy: Policy,
+        store: S,
+        on_error: impl FnMut(StoreError) + Send + 'static,
+    ) -> Self {
+        PersistentBucket {
+            policy,
+            store,
+            bucket: None,
+            on_error: Box::new(on_error),
+        }
+    }
+
+    /// See [`Bucket::call`].
+    pub async fn call<F, Fut>(&mut self, task: F) -> T
+    where
+        T: Clone,
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        self.load().await;
+        let bucket = self.bucket.as_mut().unwrap();
+        let refreshes = bucket.stats().refreshes;
+        let entry = bucket.call(task).await;
+        if bucket.stats().refreshes != refreshes {
+            if let Err(e) = self.store.save(bucket).await {
+                (self.on_error)(e);
+            }
+        }
+        entry
+    }
+
+    /// Stores `value` as a freshly refreshed entry and saves it, so that
+    /// other processes sharing the store see it.
+    ///
+    /// Unlike a save after a refresh, a failed save is returned rather than
+    /// passed to the error handler; the value is kept in memory either way.
+    pub async fn set(&mut self, value: T) -> Result<(), StoreError> {
+        self.load().await;
+        let bucket = self.bucket.as_mut().unwrap();
+        bucket.put(value);
+        self.store.save(bucket).await
+    }
+
+    /// Loads the bucket from the store on first use.
+    async fn load(&mut self) {
+        if self.bucket.is_none() {
+            let loaded = match self.store.load().await {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    (self.on_error)(e);
+                    None
+                }
+            };
+            let bucket = match loaded {
+                Some(mut bucket) => {
+                    bucket.policy = self.policy.clone();
+                    bucket
+                }
+                None => Bucket::new(self.policy.clone()),
+            };
+            self.bucket = Some(bucket);
+        }
+    }
+}
+
+/// Saves a bucket as JSON to a file.
+///
+/// Writes go to a temporary file next to `path` that is then renamed over
+/// it, so a crash never leaves a partially written bucket behind. The file
+/// is read and written with blocking i/o.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "serde")]
+impl FileStore {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> FileStore {
+        FileStore { path: path.into() }
+    }
+
+    fn load_sync<T>(&self) -> Result<Option<Bucket<T>>, StoreError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let json = match std::fs::read(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let bucket = serde_json::from_slice(&json).map_err(|e| StoreError::Format(e.into()))?;
+        Ok(Some(bucket))
+    }
+
+    fn save_sync<T>(&self, bucket: &Bucket<T>) -> Result<(), StoreError>
+    where
+        T: serde::Serialize,
+    {
+        let json = serde_json::to_vec(bucket).map_err(|e| StoreError::Format(e.into()))?;
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Store<T> for FileStore
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + Send,
+{
+    fn load(&self) -> impl Future<Output = Result<Option<Bucket<T>>, StoreError>> + Send {
+        std::future::ready(self.load_sync())
+    }
+
+    fn save(&self, bucket: &Bucket<T>) -> impl Future<Output = Result<(), StoreError>> + Send {
+        std::future::ready(self.save_sync(bucket))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn file_store_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("restart.json");
+        let policy = Policy::expire_within_counts(1);
+        let mut b =
+            PersistentBucket::new(policy.clone(), FileStore::new(&path), |e| panic!("{}", e));
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+
+        // Only refreshes are saved, so the second hit is forgotten.
+        let mut b = PersistentBucket::new(policy, FileStore::new(&path), |e| panic!("{}", e));
+        assert_eq!(b.call(|| async { 3 }).await, 1);
+        assert_eq!(b.call(|| async { 4 }).await, 4);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn errors_go_to_the_handler() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing-dir").join("bucket.json");
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut b = PersistentBucket::new(Policy::pierced(), FileStore::new(&path), {
+            let errors = errors.clone();
+            move |e| errors.lock().unwrap().push(e.to_string())
+        });
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(errors.lock().unwrap().len(), 1);
+
+        let path = dir.path().join("garbage.json");
+        std::fs::write(&path, "not json").unwrap();
+        let mut b = PersistentBucket::new(Policy::pierced(), FileStore::new(&path), {
+            let errors = errors.clone();
+            move |e| errors.lock().unwrap().push(e.to_string())
+        });
+        assert_eq!(b.call(|| async { 2 }).await, 2);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].starts_with("stored bucket is malformed"));
+    }
+
+    #[tokio::test]
+    async fn set_writes_through_to_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("set.json");
+        let policy = Policy::expire_within_counts(1);
+        let mut b =
+            PersistentBucket::new(policy.clone(), FileStore::new(&path), |e| panic!("{}", e));
+        b.call(|| async { 1 }).await;
+        b.set(2).await.unwrap();
+        assert_eq!(b.call(|| async { 3 }).await, 2);
+
+        let mut b = PersistentBucket::new(policy, FileStore::new(&path), |e| panic!("{}", e));
+        assert_eq!(b.call(|| async { 4 }).await, 2);
+
+        let path = dir.path().join("missing-dir").join("set.json");
+        let mut b = PersistentBucket::new(Policy::bottom_less(), FileStore::new(&path), |_| {});
+        assert!(b.set(5).await.is_err());
+        assert_eq!(b.call(|| async { 6 }).await, 5);
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use tokio::sync::watch;
+
+/// The sending side of [`Bucket::subscribe`](crate::Bucket::subscribe),
+/// created by the first subscriber.
+///
+/// The channel is type-erased so that buckets of values that are not
+/// `Sync` stay `Send`. A cloned bucket starts without subscribers of its
+/// own.
+pub(crate) struct Publisher<T> {
+    channel: OnceLock<Box<dyn Channel<T>>>,
+}
+
+trait Channel<T>: Send + Sync {
+    fn publish(&self, entry: Option<&T>);
+    fn subscribe(&self) -> watch::Receiver<Option<T>>;
+}
+
+impl<T> Channel<T> for watch::Sender<Option<T>>
+where
+    T: Clone + Send + Sync,
+{
+    fn publish(&self, entry: Option<&T>) {
+        self.send_replace(entry.cloned());
+    }
+
+    fn subscribe(&self) -> watch::Receiver<Option<T>> {
+        watch::Sender::subscribe(self)
+    }
+}
+
+impl<T> Publisher<T> {
+    pub(crate) fn subscribe(&self, current: Option<&T>) -> watch::Receiver<Option<T>>
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.channel
+            .get_or_init(|| Box::new(watch::Sender::new(current.cloned())))
+            .subscribe()
+    }
+
+    pub(crate) fn publish(&self, entry: Option<&T>) {
+        if let Some(channel) = self.channel.get() {
+            channel.publish(entry);
+        }
+    }
+}
+
+impl<T> Default for Publisher<T> {
+    fn default() -> Self {
+        Publisher {
+            channel: OnceLock::new(),
+        }
+    }
+}
+
+impl<T> Clone for Publisher<T> {
+    fn clone(&self) -> Self {
+        Publisher::default()
+    }
+}
+
+impl<T> fmt::Debug for Publisher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Publisher(..)")
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::future::Future;
+
+use crate::{Bucket, Policy, Store, StoreError, Task};
+
+/// A [`Bucket`] in memory in front of a [`Store`].
+///
+/// A call is served from memory while it is fresh. Otherwise the store is
+/// checked, and a saved bucket that is still fresh under this policy
+/// replaces the one in memory, so a value refreshed by another process is
+/// picked up without running the task. Only when neither is fresh does the
+/// task run, and its value is saved back to the store.
+///
+/// A call served from the store is saved back so that its pour counts for
+/// every process, but hits served from memory are not saved. A store that
+/// fails to load is treated as a miss; every load and save error is passed
+/// to the error handler.
+pub struct TieredBucket<T, S> {
+    policy: Policy,
+    memory: Bucket<T>,
+    store: S,
+    on_error: Box<dyn FnMut(StoreError) + Send>,
+}
+
+impl<T, S> fmt::Debug for TieredBucket<T, S>
+where
+    T: fmt::Debug,
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TieredBucket")
+            .field("memory", &self.memory)
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, S> TieredBucket<T, S>
+where
+    S: Store<T>,
+{
+    pub fn new(
+        policy: Policy,
+        store: S,
+        on_error: impl FnMut(StoreError) + Send + 'static,
+    ) -> Self {
+        TieredBucket {
+            memory: Bucket::new(policy.clone()),
+            policy,
+            store,
+            on_error: Box::new(on_error),
+        }
+    }
+
+    /// The bucket in memory.
+    pub fn bucket(&self) -> &Bucket<T> {
+        &self.memory
+    }
+
+    /// See [`Bucket::call`].
+    pub async fn call<F, Fut>(&mut self, task: F) -> T
+    where
+        T: Clone,
+        F: Task<Fut>,
+        Fut: Future<Output = T>,
+    {
+        let hydrated = !self.memory.is_fresh() && self.hydrate().await;
+        let refreshes = self.memory.stats().refreshes;
+        let entry = self.memory.call(task).await;
+        if hydrated || self.memory.stats().refreshes != refreshes {
+            if let Err(e) = self.store.save(&self.memory).await {
+                (self.on_error)(e);
+            }
+        }
+        entry
+    }
+
+    /// Stores `value` as a freshly refreshed entry in memory and saves it.
+    ///
+    /// A failed save is returned rather than passed to the error handler;
+    /// the value is kept in memory either way.
+    pub async fn set(&mut self, value: T) -> Result<(), StoreError> {
+        self.memory.put(value);
+        self.store.save(&self.memory).await
+    }
+
+    /// Replaces the bucket in memory with the saved one if that is fresh,
+    /// returning whether it did.
+    async fn hydrate(&mut self) -> bool {
+        let loaded = match self.store.load().await {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                (self.on_error)(e);
+                None
+            }
+        };
+        if let Some(mut bucket) = loaded {
+            bucket.policy = self.policy.clone();
+            if bucket.is_fresh() {
+                self.memory = bucket;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A store shared by every clone, which fails while `broken` is set.
+    #[derive(Debug, Clone, Default)]
+    struct MemoryStore {
+        saved: Arc<Mutex<Option<Bucket<u32>>>>,
+        broken: Arc<Mutex<bool>>,
+    }
+
+    impl MemoryStore {
+        fn check(&self) -> Result<(), StoreError> {
+            if *self.broken.lock().unwrap() {
+                return Err(std::io::Error::other("broken").into());
+            }
+            Ok(())
+        }
+    }
+
+    impl Store<u32> for MemoryStore {
+        fn load(&self) -> impl Future<Output = Result<Option<Bucket<u32>>, StoreError>> + Send {
+            let loaded = self.check().map(|_| self.saved.lock().unwrap().clone());
+            std::future::ready(loaded)
+        }
+
+        fn save(
+            &self,
+            bucket: &Bucket<u32>,
+        ) -> impl Future<Output = Result<(), StoreError>> + Send {
+            let saved = self
+                .check()
+                .map(|_| *self.saved.lock().unwrap() = Some(bucket.clone()));
+            std::future::ready(saved)
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_then_store_then_task() {
+        let store = MemoryStore::default();
+        let policy = Policy::expire_within_counts(1);
+        let mut a = TieredBucket::new(policy.clone(), store.clone(), |e| panic!("{}", e));
+        assert_eq!(a.call(|| async { 1 }).await, 1);
+        assert_eq!(
+            store.saved.lock().unwrap().as_ref().unwrap().cached(),
+            Some(&1)
+        );
+
+        // Another replica picks up the saved value instead of running its task.
+        let mut b = TieredBucket::new(policy, store.clone(), |e| panic!("{}", e));
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(b.call(|| async { 3 }).await, 3);
+        assert_eq!(a.call(|| async { 4 }).await, 1);
+        assert_eq!(a.call(|| async { 5 }).await, 3);
+    }
+
+    #[tokio::test]
+    async fn stale_store_runs_the_task() {
+        let store = MemoryStore::default();
+        let mut saved = Bucket::new(Policy::bottom_less());
+        saved.call_sync(|| 1);
+        *store.saved.lock().unwrap() = Some(saved);
+
+        // The saved bucket is judged by this policy, under which it is spent.
+        let mut b = TieredBucket::new(Policy::pierced(), store.clone(), |e| panic!("{}", e));
+        assert_eq!(b.call(|| async { 2 }).await, 2);
+        assert_eq!(
+            store.saved.lock().unwrap().as_ref().unwrap().cached(),
+            Some(&2)
+        );
+    }
+
+    #[tokio::test]
+    async fn store_errors_are_misses() {
+        let store = MemoryStore::default();
+        *store.broken.lock().unwrap() = true;
+        let errors = Arc::new(Mutex::new(0));
+        let mut b = TieredBucket::new(Policy::expire_within_counts(1), store.clone(), {
+            let errors = errors.clone();
+            move |_| *errors.lock().unwrap() += 1
+        });
+        assert_eq!(b.call(|| async { 1 }).await, 1);
+        assert_eq!(b.call(|| async { 2 }).await, 1);
+        assert_eq!(*errors.lock().unwrap(), 2);
+        assert!(store.saved.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! Serialization formats for the wall-clock [`Instant`](crate::Instant) of
+//! `chrono-clock`, for use with `#[serde(with = "...")]`.
+//!
+//! Buckets write their instants in [`rfc3339`], chrono's own format, unless
+//! the `serde-ts-millis` feature switches them to [`millis`].
+//!
+//! # Example
+//! ```
+//! use uchimizu::Instant;
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Audit {
+//!     #[serde(with = "uchimizu::timestamp::millis")]
+//!     at: Instant,
+//! }
+//!
+//! let json = r#"{"at":1700000000123}"#;
+//! let audit: Audit = serde_json::from_str(json).unwrap();
+//! assert_eq!(audit.at.timestamp_millis(), 1_700_000_000_123);
+//! assert_eq!(serde_json::to_string(&audit).unwrap(), json);
+//! ```
+
+/// Milliseconds since the Unix epoch, as an integer.
+///
+/// Anything finer than a millisecond is truncated, so a restored bucket
+/// may count its value as up to a millisecond older, never younger. An
+/// [`rfc3339`] string is read as well, so that snapshots taken before
+/// switching formats still load; this needs a self-describing format such
+/// as JSON.
+pub mod millis {
+    use core::fmt;
+
+    use serde::{de, Deserializer, Serializer};
+
+    use crate::Instant;
+
+    pub fn serialize<S: Serializer>(instant: &Instant, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i64(instant.timestamp_millis())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Instant, D::Error> {
+        d.deserialize_any(Visitor)
+    }
+
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = Instant;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("milliseconds since the Unix epoch")
+        }
+
+        fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Instant, E> {
+            Instant::from_timestamp_millis(millis)
+                .ok_or_else(|| E::custom(format_args!("timestamp {} ms is out of range", millis)))
+        }
+
+        fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Instant, E> {
+            match i64::try_from(millis) {
+                Ok(millis) => self.visit_i64(millis),
+                Err(_) => Err(E::custom(format_args!(
+                    "timestamp {} ms is out of range",
+                    millis
+                ))),
+            }
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<Instant, E> {
+            super::rfc3339::parse(s)
+        }
+    }
+}
+
+/// An RFC 3339 string in UTC, with as many fractional digits as needed.
+pub mod rfc3339 {
+    use chrono::SecondsFormat;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::Instant;
+
+    pub fn serialize<S: Serializer>(instant: &Instant, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&instant.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Instant, D::Error> {
+        parse(&<std::borrow::Cow<'de, str>>::deserialize(d)?)
+    }
+
+    pub(super) fn parse<E: de::Error>(s: &str) -> Result<Instant, E> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .map(|t| t.to_utc())
+            .map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{millis_duration, Bucket, Policy};
+
+    #[test]
+    fn millis_round_trip_keeps_expiry() {
+        let mut b = Policy::expire_within_secs(10).into_bucket();
+        b.call_sync(|| 1);
+        b.initiate = chrono::DateTime::from_timestamp_millis(b.initiate.timestamp_millis())
+            .unwrap()
+            - millis_duration(4_000);
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Millis(#[serde(with = "super::millis")] crate::Instant);
+        let json = serde_json::to_string(&Millis(b.initiate)).unwrap();
+        assert_eq!(json, b.initiate.timestamp_millis().to_string());
+        let restored = serde_json::from_str::<Millis>(&json).unwrap().0;
+        assert_eq!(restored, b.initiate);
+        let rfc3339 = format!("\"{}\"", b.initiate.to_rfc3339());
+        let restored = serde_json::from_str::<Millis>(&rfc3339).unwrap().0;
+        assert_eq!(restored, b.initiate);
+        assert!(serde_json::from_str::<Millis>(&i64::MAX.to_string()).is_err());
+
+        let json = serde_json::to_string(&b).unwrap();
+        let restored: Bucket<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, b);
+        assert_eq!(restored.expires_at(), b.expires_at());
+    }
+
+    #[test]
+    fn rfc3339_reads_any_offset() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Rfc3339(#[serde(with = "super::rfc3339")] crate::Instant);
+        let t = serde_json::from_str::<Rfc3339>(r#""2024-01-02T12:00:00.5+09:00""#)
+            .unwrap()
+            .0;
+        assert_eq!(
+            serde_json::to_string(&Rfc3339(t)).unwrap(),
+            r#""2024-01-02T03:00:00.500Z""#
+        );
+        assert!(serde_json::from_str::<Rfc3339>(r#""yesterday""#).is_err());
+    }
+}
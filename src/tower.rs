@@ -0,0 +1,302 @@
+use std::future::poll_fn;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll};
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{BoxFuture, BucketMap, Error, Policy};
+
+/// Wraps services in a [`Cache`], see there.
+///
+/// Each service it wraps gets a cache of its own.
+pub struct CacheLayer<K, R, F> {
+    policy: Policy,
+    key: Arc<F>,
+    error_policy: Option<Policy>,
+    capacity: Option<usize>,
+    _entry: PhantomData<fn() -> (K, R)>,
+}
+
+impl<K, R, F> CacheLayer<K, R, F> {
+    /// Caches responses per key of `key(&request)`, each for as long as
+    /// `policy` allows.
+    pub fn new<Req>(policy: Policy, key: F) -> Self
+    where
+        F: Fn(&Req) -> K,
+    {
+        CacheLayer {
+            policy,
+            key: Arc::new(key),
+            error_policy: None,
+            capacity: None,
+            _entry: PhantomData,
+        }
+    }
+
+    /// Caches errors of the inner service for as long as `policy` allows,
+    /// see [`Bucket::with_error_policy`](crate::Bucket::with_error_policy).
+    /// Without it, errors are never cached.
+    pub fn with_error_policy(mut self, policy: Policy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
+
+    /// See [`BucketMap::with_capacity`].
+    pub fn with_capacity(mut self, max_entries: usize) -> Self {
+        self.capacity = Some(max_entries);
+        self
+    }
+}
+
+impl<K, R, F> Clone for CacheLayer<K, R, F> {
+    fn clone(&self) -> Self {
+        CacheLayer {
+            policy: self.policy.clone(),
+            key: self.key.clone(),
+            error_policy: self.error_policy.clone(),
+            capacity: self.capacity,
+            _entry: PhantomData,
+        }
+    }
+}
+
+impl<S, K, R, F> Layer<S> for CacheLayer<K, R, F>
+where
+    K: Eq + Hash,
+{
+    type Service = Cache<S, K, R, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let mut map = BucketMap::new(self.policy.clone());
+        if let Some(capacity) = self.capacity {
+            map = map.with_capacity(capacity);
+        }
+        Cache {
+            inner,
+            key: self.key.clone(),
+            map: Arc::new(Mutex::new(map)),
+            error_policy: self.error_policy.clone(),
+        }
+    }
+}
+
+/// A [`Service`] that serves the responses of an inner service from a
+/// [`BucketMap`] while their policy is remaining.
+///
+/// A request is only passed on when its key has no fresh response, and
+/// only then does the inner service have to be ready: [`Service::poll_ready`]
+/// is always ready, and a call that refreshes waits for the readiness of a
+/// clone of the inner service. Calls for different keys run in parallel,
+/// but concurrent calls for the same expired key each reach the inner
+/// service.
+///
+/// Errors of the inner service are returned as [`Error::Task`], and are
+/// not cached unless an error policy is set with
+/// [`CacheLayer::with_error_policy`]; a call short-circuited by it returns
+/// [`Error::NegativeCached`].
+///
+/// # Example
+/// ```
+/// use std::future::poll_fn;
+/// use uchimizu::{CacheLayer, Policy};
+/// use tower_layer::Layer;
+/// use tower_service::Service;
+///
+/// #[derive(Clone)]
+/// struct Lookup;
+///
+/// impl Service<u32> for Lookup {
+///     type Response = String;
+///     type Error = std::convert::Infallible;
+///     type Future = std::future::Ready<Result<String, Self::Error>>;
+///
+///     fn poll_ready(&mut self, _: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+///         std::task::Poll::Ready(Ok(()))
+///     }
+///
+///     fn call(&mut self, id: u32) -> Self::Future {
+///         std::future::ready(Ok(format!("user-{}", id)))
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let layer = CacheLayer::new(Policy::expire_within_secs(60), |id: &u32| *id);
+/// let mut users = layer.layer(Lookup);
+/// poll_fn(|cx| users.poll_ready(cx)).await.unwrap();
+/// assert_eq!(users.call(7).await.unwrap(), "user-7");
+/// # }
+/// ```
+pub struct Cache<S, K, R, F> {
+    inner: S,
+    key: Arc<F>,
+    map: Arc<Mutex<BucketMap<K, R>>>,
+    error_policy: Option<Policy>,
+}
+
+impl<S, K, R, F> Cache<S, K, R, F> {
+    /// Like [`CacheLayer::layer`](Layer::layer) of a new [`CacheLayer`].
+    pub fn new<Req>(inner: S, policy: Policy, key: F) -> Self
+    where
+        K: Eq + Hash,
+        F: Fn(&Req) -> K,
+    {
+        CacheLayer::new(policy, key).layer(inner)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+fn lock<K, R>(map: &Mutex<BucketMap<K, R>>) -> MutexGuard<'_, BucketMap<K, R>> {
+    map.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Clones share the same cache.
+impl<S: Clone, K, R, F> Clone for Cache<S, K, R, F> {
+    fn clone(&self) -> Self {
+        Cache {
+            inner: self.inner.clone(),
+            key: self.key.clone(),
+            map: self.map.clone(),
+            error_policy: self.error_policy.clone(),
+        }
+    }
+}
+
+impl<S, K, R, F, Req> Service<Req> for Cache<S, K, R, F>
+where
+    S: Service<Req, Response = R> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Send,
+    K: Eq + Hash + Clone + Send + 'static,
+    R: Clone + Send + 'static,
+    F: Fn(&Req) -> K,
+    Req: Send + 'static,
+{
+    type Response = R;
+    type Error = Error<S::Error>;
+    type Future = BoxFuture<'static, Result<R, Error<S::Error>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let key = (self.key)(&req);
+        let exhausted = {
+            let mut map = lock(&self.map);
+            let bucket = map.touch(&key);
+            if bucket.error_policy.is_none() {
+                bucket.error_policy = self.error_policy.clone();
+            }
+            if let Some(entry) = bucket.hit() {
+                bucket.observe(|o| o.on_hit());
+                return Box::pin(std::future::ready(Ok(entry)));
+            }
+            if let Some(result) = bucket.short_circuited() {
+                return Box::pin(std::future::ready(result));
+            }
+            let exhausted = bucket.miss();
+            bucket.observe(|o| o.on_miss());
+            exhausted
+        };
+        let mut inner = self.inner.clone();
+        let map = self.map.clone();
+        Box::pin(async move {
+            let result = match poll_fn(|cx| inner.poll_ready(cx)).await {
+                Ok(()) => inner.call(req).await,
+                Err(e) => Err(e),
+            };
+            let mut map = lock(&map);
+            let bucket = map.touch(&key);
+            let result = match result {
+                Ok(entry) => Ok(bucket.complete(entry, exhausted)),
+                Err(e) => bucket.recover_observed(e),
+            };
+            map.reweigh(&key);
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers `n * 10`, or fails for odd `n`, counting its calls.
+    #[derive(Clone, Default)]
+    struct Counting {
+        calls: Arc<AtomicUsize>,
+        polls: Arc<AtomicUsize>,
+    }
+
+    impl Service<u32> for Counting {
+        type Response = u32;
+        type Error = &'static str;
+        type Future = std::future::Ready<Result<u32, &'static str>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.polls.fetch_add(1, Ordering::SeqCst);
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, n: u32) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(if n.is_multiple_of(2) {
+                Ok(n * 10)
+            } else {
+                Err("odd")
+            })
+        }
+    }
+
+    async fn send<S: Service<u32>>(svc: &mut S, n: u32) -> Result<S::Response, S::Error> {
+        poll_fn(|cx| svc.poll_ready(cx)).await?;
+        svc.call(n).await
+    }
+
+    #[tokio::test]
+    async fn serves_hits_without_the_inner_service() {
+        let inner = Counting::default();
+        let mut svc = Cache::new(
+            inner.clone(),
+            Policy::expire_within_counts(10),
+            |n: &u32| *n,
+        );
+        for _ in 0..5 {
+            assert_eq!(send(&mut svc, 2).await, Ok(20));
+        }
+        assert_eq!(send(&mut svc.clone(), 4).await, Ok(40));
+        assert_eq!(send(&mut svc, 4).await, Ok(40));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(inner.polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn caches_errors_only_with_an_error_policy() {
+        let inner = Counting::default();
+        let mut svc = Cache::new(
+            inner.clone(),
+            Policy::expire_within_counts(10),
+            |n: &u32| *n,
+        );
+        assert_eq!(send(&mut svc, 1).await, Err(Error::Task("odd")));
+        assert_eq!(send(&mut svc, 1).await, Err(Error::Task("odd")));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        let inner = Counting::default();
+        let mut svc = CacheLayer::new(Policy::expire_within_counts(10), |n: &u32| *n)
+            .with_error_policy(Policy::expire_within_counts(1))
+            .layer(inner.clone());
+        assert_eq!(send(&mut svc, 1).await, Err(Error::Task("odd")));
+        assert_eq!(send(&mut svc, 1).await, Err(Error::NegativeCached));
+        assert_eq!(send(&mut svc, 1).await, Err(Error::Task("odd")));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,66 @@
+//! Wall-clock time for `chrono-clock`, read off the monotonic clock.
+//!
+//! The system clock is read once, and every later reading adds the
+//! monotonic time elapsed since then. Freshness decided within the process
+//! is thus immune to the system clock being stepped, while the instants
+//! buckets store and serialize remain wall-clock times that another process
+//! can compare with its own. The price is that a step of the system clock
+//! after the first reading is not followed.
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+#[cfg(not(feature = "tokio"))]
+type Monotonic = std::time::Instant;
+#[cfg(feature = "tokio")]
+type Monotonic = tokio::time::Instant;
+
+static ANCHOR: OnceLock<(DateTime<Utc>, Monotonic)> = OnceLock::new();
+
+pub(crate) fn now() -> DateTime<Utc> {
+    let (wall, since) = *ANCHOR.get_or_init(|| (Utc::now(), Monotonic::now()));
+    after(wall, since, Monotonic::now())
+}
+
+/// Returns `wall` moved on by the monotonic time from `since` to `now`,
+/// which never moves it back.
+fn after(wall: DateTime<Utc>, since: Monotonic, now: Monotonic) -> DateTime<Utc> {
+    TimeDelta::from_std(now.saturating_duration_since(since))
+        .ok()
+        .and_then(|elapsed| wall.checked_add_signed(elapsed))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn follows_the_monotonic_clock() {
+        let wall = Utc::now();
+        let since = Monotonic::now();
+        let later = since + Duration::from_millis(1500);
+        assert_eq!(
+            after(wall, since, later),
+            wall + TimeDelta::milliseconds(1500)
+        );
+        assert_eq!(after(wall, later, since), wall);
+        assert_eq!(
+            after(DateTime::<Utc>::MAX_UTC, since, later),
+            DateTime::<Utc>::MAX_UTC
+        );
+    }
+
+    #[test]
+    fn readings_never_go_back() {
+        let mut last = now();
+        for _ in 0..1000 {
+            let next = now();
+            assert!(next >= last);
+            last = next;
+        }
+        assert!((Utc::now() - last).abs() < TimeDelta::minutes(1));
+    }
+}
@@ -0,0 +1,24 @@
+//! Runs in a headless browser with `wasm-pack test --headless`, where
+//! `std::time::Instant::now` would panic.
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use uchimizu::Policy;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// 2020-01-01T00:00:00Z, in seconds since the Unix epoch.
+const YEAR_2020: u64 = 1_577_836_800;
+
+#[wasm_bindgen_test]
+fn reads_the_time_from_the_browser() {
+    let mut b = Policy::expire_within_secs(60).into_bucket();
+    assert_eq!(b.call_sync(|| 1), 1);
+    assert_eq!(b.call_sync(|| 2), 1);
+    assert!(b.is_fresh());
+    #[cfg(not(feature = "chrono-clock"))]
+    let refreshed = b.last_refreshed_at().since_epoch().as_secs();
+    #[cfg(feature = "chrono-clock")]
+    let refreshed = b.last_refreshed_at().timestamp() as u64;
+    assert!(refreshed > YEAR_2020);
+}
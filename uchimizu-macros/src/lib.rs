@@ -0,0 +1,238 @@
+//! Attribute macros for [uchimizu](https://docs.rs/uchimizu), re-exported
+//! from there with the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, FnArg, Ident, ItemFn, LitStr, Pat, ReturnType, Type};
+
+/// Caches the value of an `async fn` in a static bucket.
+///
+/// The function body becomes the loader. A function with parameters gets a
+/// bucket per distinct set of arguments; one without shares a single
+/// bucket. `policy` is written in the notation read by `Policy::from_str`
+/// and is parsed on the first call, which panics if it is invalid.
+///
+/// Each parameter must be a plain name. Its type must be `Hash + Eq +
+/// Clone`, or a shared reference to a `ToOwned` type whose owned form is,
+/// such as `&str`, in which case the owned form is kept as the key. The
+/// return type must be `Clone`. Methods, generic functions and `&mut` or
+/// `impl Trait` parameters are rejected.
+///
+/// # Example
+/// ```ignore
+/// #[uchimizu::memoize(policy = "secs:300")]
+/// async fn load_config(env: &str) -> Config {
+///     fetch_config(env).await
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn memoize(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut policy = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("policy") {
+            policy = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown memoize option, expected `policy`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let func = parse_macro_input!(item as ItemFn);
+    expand(policy, func)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A parameter of the memoized function and how its key is made.
+struct Param {
+    name: Ident,
+    key_ty: TokenStream2,
+    key: TokenStream2,
+}
+
+fn expand(policy: Option<LitStr>, func: ItemFn) -> syn::Result<TokenStream2> {
+    let policy = policy.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "memoize needs a policy, as in `#[memoize(policy = \"secs:300\")]`",
+        )
+    })?;
+    let sig = &func.sig;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            sig.fn_token.span(),
+            "memoize only supports `async fn`",
+        ));
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "memoize does not support generic functions",
+        ));
+    }
+    let params = sig
+        .inputs
+        .iter()
+        .map(param)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &sig.ident;
+    let ret = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let block = &func.block;
+    let loader = Ident::new("__uchimizu_load", Span::mixed_site());
+    let names = params.iter().map(|p| &p.name).collect::<Vec<_>>();
+    let outer_inputs = params.iter().zip(inputs).map(|(p, input)| {
+        let FnArg::Typed(input) = input else {
+            unreachable!()
+        };
+        let (name, ty) = (&p.name, &input.ty);
+        quote!(#name: #ty)
+    });
+    let make_policy = quote! {
+        #policy.parse::<::uchimizu::Policy>().unwrap_or_else(|e| {
+            ::std::panic!("invalid policy for `{}`: {}", ::std::stringify!(#name), e)
+        })
+    };
+    let body = if params.is_empty() {
+        quote! {
+            static CACHE: ::std::sync::OnceLock<::uchimizu::SharedBucket<#ret>> =
+                ::std::sync::OnceLock::new();
+            CACHE
+                .get_or_init(|| #make_policy.into_shared_bucket())
+                .call(#loader)
+                .await
+        }
+    } else {
+        let key_tys = params.iter().map(|p| &p.key_ty);
+        let keys = params.iter().map(|p| &p.key);
+        quote! {
+            static CACHE: ::std::sync::OnceLock<
+                ::uchimizu::ConcurrentBucketMap<(#(#key_tys,)*), #ret>,
+            > = ::std::sync::OnceLock::new();
+            CACHE
+                .get_or_init(|| ::uchimizu::ConcurrentBucketMap::new(#make_policy))
+                .call((#(#keys,)*), |_| #loader(#(#names),*))
+                .await
+        }
+    };
+    Ok(quote! {
+        #(#attrs)*
+        #vis async fn #name(#(#outer_inputs),*) #output {
+            async fn #loader(#inputs) #output #block
+            #body
+        }
+    })
+}
+
+fn param(input: &FnArg) -> syn::Result<Param> {
+    let input = match input {
+        FnArg::Receiver(receiver) => {
+            return Err(syn::Error::new(
+                receiver.span(),
+                "memoize does not support methods",
+            ))
+        }
+        FnArg::Typed(input) => input,
+    };
+    let Pat::Ident(pat) = &*input.pat else {
+        return Err(syn::Error::new(
+            input.pat.span(),
+            "memoize needs a plain name for each parameter",
+        ));
+    };
+    let name = pat.ident.clone();
+    match &*input.ty {
+        Type::Reference(r) if r.mutability.is_some() => Err(syn::Error::new(
+            r.span(),
+            "memoize does not support `&mut` parameters",
+        )),
+        Type::Reference(r) => {
+            let ty = &r.elem;
+            Ok(Param {
+                key_ty: quote!(<#ty as ::std::borrow::ToOwned>::Owned),
+                key: quote!(::std::borrow::ToOwned::to_owned(#name)),
+                name,
+            })
+        }
+        Type::ImplTrait(ty) => Err(syn::Error::new(
+            ty.span(),
+            "memoize does not support `impl Trait` parameters",
+        )),
+        ty => Ok(Param {
+            key_ty: quote!(#ty),
+            key: quote!(::std::clone::Clone::clone(&#name)),
+            name,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn error(func: ItemFn) -> String {
+        let policy = Some(parse_quote!("secs:300"));
+        expand(policy, func).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_unsupported_signatures() {
+        assert_eq!(
+            error(parse_quote!(
+                async fn f(v: &mut Vec<u8>) {}
+            )),
+            "memoize does not support `&mut` parameters"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                fn f() {}
+            )),
+            "memoize only supports `async fn`"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                async fn f<T>(t: T) {}
+            )),
+            "memoize does not support generic functions"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                async fn f(&self) {}
+            )),
+            "memoize does not support methods"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                async fn f((a, b): (u8, u8)) {}
+            )),
+            "memoize needs a plain name for each parameter"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                async fn f(t: impl Clone) {}
+            )),
+            "memoize does not support `impl Trait` parameters"
+        );
+    }
+
+    #[test]
+    fn requires_a_policy() {
+        let func = parse_quote!(
+            async fn f() {}
+        );
+        assert!(expand(None, func)
+            .unwrap_err()
+            .to_string()
+            .starts_with("memoize needs a policy"));
+    }
+}